@@ -0,0 +1,225 @@
+//! Benchmarks for address origins.
+//!
+//! These use a synthetic set of about 410,000 address origins, a bit more
+//! than the set produced by validating all five RIR repositories, with
+//! about four in five of them for IPv4. Before running the benchmarks, the
+//! memory used by the set is printed.
+
+#[macro_use] extern crate bencher;
+extern crate routinator;
+extern crate rpki;
+
+use std::{env, mem};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use bencher::{Bencher, TestOpts, black_box, run_tests_console};
+use routinator::bgp::Announcement;
+use routinator::origins::{AddressOrigin, AddressOrigins, AddressPrefix};
+use routinator::validity::RouteValidity;
+use rpki::asres::AsId;
+
+
+//------------ Test Data -----------------------------------------------------
+
+const V4_ORIGINS: usize = 330_000;
+const V6_ORIGINS: usize = 80_000;
+const PROBES: usize = 1_000;
+const CHANGES: usize = 1_000;
+
+/// A simple linear congruential generator for reproducible data.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(6_364_136_223_846_793_005)
+                       .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 32) as u32
+    }
+
+    fn v4_prefix(&mut self) -> AddressPrefix {
+        let len = 12 + (self.next() % 13) as u8;
+        let addr = self.next() & !(::std::u32::MAX >> len);
+        AddressPrefix::new(IpAddr::V4(Ipv4Addr::from(addr)), len)
+    }
+
+    fn v6_prefix(&mut self) -> AddressPrefix {
+        let len = 19 + (self.next() % 30) as u8;
+        let addr = 0x2000_0000_0000_0000u64 | (
+            (u64::from(self.next()) << 32 | u64::from(self.next()))
+            >> 3
+        );
+        let addr = addr & !(::std::u64::MAX >> len);
+        AddressPrefix::new(
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) << 64)), len
+        )
+    }
+
+    fn asn(&mut self) -> AsId {
+        AsId::from(64_496 + self.next() % 1024)
+    }
+}
+
+fn make_origins() -> Vec<AddressOrigin> {
+    let mut random = Random(0x5eed);
+    let mut res = Vec::with_capacity(V4_ORIGINS + V6_ORIGINS);
+    for _ in 0..V4_ORIGINS {
+        let prefix = random.v4_prefix();
+        let max_len = prefix.address_length() + (random.next() % 2) as u8;
+        res.push(AddressOrigin::new(random.asn(), prefix, max_len));
+    }
+    for _ in 0..V6_ORIGINS {
+        let prefix = random.v6_prefix();
+        let max_len = prefix.address_length() + (random.next() % 2) as u8;
+        res.push(AddressOrigin::new(random.asn(), prefix, max_len));
+    }
+    res
+}
+
+fn make_set() -> AddressOrigins {
+    AddressOrigins::try_from_iter(make_origins()).unwrap()
+}
+
+fn make_set_from(origins: &[AddressOrigin]) -> AddressOrigins {
+    AddressOrigins::try_from_iter(origins.iter().cloned()).unwrap()
+}
+
+/// Returns a copy of `origins` with `CHANGES` origins replaced.
+fn make_changed(origins: &[AddressOrigin]) -> Vec<AddressOrigin> {
+    let mut random = Random(0xd1ff);
+    let mut res = origins.to_vec();
+    for _ in 0..CHANGES {
+        let idx = random.next() as usize % res.len();
+        let prefix = random.v4_prefix();
+        res[idx] = AddressOrigin::new(
+            random.asn(), prefix, prefix.address_length()
+        );
+    }
+    res
+}
+
+/// Returns announcements half of which are more specifics of origins.
+fn make_probes(origins: &AddressOrigins) -> Vec<Announcement> {
+    let mut random = Random(0xcafe);
+    let mut res = Vec::with_capacity(PROBES);
+    for i in 0..PROBES {
+        let prefix = if i % 2 == 0 {
+            let idx = random.next() as usize % origins.len();
+            origins.get(idx).unwrap().prefix()
+        }
+        else if i % 4 == 1 {
+            random.v4_prefix()
+        }
+        else {
+            random.v6_prefix()
+        };
+        res.push(Announcement::new(prefix, random.asn()))
+    }
+    res
+}
+
+
+//------------ Benchmarks ----------------------------------------------------
+
+fn build_index(bench: &mut Bencher) {
+    let origins = make_origins();
+    bench.iter(|| {
+        AddressOrigins::try_from_iter(origins.iter().cloned()).unwrap()
+    })
+}
+
+fn diff_merge(bench: &mut Bencher) {
+    let origins = make_origins();
+    let current = make_set_from(&origins);
+    let next = make_set_from(&make_changed(&origins));
+    bench.iter(|| {
+        black_box(current.diff(&next))
+    })
+}
+
+fn diff_hashset(bench: &mut Bencher) {
+    let origins = make_origins();
+    let current = make_set_from(&origins);
+    let next = make_set_from(&make_changed(&origins));
+    bench.iter(|| {
+        let mut current: HashSet<_> = current.iter().collect();
+        let mut announce = Vec::new();
+        for addr in next.iter() {
+            if !current.remove(&addr) {
+                announce.push(addr)
+            }
+        }
+        let withdraw: Vec<_> = current.into_iter().collect();
+        black_box((announce, withdraw))
+    })
+}
+
+fn validate_indexed(bench: &mut Bencher) {
+    let origins = make_set();
+    let probes = make_probes(&origins);
+    bench.iter(|| {
+        for probe in &probes {
+            black_box(origins.validate(*probe).state());
+        }
+    })
+}
+
+fn validate_linear(bench: &mut Bencher) {
+    let origins = make_set();
+    let probes = make_probes(&origins);
+    bench.iter(|| {
+        for probe in &probes {
+            black_box(RouteValidity::new(*probe, origins.iter()).state());
+        }
+    })
+}
+
+fn covered_indexed(bench: &mut Bencher) {
+    let origins = make_set();
+    let probes = make_probes(&origins);
+    bench.iter(|| {
+        for probe in &probes {
+            black_box(origins.covered(probe.prefix).len());
+        }
+    })
+}
+
+fn covered_linear(bench: &mut Bencher) {
+    let origins = make_set();
+    let probes = make_probes(&origins);
+    bench.iter(|| {
+        for probe in &probes {
+            black_box(origins.iter().filter(|origin| {
+                probe.prefix.covers(origin.prefix())
+            }).count());
+        }
+    })
+}
+
+benchmark_group!(
+    benches,
+    build_index, diff_merge, diff_hashset, validate_indexed,
+    validate_linear, covered_indexed, covered_linear
+);
+
+
+//------------ Main ----------------------------------------------------------
+
+/// Prints the memory used by the set and by a plain list of its origins.
+///
+/// The list only counts the origins themselves, not any heap allocations
+/// they refer to.
+fn print_memory() {
+    let origins = make_set();
+    let list = origins.len() * mem::size_of::<AddressOrigin>();
+    println!(
+        "memory for {} origins: {} bytes compact, {} bytes as a list\n",
+        origins.len(), origins.memory_size(), list
+    );
+}
+
+fn main() {
+    print_memory();
+    let mut opts = TestOpts::default();
+    opts.filter = env::args().skip(1).find(|arg| *arg != "--bench");
+    run_tests_console(&opts, benches()).unwrap();
+}
@@ -0,0 +1,189 @@
+//! Replacing files atomically.
+//!
+//! Files that are read by others while we may be rewriting them – output
+//! files, the RTR state file, or TALs – should never be seen half
+//! written. [`AtomicFile`] takes care of this by writing to a temporary
+//! file first and renaming it into place once everything went well.
+//!
+//! [`AtomicFile`]: struct.AtomicFile.html
+
+use std::{fs, io, process};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+//------------ AtomicFile ----------------------------------------------------
+
+/// A file that is replaced atomically.
+///
+/// The content is written to a temporary file next to the target file
+/// which is renamed to the target once everything has been written. This
+/// way, anyone reading the target file always sees complete content.
+///
+/// The temporary file gets a unique name so that several writers for the
+/// same target don’t trip over each other. If the target already exists,
+/// the temporary file is given the target’s permissions.
+pub struct AtomicFile {
+    file: File,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    /// Starts replacing the file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = path.as_ref().to_path_buf();
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput, "not a file path"
+                ))
+            }
+        };
+        let (file, tmp) = loop {
+            let tmp = path.with_file_name(format!(
+                ".{}.{}.{}.tmp",
+                name, process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = OpenOptions::new()
+                .write(true).create_new(true).open(&tmp);
+            match file {
+                Ok(file) => break (file, tmp),
+                Err(err) => {
+                    if err.kind() != io::ErrorKind::AlreadyExists {
+                        return Err(err)
+                    }
+                }
+            }
+        };
+        let res = AtomicFile { file, tmp, path };
+        if let Ok(metadata) = fs::metadata(&res.path) {
+            let perms = metadata.permissions();
+            if let Err(err) = res.file.set_permissions(perms) {
+                res.abort();
+                return Err(err)
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the temporary file to write the content to.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Replaces the target file with what has been written.
+    ///
+    /// If this fails, the temporary file is removed and the target file
+    /// is left unchanged.
+    pub fn commit(self) -> Result<(), io::Error> {
+        let res = self.file.sync_all().and_then(|_| {
+            fs::rename(&self.tmp, &self.path)
+        });
+        if res.is_err() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+        res
+    }
+
+    /// Abandons writing, leaving the target file unchanged.
+    pub fn abort(self) {
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::{Read, Write};
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "routinator-test-{}-{}", process::id(), name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        let mut res = String::new();
+        File::open(path).unwrap().read_to_string(&mut res).unwrap();
+        res
+    }
+
+    #[test]
+    fn atomic_file_commit_replaces_target() {
+        let dir = test_dir("commit");
+        let path = dir.join("out.txt");
+        fs::write(&path, b"old").unwrap();
+        let file = AtomicFile::create(&path).unwrap();
+        file.file().write_all(b"new").unwrap();
+        assert_eq!(read(&path), "old");
+        file.commit().unwrap();
+        assert_eq!(read(&path), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn atomic_file_uses_unique_temp_files() {
+        let dir = test_dir("unique");
+        let path = dir.join("out.txt");
+        let first = AtomicFile::create(&path).unwrap();
+        let second = AtomicFile::create(&path).unwrap();
+        assert_ne!(first.tmp, second.tmp);
+        first.file().write_all(b"first").unwrap();
+        second.file().write_all(b"second").unwrap();
+        first.commit().unwrap();
+        assert_eq!(read(&path), "first");
+        second.commit().unwrap();
+        assert_eq!(read(&path), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_file_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("permissions");
+        let path = dir.join("out.txt");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640))
+            .unwrap();
+        AtomicFile::create(&path).unwrap().commit().unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn atomic_file_abort_keeps_target() {
+        let dir = test_dir("abort");
+        let path = dir.join("out.txt");
+        fs::write(&path, b"old").unwrap();
+        let file = AtomicFile::create(&path).unwrap();
+        file.file().write_all(b"new").unwrap();
+        file.abort();
+        assert_eq!(read(&path), "old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let path = dir.join("missing.txt");
+        AtomicFile::create(&path).unwrap().abort();
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
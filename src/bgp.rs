@@ -0,0 +1,230 @@
+//! BGP route announcements.
+//!
+//! Some of the analyses Routinator can perform need to know which routes
+//! are actually announced in BGP. This module provides the type
+//! [`Announcements`] which holds such a list of routes, each consisting of
+//! an address prefix and an origin AS number.
+//!
+//! Announcements are read from a simple text format with one announcement
+//! per line. Each line contains the prefix in the usual slash notation
+//! followed by white space and the AS number, either as a plain number or
+//! with a prefix of `AS`. Empty lines and lines starting with `#` are
+//! ignored.
+//!
+//! Alternatively, announcements can be taken from a RIB dump in MRT format
+//! via the [`mrt`] module.
+//!
+//! [`Announcements`]: struct.Announcements.html
+//! [`mrt`]: ../mrt/index.html
+
+use std::{io, slice};
+use std::fs::File;
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+use rpki::asres::AsId;
+use super::mrt::{self, MrtError};
+use super::origins::{AddressPrefix, parse_as_id};
+
+
+//------------ Announcement --------------------------------------------------
+
+/// A single route announcement.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Announcement {
+    /// The announced prefix.
+    pub prefix: AddressPrefix,
+
+    /// The origin AS of the announcement.
+    pub origin: AsId,
+}
+
+impl Announcement {
+    pub fn new(prefix: AddressPrefix, origin: AsId) -> Self {
+        Announcement { prefix, origin }
+    }
+}
+
+impl FromStr for Announcement {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let prefix = match words.next() {
+            Some(prefix) => prefix,
+            None => return Err(ParseError::Missing)
+        };
+        let prefix = AddressPrefix::from_str(prefix)
+            .map_err(|_| ParseError::BadPrefix(prefix.into()))?;
+        let origin = match words.next() {
+            Some(origin) => origin,
+            None => return Err(ParseError::Missing)
+        };
+        let origin = parse_as_id(origin)
+            .ok_or_else(|| ParseError::BadAsn(origin.into()))?;
+        if words.next().is_some() {
+            return Err(ParseError::Trailing)
+        }
+        Ok(Announcement::new(prefix, origin))
+    }
+}
+
+
+//------------ Announcements -------------------------------------------------
+
+/// A list of route announcements.
+#[derive(Clone, Debug, Default)]
+pub struct Announcements(Vec<Announcement>);
+
+impl Announcements {
+    /// Creates a new, empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads announcements from the text file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let file = File::open(path)?;
+        Self::from_reader(io::BufReader::new(file))
+    }
+
+    /// Loads announcements in text form from a reader.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, LoadError> {
+        let mut res = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            match Announcement::from_str(line) {
+                Ok(item) => res.push(item),
+                Err(err) => return Err(LoadError::Parse(idx + 1, err))
+            }
+        }
+        Ok(Announcements(res))
+    }
+
+    /// Loads announcements from the MRT RIB dump at `path`.
+    pub fn from_mrt_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let file = File::open(path)?;
+        Self::from_mrt_reader(io::BufReader::new(file))
+    }
+
+    /// Loads announcements from an MRT RIB dump provided by a reader.
+    pub fn from_mrt_reader<R: io::Read>(
+        mut reader: R
+    ) -> Result<Self, LoadError> {
+        let mut res = Announcements::new();
+        mrt::read_announcements(&mut reader, &mut res)?;
+        Ok(res)
+    }
+
+    /// Adds an announcement to the end of the list.
+    pub fn push(&mut self, announcement: Announcement) {
+        self.0.push(announcement)
+    }
+
+    /// Returns the number of announcements.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the announcements.
+    pub fn iter(&self) -> slice::Iter<Announcement> {
+        self.0.iter()
+    }
+}
+
+
+//------------ ParseError ----------------------------------------------------
+
+#[derive(Clone, Debug, Fail)]
+pub enum ParseError {
+    #[fail(display="expected prefix and AS number")]
+    Missing,
+
+    #[fail(display="bad prefix {}", _0)]
+    BadPrefix(String),
+
+    #[fail(display="bad AS number {}", _0)]
+    BadAsn(String),
+
+    #[fail(display="trailing data")]
+    Trailing,
+}
+
+
+//------------ LoadError -----------------------------------------------------
+
+#[derive(Debug, Fail)]
+pub enum LoadError {
+    #[fail(display="{}", _0)]
+    Io(io::Error),
+
+    #[fail(display="line {}: {}", _0, _1)]
+    Parse(usize, ParseError),
+
+    #[fail(display="{}", _0)]
+    Mrt(MrtError),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<MrtError> for LoadError {
+    fn from(err: MrtError) -> LoadError {
+        LoadError::Mrt(err)
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_announcements() {
+        let data = b"# Comment\n\
+                     10.0.0.0/8 AS64496\n\
+                     \n\
+                     2001:db8::/32    64497\n";
+        let res = Announcements::from_reader(&data[..]).unwrap();
+        assert_eq!(
+            res.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Announcement::new(
+                    "10.0.0.0/8".parse().unwrap(), AsId::from(64496)
+                ),
+                Announcement::new(
+                    "2001:db8::/32".parse().unwrap(), AsId::from(64497)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_reject_bad_lines() {
+        for line in &[
+            &b"10.0.0.0/8\n"[..], b"10.0.0.0 AS1\n", b"10.0.0.0/8 ASx\n",
+            b"10.0.0.0/8 AS1 AS2\n", b"10.0.0.0/33 AS1\n",
+            b"10.0.0.0/255 AS1\n", b"2001:db8::/129 AS1\n"
+        ] {
+            match Announcements::from_reader(*line) {
+                Err(LoadError::Parse(1, _)) => { }
+                res => panic!("{:?} parsed as {:?}", line, res)
+            }
+        }
+    }
+}
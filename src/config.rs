@@ -6,9 +6,14 @@ use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
-use clap::{App, Arg, ArgMatches};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use dirs::home_dir;
 use log::LevelFilter;
+use rpki::asres::AsId;
+use super::bgp::Announcement;
+use super::origins::{AddressPrefix, parse_as_id};
+use super::output::{AsnFormat, OutputOptions, OutputRegistry, Selection};
+use super::tals::TalDir;
 
 
 //------------ Config --------------------------------------------------------
@@ -25,20 +30,27 @@ pub struct Config {
     /// Path to the optional local exceptions file.
     pub exceptions: Option<PathBuf>,
 
+    /// The command to execute.
+    pub command: Command,
+
     /// Expected mode of operation.
     pub mode: RunMode,
 
-    /// Path to the output file.
-    ///
-    /// If this is `None`, we are supposed to output to stdout. 
-    pub output: Option<PathBuf>,
+    /// The outputs to produce.
+    pub outputs: Vec<OutputTarget>,
 
-    /// Format for output to a file.
-    pub outform: OutputFormat,
+    /// Format specific options for the outputs.
+    pub output_options: OutputOptions,
+
+    /// The route origins to include in the output.
+    pub selection: Selection,
 
     /// Should we do strict validation?
     pub strict: bool,
 
+    /// Should we continue without broken TALs?
+    pub skip_broken_tals: bool,
+
     /// Should we update the repository cache?
     pub update: bool,
 
@@ -58,14 +70,146 @@ pub struct Config {
     /// How many diffs to keep in the history.
     pub history_size: usize,
 
+    /// Path to the file for keeping the RTR state across restarts.
+    ///
+    /// If this is `None`, the state is not kept.
+    pub state_file: Option<PathBuf>,
+
+    /// A shell command to run after the output files have been updated.
+    ///
+    /// This is only used in repeat and daemon mode.
+    pub update_hook: Option<String>,
+
     /// Addresses to listen for RTR connections on.
     pub rtr_listen: Vec<SocketAddr>,
 }
 
 impl Config {
-    pub fn create() -> Self {
+    /// Creates the configuration from the command line arguments.
+    ///
+    /// Output formats are checked against those available in `registry`.
+    pub fn create(registry: &OutputRegistry) -> Self {
+        let matches = Self::app().get_matches();
+
+        if matches.is_present("man") {
+            let stdout = io::stdout();
+            let _ = stdout.lock().write_all(MAN_PAGE);
+            process::exit(0);
+        }
+
+        let cur_dir = match env::current_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                println!(
+                    "Fatal: cannot get current directory ({}). Aborting.",
+                    err
+                );
+                process::exit(1);
+            }
+        };
+
+        let listen = match matches.values_of("listen") {
+            Some(values) => {
+                let mut listen = Vec::new();
+                for val in values {
+                    match val.to_socket_addrs() {
+                        Ok(some) => listen.extend(some),
+                        Err(_) => {
+                            println!("Invalid socket address {}", val);
+                            process::exit(1);
+                        }
+                    }
+                }
+                listen
+            }
+            None => {
+                "127.0.0.1:3323".to_socket_addrs().unwrap().collect()
+            }
+        };
+
+        let (cache_dir, tal_dir) = Self::prepare_dirs(
+            &matches, &cur_dir
+        );
+
+        Config {
+            cache_dir,
+            tal_dir,
+            command: Command::from_matches(&matches, &cur_dir),
+            exceptions: matches.value_of("exceptions").map(|path| {
+                cur_dir.join(path)
+            }),
+            mode: if matches.is_present("daemon") {
+                RunMode::Daemon
+            }
+            else if matches.is_present("repeat") {
+                RunMode::Repeat
+            }
+            else {
+                RunMode::Once
+            },
+            outputs: Self::outputs_from_matches(&matches, &cur_dir, registry),
+            output_options: OutputOptions {
+                json_asn: match matches.value_of("json_asn") {
+                    Some("number") => Some(AsnFormat::Number),
+                    Some(_) => Some(AsnFormat::String),
+                    None => None,
+                },
+                bird_table: matches.value_of("bird_table").map(Into::into),
+                openbgpd_bare: matches.is_present("openbgpd_bare"),
+                iosxr_bgp_as: matches.value_of("iosxr_bgp_as").map(parse_asn),
+            },
+            selection: Self::selection_from_matches(&matches),
+            strict: matches.is_present("strict"),
+            skip_broken_tals: matches.is_present("skip_broken_tals"),
+            update: !matches.is_present("noupdate"),
+            process: !matches.is_present("noprocess"),
+            verbose: match matches.occurrences_of("verbose") {
+                0 => LevelFilter::Error,
+                1 => LevelFilter::Info,
+                _ => LevelFilter::Debug,
+            },
+            refresh: {
+                let value = matches.value_of("refresh").unwrap();
+                match u64::from_str(value) {
+                    Ok(some) => Duration::from_secs(some),
+                    Err(_) => {
+                        error!(
+                            "Invalid value '{}' for refresh argument.\
+                             Needs to be number of seconds.",
+                            value
+                        );
+                        process::exit(1);
+                    }
+                }
+            },
+            retry: Duration::from_secs(600),
+            expire: Duration::from_secs(7200),
+            history_size: {
+                let value = matches.value_of("history_size").unwrap();
+                match usize::from_str(value) {
+                    Ok(some) => some,
+                    Err(_) => {
+                        error!(
+                            "Invalid value '{}' for history argument. \
+                             Needs to be a number.",
+                            value
+                        );
+                        process::exit(1);
+                    }
+                }
+            },
+            state_file: matches.value_of("state_file").map(|path| {
+                cur_dir.join(path)
+            }),
+            update_hook: matches.value_of("update_hook").map(Into::into),
+            rtr_listen: listen,
+        }
+    }
+
+    /// Returns the command line parser.
+    fn app() -> App<'static, 'static> {
         // Remember to update the man page if you change things here!
-        let matches = App::new("Routinator")
+        App::new("Routinator")
             .version("0.1")
 
             .author(crate_authors!())
@@ -119,11 +263,67 @@ impl Config {
                  .short("f")
                  .long("outform")
                  .value_name("FORMAT")
-                 .possible_values(&["csv", "json", "rpsl", "none"])
                  //.help("sets the output format (csv, json, rpsl, none)")
                  .help("sets the output format")
                  .takes_value(true)
             )
+            .arg(Arg::with_name("output_to")
+                 .long("output-to")
+                 .value_name("FORMAT:FILE")
+                 .help("additionally writes output in a format to a file")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+            )
+            .arg(Arg::with_name("json_asn")
+                 .long("json-asn")
+                 .value_name("FORMAT")
+                 .possible_values(&["string", "number"])
+                 .help("sets the format of AS numbers in JSON output")
+                 .takes_value(true)
+            )
+            .arg(Arg::with_name("bird_table")
+                 .long("bird-table")
+                 .value_name("NAME")
+                 .help("name of the ROA table for BIRD output")
+                 .takes_value(true)
+            )
+            .arg(Arg::with_name("openbgpd_bare")
+                 .long("openbgpd-bare")
+                 .help("omit the roa-set block in OpenBGPD output")
+            )
+            .arg(Arg::with_name("iosxr_bgp_as")
+                 .long("iosxr-bgp-as")
+                 .value_name("ASN")
+                 .help("local AS for the router bgp section in IOS-XR output")
+                 .takes_value(true)
+            )
+            .arg(Arg::with_name("select_asn")
+                 .long("select-asn")
+                 .value_name("ASN")
+                 .help("only output route origins for this AS")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+            )
+            .arg(Arg::with_name("select_prefix")
+                 .long("select-prefix")
+                 .value_name("PREFIX")
+                 .validator(check_prefix)
+                 .help("only output route origins covering or covered by \
+                        this prefix")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+            )
+            .arg(Arg::with_name("select_ta")
+                 .long("select-ta")
+                 .value_name("TAL")
+                 .help("only output route origins from this trust anchor")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+            )
             .arg(Arg::with_name("listen")
                  .short("l")
                  .long("listen")
@@ -146,6 +346,10 @@ impl Config {
                  .long("strict")
                  .help("parse RPKI data in strict mode")
             )
+            .arg(Arg::with_name("skip_broken_tals")
+                 .long("skip-broken-tals")
+                 .help("continue without TALs that cannot be used")
+            )
             .arg(Arg::with_name("refresh")
                  .long("refresh")
                  .value_name("SECONDS")
@@ -158,6 +362,18 @@ impl Config {
                  .default_value("10")
                  .help("number of history items to keep in repeat mode")
             )
+            .arg(Arg::with_name("state_file")
+                 .long("state-file")
+                 .value_name("FILE")
+                 .help("file for keeping RTR state across restarts")
+                 .takes_value(true)
+            )
+            .arg(Arg::with_name("update_hook")
+                 .long("update-hook")
+                 .value_name("COMMAND")
+                 .help("command to run after output files are updated")
+                 .takes_value(true)
+            )
             .arg(Arg::with_name("verbose")
                  .short("v")
                  .long("verbose")
@@ -168,116 +384,287 @@ impl Config {
                  .long("man")
                  .help("print the man page to stdout")
             )
-            .get_matches();
-
-        if matches.is_present("man") {
-            let stdout = io::stdout();
-            let _ = stdout.lock().write_all(MAN_PAGE);
-            process::exit(0);
-        }
+            .subcommand(SubCommand::with_name("tal")
+                .about("manages trust anchor locators")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list")
+                    .about("lists all TALs and their status")
+                )
+                .subcommand(SubCommand::with_name("check")
+                    .about("checks all enabled TALs for problems")
+                )
+                .subcommand(SubCommand::with_name("enable")
+                    .about("enables a disabled TAL")
+                    .arg(Arg::with_name("name")
+                         .value_name("NAME")
+                         .help("the name of the TAL")
+                         .required(true)
+                    )
+                )
+                .subcommand(SubCommand::with_name("disable")
+                    .about("disables a TAL without deleting it")
+                    .arg(Arg::with_name("name")
+                         .value_name("NAME")
+                         .help("the name of the TAL")
+                         .required(true)
+                    )
+                )
+                .subcommand(SubCommand::with_name("add")
+                    .about("adds a TAL from a file or URL")
+                    .arg(Arg::with_name("source")
+                         .value_name("FILE-OR-URL")
+                         .help("file or http(s) URL of the TAL")
+                         .required(true)
+                    )
+                    .arg(Arg::with_name("name")
+                         .long("name")
+                         .value_name("NAME")
+                         .help("name of the new TAL")
+                         .takes_value(true)
+                    )
+                )
+                .subcommand(SubCommand::with_name("install-arin")
+                    .about("fetches and installs the ARIN TAL")
+                    .arg(Arg::with_name("accept")
+                         .long("accept-arin-rpa")
+                         .help("agree to ARIN's Relying Party Agreement")
+                    )
+                )
+            )
+            .subcommand(SubCommand::with_name("report")
+                .about("validates and reports on problems found")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("overclaims")
+                    .about("lists objects claiming resources not held \
+                            by their issuer")
+                    .arg(Arg::with_name("format")
+                         .short("f")
+                         .long("format")
+                         .value_name("FORMAT")
+                         .possible_values(&["text", "json"])
+                         .default_value("text")
+                         .help("sets the report format")
+                    )
+                )
+                .subcommand(SubCommand::with_name("maxlen")
+                    .about("lists route origins whose maxLength allows \
+                            forged-origin hijacks")
+                    .arg(Arg::with_name("format")
+                         .short("f")
+                         .long("format")
+                         .value_name("FORMAT")
+                         .possible_values(&["text", "json"])
+                         .default_value("text")
+                         .help("sets the report format")
+                    )
+                    .arg(Arg::with_name("announcements")
+                         .short("a")
+                         .long("announcements")
+                         .value_name("FILE")
+                         .help("file with announced routes")
+                         .takes_value(true)
+                    )
+                )
+                .subcommand(SubCommand::with_name("expiry")
+                    .about("forecasts objects and route origins expiring \
+                            soon")
+                    .arg(Arg::with_name("format")
+                         .short("f")
+                         .long("format")
+                         .value_name("FORMAT")
+                         .possible_values(&["text", "json", "metrics"])
+                         .default_value("text")
+                         .help("sets the report format")
+                    )
+                    .arg(Arg::with_name("within")
+                         .long("within")
+                         .value_name("HOURS")
+                         .default_value("24")
+                         .help("length of the forecast window in hours")
+                    )
+                )
+            )
+            .subcommand(SubCommand::with_name("validate")
+                .about("validates route announcements")
+                .arg(Arg::with_name("asn")
+                     .short("a")
+                     .long("asn")
+                     .value_name("ASN")
+                     .help("origin AS number of the announcement")
+                     .takes_value(true)
+                     .requires("prefix")
+                     .conflicts_with("input")
+                )
+                .arg(Arg::with_name("prefix")
+                     .short("p")
+                     .long("prefix")
+                     .value_name("PREFIX")
+                     .validator(check_prefix)
+                     .help("prefix of the announcement")
+                     .takes_value(true)
+                     .requires("asn")
+                )
+                .arg(Arg::with_name("input")
+                     .short("i")
+                     .long("input")
+                     .value_name("FILE")
+                     .help("file with announcements, '-' for stdin")
+                     .takes_value(true)
+                     .required_unless("asn")
+                )
+                .arg(Arg::with_name("input_format")
+                     .long("input-format")
+                     .value_name("FORMAT")
+                     .possible_values(&["text", "mrt"])
+                     .default_value("text")
+                     .help("sets the format of the input file")
+                )
+                .arg(Arg::with_name("summary")
+                     .short("s")
+                     .long("summary")
+                     .help("only print counts and the invalid routes")
+                )
+                .arg(Arg::with_name("format")
+                     .short("f")
+                     .long("format")
+                     .value_name("FORMAT")
+                     .possible_values(&["text", "json"])
+                     .default_value("text")
+                     .help("sets the output format")
+                )
+            )
+            .subcommand(SubCommand::with_name("explain")
+                .about("shows where the route origins for a prefix \
+                        came from")
+                .arg(Arg::with_name("prefix")
+                     .short("p")
+                     .long("prefix")
+                     .value_name("PREFIX")
+                     .validator(check_prefix)
+                     .help("prefix of the route origins")
+                     .takes_value(true)
+                     .required(true)
+                )
+                .arg(Arg::with_name("asn")
+                     .short("a")
+                     .long("asn")
+                     .value_name("ASN")
+                     .help("only explain route origins for this AS")
+                     .takes_value(true)
+                )
+                .arg(Arg::with_name("format")
+                     .short("f")
+                     .long("format")
+                     .value_name("FORMAT")
+                     .possible_values(&["text", "json"])
+                     .default_value("text")
+                     .help("sets the output format")
+                )
+            )
+            .subcommand(SubCommand::with_name("diff")
+                .about("compares two sets of validated route origins")
+                .arg(Arg::with_name("old")
+                     .value_name("OLD")
+                     .help("file with the old route origins")
+                     .required(true)
+                )
+                .arg(Arg::with_name("new")
+                     .value_name("NEW")
+                     .help("file with the new route origins")
+                     .required(true)
+                )
+                .arg(Arg::with_name("format")
+                     .short("f")
+                     .long("format")
+                     .value_name("FORMAT")
+                     .possible_values(&["text", "json"])
+                     .default_value("text")
+                     .help("sets the output format")
+                )
+            )
+    }
 
-        let cur_dir = match env::current_dir() {
-            Ok(dir) => dir,
-            Err(err) => {
-                println!(
-                    "Fatal: cannot get current directory ({}). Aborting.",
-                    err
-                );
-                process::exit(1);
+    /// Creates the list of outputs from the command line arguments.
+    ///
+    /// The output given via `-o` and `-f` comes first. It is only included
+    /// if either option is present or if there are no `--output-to`
+    /// options. This keeps the default of CSV on stdout.
+    fn outputs_from_matches(
+        matches: &ArgMatches,
+        cur_dir: &Path,
+        registry: &OutputRegistry
+    ) -> Vec<OutputTarget> {
+        let mut res = Vec::new();
+        if let Some(values) = matches.values_of("output_to") {
+            for value in values {
+                match OutputTarget::from_arg(value, cur_dir, registry) {
+                    Some(target) => res.push(target),
+                    None => {
+                        println!(
+                            "Invalid output '{}'. Expected FORMAT:FILE \
+                             with FORMAT one of {}.",
+                            value, format_names(registry)
+                        );
+                        process::exit(1);
+                    }
+                }
             }
-        };
-
-        let listen = match matches.values_of("listen") {
-            Some(values) => {
-                let mut listen = Vec::new();
-                for val in values {
-                    match val.to_socket_addrs() {
-                        Ok(some) => listen.extend(some),
-                        Err(_) => {
-                            println!("Invalid socket address {}", val);
+        }
+        if res.is_empty() || matches.is_present("output")
+            || matches.is_present("outform")
+        {
+            res.insert(0, OutputTarget {
+                format: match matches.value_of("outform") {
+                    Some(name) => {
+                        if !OutputTarget::is_known(name, registry) {
+                            println!(
+                                "Invalid output format '{}'. \
+                                 Expected one of {}.",
+                                name, format_names(registry)
+                            );
                             process::exit(1);
                         }
+                        name.into()
                     }
+                    None => "csv".into(),
+                },
+                path: match matches.value_of("output") {
+                    None | Some("-") => None,
+                    Some(path) => Some(cur_dir.join(path)),
                 }
-                listen
-            }
-            None => {
-                "127.0.0.1:3323".to_socket_addrs().unwrap().collect()
-            }
-        };
-
-        let (cache_dir, tal_dir) = Self::prepare_dirs(&matches, &cur_dir);
+            })
+        }
+        if !matches.is_present("bird_table")
+            && res.iter().any(|target| target.format == "bird2")
+        {
+            println!(
+                "The bird2 output format needs a table name. \
+                 Please provide one via the --bird-table option."
+            );
+            process::exit(1);
+        }
+        res
+    }
 
-        Config {
-            cache_dir,
-            tal_dir,
-            exceptions: matches.value_of("exceptions").map(|path| {
-                cur_dir.join(path)
-            }),
-            mode: if matches.is_present("daemon") {
-                RunMode::Daemon
+    /// Creates the output selection from the command line arguments.
+    fn selection_from_matches(matches: &ArgMatches) -> Selection {
+        let mut res = Selection::new();
+        if let Some(values) = matches.values_of("select_asn") {
+            for value in values {
+                res.add_asn(parse_asn(value))
             }
-            else if matches.is_present("repeat") {
-                RunMode::Repeat
+        }
+        if let Some(values) = matches.values_of("select_prefix") {
+            for value in values {
+                res.add_prefix(parse_prefix(value))
+            }
+        }
+        if let Some(values) = matches.values_of("select_ta") {
+            for value in values {
+                res.add_tal(value.into())
             }
-            else {
-                RunMode::Once
-            },
-            output: match matches.value_of("output") {
-                None | Some("-") => None,
-                Some(path) => Some(cur_dir.join(path)),
-            },
-            outform: match matches.value_of("outform") {
-                Some("csv") => OutputFormat::Csv,
-                Some("json") => OutputFormat::Json,
-                Some("rpsl") => OutputFormat::Rpsl,
-                Some("none") => OutputFormat::None,
-                Some(_) => {
-                    // This should be covered by clap above.
-                    unreachable!();
-                }
-                None => OutputFormat::Csv,
-            },
-            strict: matches.is_present("strict"),
-            update: !matches.is_present("noupdate"),
-            process: !matches.is_present("noprocess"),
-            verbose: match matches.occurrences_of("verbose") {
-                0 => LevelFilter::Error,
-                1 => LevelFilter::Info,
-                _ => LevelFilter::Debug,
-            },
-            refresh: {
-                let value = matches.value_of("refresh").unwrap();
-                match u64::from_str(value) {
-                    Ok(some) => Duration::from_secs(some),
-                    Err(_) => {
-                        error!(
-                            "Invalid value '{}' for refresh argument.\
-                             Needs to be number of seconds.",
-                            value
-                        );
-                        process::exit(1);
-                    }
-                }
-            },
-            retry: Duration::from_secs(600),
-            expire: Duration::from_secs(7200),
-            history_size: {
-                let value = matches.value_of("refresh").unwrap();
-                match usize::from_str(value) {
-                    Ok(some) => some,
-                    Err(_) => {
-                        error!(
-                            "Invalid value '{}' for refresh argument.\
-                             Needs to be number of seconds.",
-                            value
-                        );
-                        process::exit(1);
-                    }
-                }
-            },
-            rtr_listen: listen,
         }
+        res
     }
 
     /// Prepares and returns the cache dir and tal dir.
@@ -330,28 +717,341 @@ impl Config {
                 );
                 process::exit(1);
             }
-            for (name, content) in &DEFAULT_TALS {
-                let mut file = match fs::File::create(tal_dir.join(name)) {
-                    Ok(file) => file,
+            let tals = TalDir::new(tal_dir.clone());
+            if let Err(err) = tals.install_defaults() {
+                println!("Can't create TAL file {}.\n Aborting.", err);
+                process::exit(1);
+            }
+        }
+
+        (cache_dir, tal_dir)
+    }
+}
+
+
+//------------ Command -------------------------------------------------------
+
+/// The command Routinator was asked to execute.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// Validate and then either output or serve the result.
+    ///
+    /// How exactly this happens is determined by the run mode.
+    Run,
+
+    /// Manage the trust anchor locators.
+    Tal(TalCommand),
+
+    /// Validate and report on problems found.
+    Report(ReportCommand),
+
+    /// Validate and then check route announcements.
+    Validate(ValidateCommand),
+
+    /// Validate and then show the provenance of route origins.
+    Explain(ExplainCommand),
+
+    /// Compare two sets of route origins stored in files.
+    Diff(DiffCommand),
+}
+
+impl Command {
+    fn from_matches(matches: &ArgMatches, cur_dir: &Path) -> Self {
+        match matches.subcommand() {
+            ("tal", Some(matches)) => {
+                Command::Tal(TalCommand::from_matches(matches))
+            }
+            ("report", Some(matches)) => {
+                Command::Report(
+                    ReportCommand::from_matches(matches, cur_dir)
+                )
+            }
+            ("validate", Some(matches)) => {
+                Command::Validate(
+                    ValidateCommand::from_matches(matches, cur_dir)
+                )
+            }
+            ("explain", Some(matches)) => {
+                Command::Explain(ExplainCommand::from_matches(matches))
+            }
+            ("diff", Some(matches)) => {
+                Command::Diff(DiffCommand::from_matches(matches, cur_dir))
+            }
+            _ => Command::Run
+        }
+    }
+}
+
+
+//------------ TalCommand ----------------------------------------------------
+
+/// The sub-commands of the `tal` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TalCommand {
+    /// List all TALs.
+    List,
+
+    /// Check the TAL directory and all enabled TALs.
+    Check,
+
+    /// Enable the TAL with the given name.
+    Enable(String),
+
+    /// Disable the TAL with the given name.
+    Disable(String),
+
+    /// Add a TAL from a file or URL, optionally with an explicit name.
+    Add {
+        source: String,
+        name: Option<String>,
+    },
+
+    /// Install the ARIN TAL if the user agreed to the terms.
+    InstallArin {
+        accept: bool,
+    },
+}
+
+impl TalCommand {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            ("check", Some(_)) => TalCommand::Check,
+            ("enable", Some(matches)) => {
+                TalCommand::Enable(
+                    matches.value_of("name").unwrap().into()
+                )
+            }
+            ("disable", Some(matches)) => {
+                TalCommand::Disable(
+                    matches.value_of("name").unwrap().into()
+                )
+            }
+            ("add", Some(matches)) => {
+                TalCommand::Add {
+                    source: matches.value_of("source").unwrap().into(),
+                    name: matches.value_of("name").map(Into::into),
+                }
+            }
+            ("install-arin", Some(matches)) => {
+                TalCommand::InstallArin {
+                    accept: matches.is_present("accept")
+                }
+            }
+            _ => TalCommand::List
+        }
+    }
+}
+
+
+//------------ ReportCommand -------------------------------------------------
+
+/// The sub-commands of the `report` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReportCommand {
+    /// Report objects claiming resources not held by their issuer.
+    Overclaims(ReportFormat),
+
+    /// Report route origins with a maxLength creating exposure.
+    ///
+    /// If a path is given, announced routes are read from that file.
+    MaxLength(ReportFormat, Option<PathBuf>),
+
+    /// Forecast objects and route origins expiring within the duration.
+    Expiry(ReportFormat, Duration),
+}
+
+impl ReportCommand {
+    fn from_matches(matches: &ArgMatches, cur_dir: &Path) -> Self {
+        match matches.subcommand() {
+            ("overclaims", Some(matches)) => {
+                ReportCommand::Overclaims(ReportFormat::from_matches(matches))
+            }
+            ("maxlen", Some(matches)) => {
+                ReportCommand::MaxLength(
+                    ReportFormat::from_matches(matches),
+                    matches.value_of("announcements").map(|path| {
+                        cur_dir.join(path)
+                    })
+                )
+            }
+            ("expiry", Some(matches)) => {
+                let value = matches.value_of("within").unwrap();
+                ReportCommand::Expiry(
+                    ReportFormat::from_matches(matches),
+                    match u64::from_str(value).ok().and_then(|some| {
+                        some.checked_mul(3600)
+                    }) {
+                        Some(secs) => Duration::from_secs(secs),
+                        None => {
+                            println!(
+                                "Invalid value '{}' for within argument. \
+                                 Needs to be number of hours.",
+                                value
+                            );
+                            process::exit(1);
+                        }
+                    }
+                )
+            }
+            _ => ReportCommand::Overclaims(ReportFormat::Text)
+        }
+    }
+}
+
+
+//------------ ValidateCommand -----------------------------------------------
+
+/// The arguments of the `validate` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidateCommand {
+    /// The announcements to validate.
+    pub input: ValidateInput,
+
+    /// The format of the announcements in a file or on stdin.
+    pub input_format: InputFormat,
+
+    /// Only print the number of routes per state and the invalid routes.
+    pub summary: bool,
+
+    /// The output format.
+    pub format: ReportFormat,
+}
+
+impl ValidateCommand {
+    fn from_matches(matches: &ArgMatches, cur_dir: &Path) -> Self {
+        let input = match matches.value_of("input") {
+            Some("-") => ValidateInput::Stdin,
+            Some(path) => ValidateInput::File(cur_dir.join(path)),
+            None => {
+                let prefix = matches.value_of("prefix").unwrap();
+                let asn = matches.value_of("asn").unwrap();
+                match Announcement::from_str(
+                    &format!("{} {}", prefix, asn)
+                ) {
+                    Ok(some) => ValidateInput::Single(some),
                     Err(err) => {
-                        println!(
-                            "Can't create TAL file {}: {}.\n Aborting.",
-                            tal_dir.join(name).display(), err
-                        );
+                        println!("Invalid announcement: {}", err);
                         process::exit(1);
                     }
-                };
-                if let Err(err) = file.write_all(content) {
-                    println!(
-                        "Can't create TAL file {}: {}.\n Aborting.",
-                        tal_dir.join(name).display(), err
-                    );
-                    process::exit(1);
                 }
             }
+        };
+        ValidateCommand {
+            input,
+            input_format: match matches.value_of("input_format") {
+                Some("mrt") => InputFormat::Mrt,
+                _ => InputFormat::Text,
+            },
+            summary: matches.is_present("summary"),
+            format: ReportFormat::from_matches(matches),
         }
+    }
+}
 
-        (cache_dir, tal_dir)
+
+//------------ ExplainCommand ------------------------------------------------
+
+/// The arguments of the `explain` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExplainCommand {
+    /// The prefix of the route origins to explain.
+    pub prefix: AddressPrefix,
+
+    /// The AS number of the route origins to explain if given.
+    pub asn: Option<AsId>,
+
+    /// The output format.
+    pub format: ReportFormat,
+}
+
+impl ExplainCommand {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        ExplainCommand {
+            prefix: parse_prefix(matches.value_of("prefix").unwrap()),
+            asn: matches.value_of("asn").map(parse_asn),
+            format: ReportFormat::from_matches(matches),
+        }
+    }
+}
+
+
+//------------ DiffCommand ---------------------------------------------------
+
+/// The arguments of the `diff` command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DiffCommand {
+    /// The file with the old route origins.
+    pub old: PathBuf,
+
+    /// The file with the new route origins.
+    pub new: PathBuf,
+
+    /// The output format.
+    pub format: ReportFormat,
+}
+
+impl DiffCommand {
+    fn from_matches(matches: &ArgMatches, cur_dir: &Path) -> Self {
+        DiffCommand {
+            old: cur_dir.join(matches.value_of("old").unwrap()),
+            new: cur_dir.join(matches.value_of("new").unwrap()),
+            format: ReportFormat::from_matches(matches),
+        }
+    }
+}
+
+
+//------------ ValidateInput -------------------------------------------------
+
+/// Where to take announcements to validate from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValidateInput {
+    /// A single announcement given on the command line.
+    Single(Announcement),
+
+    /// A file with announcements.
+    File(PathBuf),
+
+    /// Standard input.
+    Stdin,
+}
+
+
+//------------ InputFormat ---------------------------------------------------
+
+/// The format of a file with announcements.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputFormat {
+    /// One announcement per line.
+    Text,
+
+    /// An MRT RIB dump.
+    Mrt,
+}
+
+
+//------------ ReportFormat --------------------------------------------------
+
+/// The format of a report.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReportFormat {
+    /// Human readable text.
+    Text,
+
+    /// JSON.
+    Json,
+
+    /// Metrics in Prometheus text format.
+    Metrics,
+}
+
+impl ReportFormat {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        match matches.value_of("format") {
+            Some("json") => ReportFormat::Json,
+            Some("metrics") => ReportFormat::Metrics,
+            _ => ReportFormat::Text
+        }
     }
 }
 
@@ -376,29 +1076,171 @@ impl RunMode {
 }
 
 
-//------------ OutputFormat --------------------------------------------------
+//------------ OutputTarget --------------------------------------------------
 
-#[derive(Clone, Copy, Debug)]
-pub enum OutputFormat {
-    Csv,
-    Json,
-    Rpsl,
-    None,
+/// An output to be produced.
+#[derive(Clone, Debug)]
+pub struct OutputTarget {
+    /// The name of the output format.
+    ///
+    /// This is either the name of a format in the output registry or
+    /// `"none"` for no output at all.
+    pub format: String,
+
+    /// The path of the output file.
+    ///
+    /// If this is `None`, the output goes to stdout.
+    pub path: Option<PathBuf>,
 }
 
+impl OutputTarget {
+    /// Creates an output target from a `FORMAT:FILE` argument.
+    ///
+    /// A file of `-` means stdout. Returns `None` if the argument is
+    /// malformed or the format isn’t known to `registry`.
+    fn from_arg(
+        value: &str,
+        cur_dir: &Path,
+        registry: &OutputRegistry
+    ) -> Option<Self> {
+        let mut parts = value.splitn(2, ':');
+        let format = parts.next().filter(|name| {
+            Self::is_known(name, registry)
+        });
+        match (format, parts.next()) {
+            (Some(format), Some(path)) if !path.is_empty() => {
+                Some(OutputTarget {
+                    format: format.into(),
+                    path: if path == "-" {
+                        None
+                    }
+                    else {
+                        Some(cur_dir.join(path))
+                    }
+                })
+            }
+            _ => None
+        }
+    }
+
+    /// Returns whether `name` is a format that can be used for a target.
+    fn is_known(name: &str, registry: &OutputRegistry) -> bool {
+        name == "none" || registry.contains(name)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns a list of all output format names for use in messages.
+fn format_names(registry: &OutputRegistry) -> String {
+    let mut res = String::new();
+    for name in registry.names().chain(Some("none")) {
+        if !res.is_empty() {
+            res.push_str(", ");
+        }
+        res.push_str(name);
+    }
+    res
+}
+
+/// Parses an AS number with an optional `AS` prefix or exits.
+fn parse_asn(value: &str) -> AsId {
+    match parse_as_id(value) {
+        Some(as_id) => as_id,
+        None => {
+            println!("Invalid AS number '{}'.", value);
+            process::exit(1);
+        }
+    }
+}
 
-//------------ DEFAULT_TALS --------------------------------------------------
+/// Checks that a command line argument is an address prefix.
+fn check_prefix(value: String) -> Result<(), String> {
+    AddressPrefix::from_str(&value).map(|_| ()).map_err(|_| {
+        format!("invalid prefix '{}'", value)
+    })
+}
 
-const DEFAULT_TALS: [(&str, &[u8]); 5] = [
-    ("afrinic.tal", include_bytes!("../tals/afrinic.tal")),
-    ("apnic.tal", include_bytes!("../tals/apnic.tal")),
-    ("arin.tal", include_bytes!("../tals/arin.tal")),
-    ("lacnic.tal", include_bytes!("../tals/lacnic.tal")),
-    ("ripe.tal", include_bytes!("../tals/ripe.tal")),
-];
+/// Parses an address prefix or exits.
+fn parse_prefix(value: &str) -> AddressPrefix {
+    match AddressPrefix::from_str(value) {
+        Ok(prefix) => prefix,
+        Err(_) => {
+            println!("Invalid prefix '{}'.", value);
+            process::exit(1);
+        }
+    }
+}
 
 
 //------------ The Man Page --------------------------------------------------
 
 const MAN_PAGE: &[u8] = include_bytes!("../doc/routinator.1");
 
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use super::super::output::CsvWriter;
+
+    fn from_arg(value: &str) -> Option<OutputTarget> {
+        let mut registry = OutputRegistry::with_builtin();
+        registry.register("custom", |_, _| Box::new(CsvWriter));
+        OutputTarget::from_arg(
+            value, Path::new("/var/lib/routinator"), &registry
+        )
+    }
+
+    #[test]
+    fn output_target_from_arg() {
+        let target = from_arg("csv:-").unwrap();
+        assert_eq!(target.format, "csv");
+        assert_eq!(target.path, None);
+
+        let target = from_arg("json:out.json").unwrap();
+        assert_eq!(target.format, "json");
+        assert_eq!(
+            target.path, Some(PathBuf::from("/var/lib/routinator/out.json"))
+        );
+
+        let target = from_arg("rpki-client:/tmp/a:b.json").unwrap();
+        assert_eq!(target.format, "rpki-client");
+        assert_eq!(target.path, Some(PathBuf::from("/tmp/a:b.json")));
+
+        assert_eq!(from_arg("custom:-").unwrap().format, "custom");
+        assert_eq!(from_arg("none:-").unwrap().format, "none");
+    }
+
+    #[test]
+    fn reject_overlong_prefixes() {
+        for args in &[
+            &["routinator", "explain", "-p", "10.0.0.0/33"][..],
+            &["routinator", "explain", "-p", "10.0.0.0/255"],
+            &["routinator", "explain", "-p", "2001:db8::/129"],
+            &["routinator", "--select-prefix", "10.0.0.0/255"],
+            &["routinator", "--select-prefix", "2001:db8::/129"],
+        ] {
+            assert!(Config::app().get_matches_from_safe(*args).is_err())
+        }
+        for args in &[
+            &["routinator", "explain", "-p", "10.0.0.0/32"][..],
+            &["routinator", "explain", "-p", "2001:db8::/128"],
+            &["routinator", "--select-prefix", "10.0.0.0/8"],
+        ] {
+            assert!(Config::app().get_matches_from_safe(*args).is_ok())
+        }
+    }
+
+    #[test]
+    fn output_target_from_bad_arg() {
+        assert!(from_arg("csv").is_none());
+        assert!(from_arg("csv:").is_none());
+        assert!(from_arg("").is_none());
+        assert!(from_arg("xml:out.xml").is_none());
+        assert!(from_arg(":out.csv").is_none());
+    }
+}
@@ -0,0 +1,409 @@
+//! Forecasting the expiry of RPKI objects.
+//!
+//! All RPKI objects have a limited life time. Certificates expire at the
+//! end of their validity and manifests and CRLs need to be replaced before
+//! their next update time. If a CA fails to renew its objects in time,
+//! validated route origins will disappear.
+//!
+//! During validation, [`Repository`] records the expiry time of every
+//! object it validated as an [`Expiry`] and, for every ROA, the earliest
+//! expiry along its certificate chain. From this information, an
+//! [`ExpiryForecast`] determines which objects expire and which route
+//! origins will disappear within a given time frame.
+//!
+//! [`Repository`]: ../repository/struct.Repository.html
+//! [`Expiry`]: struct.Expiry.html
+//! [`ExpiryForecast`]: struct.ExpiryForecast.html
+
+use std::{fmt, io};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use chrono::{DateTime, Duration, MAX_DATE, SecondsFormat, Utc};
+use json::JsonValue;
+use super::origins::AddressOrigin;
+use super::report::ValidationReport;
+
+
+//------------ ObjectKind ----------------------------------------------------
+
+/// The kind of an object with an expiry time.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ObjectKind {
+    /// A CA certificate, including trust anchor certificates.
+    CaCertificate,
+
+    /// The EE certificate of a ROA or manifest.
+    EeCertificate,
+
+    /// A manifest which needs to be replaced before its next update time.
+    Manifest,
+
+    /// A CRL which needs to be replaced before its next update time.
+    Crl,
+}
+
+impl ObjectKind {
+    /// Returns all object kinds.
+    pub fn all() -> &'static [ObjectKind] {
+        &[
+            ObjectKind::CaCertificate, ObjectKind::EeCertificate,
+            ObjectKind::Manifest, ObjectKind::Crl
+        ]
+    }
+
+    /// Returns a short name of the kind for use in machine readable output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectKind::CaCertificate => "ca-certificate",
+            ObjectKind::EeCertificate => "ee-certificate",
+            ObjectKind::Manifest => "manifest",
+            ObjectKind::Crl => "crl",
+        }
+    }
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ObjectKind::CaCertificate => "CA certificate",
+            ObjectKind::EeCertificate => "EE certificate",
+            ObjectKind::Manifest => "manifest",
+            ObjectKind::Crl => "CRL",
+        })
+    }
+}
+
+
+//------------ Expiry --------------------------------------------------------
+
+/// The time an object expires.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Expiry {
+    /// The kind of object.
+    pub kind: ObjectKind,
+
+    /// The URI of the object.
+    ///
+    /// For EE certificates, this is the URI of the object containing the
+    /// certificate.
+    pub uri: String,
+
+    /// The name of the TAL the object was found under.
+    pub tal: String,
+
+    /// The time the object expires.
+    pub time: DateTime<Utc>,
+}
+
+impl Expiry {
+    pub fn new(
+        kind: ObjectKind,
+        uri: String,
+        tal: String,
+        time: DateTime<Utc>
+    ) -> Self {
+        Expiry { kind, uri, tal, time }
+    }
+
+    /// Returns whichever of the two expires earlier.
+    pub fn earliest<'a>(&'a self, other: &'a Expiry) -> &'a Expiry {
+        if other.time < self.time { other } else { self }
+    }
+}
+
+
+//------------ RoaExpiry -----------------------------------------------------
+
+/// The expiry of a ROA and the route origins it contains.
+#[derive(Clone, Debug)]
+pub struct RoaExpiry {
+    /// The URI of the ROA.
+    pub uri: String,
+
+    /// The earliest expiring certificate along the ROA’s chain.
+    ///
+    /// This includes the ROA’s own EE certificate.
+    pub expiry: Expiry,
+
+    /// The route origins of the ROA.
+    pub origins: Vec<AddressOrigin>,
+}
+
+
+//------------ ExpiryForecast ------------------------------------------------
+
+/// The objects and route origins expiring within a time frame.
+#[derive(Clone, Debug)]
+pub struct ExpiryForecast {
+    /// The start of the time frame.
+    now: DateTime<Utc>,
+
+    /// The end of the time frame.
+    deadline: DateTime<Utc>,
+
+    /// The objects expiring before the deadline ordered by expiry.
+    objects: Vec<Expiry>,
+
+    /// The route origins disappearing before the deadline.
+    ///
+    /// Each origin is paired with the object whose expiry causes it to
+    /// disappear. The list is ordered by expiry.
+    origins: Vec<(AddressOrigin, Expiry)>,
+}
+
+impl ExpiryForecast {
+    /// Creates a forecast for the time between `now` and `now + window`.
+    ///
+    /// Because a route origin can be contained in more than one ROA, it
+    /// only disappears once the last of these ROAs expires. If the window
+    /// reaches beyond the latest representable time, it ends there.
+    pub fn new(
+        report: &ValidationReport,
+        now: DateTime<Utc>,
+        window: Duration
+    ) -> Self {
+        let deadline = now.checked_add_signed(window).unwrap_or_else(|| {
+            MAX_DATE.and_hms(23, 59, 59)
+        });
+        let mut objects: Vec<_> = report.objects().iter().filter(|item| {
+            item.time <= deadline
+        }).cloned().collect();
+        objects.sort_by(|left, right| {
+            left.time.cmp(&right.time).then(left.uri.cmp(&right.uri))
+        });
+
+        let mut latest: HashMap<&AddressOrigin, &Expiry> = HashMap::new();
+        for roa in report.roas() {
+            for origin in &roa.origins {
+                match latest.entry(origin) {
+                    Entry::Occupied(mut entry) => {
+                        if entry.get().time < roa.expiry.time {
+                            entry.insert(&roa.expiry);
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(&roa.expiry);
+                    }
+                }
+            }
+        }
+        let mut origins: Vec<_> = latest.into_iter().filter(|item| {
+            item.1.time <= deadline
+        }).map(|(origin, expiry)| {
+            (origin.clone(), expiry.clone())
+        }).collect();
+        origins.sort_by(|left, right| {
+            left.1.time.cmp(&right.1.time)
+            .then(left.0.as_id().cmp(&right.0.as_id()))
+            .then(left.0.address().cmp(&right.0.address()))
+            .then(left.0.address_length().cmp(&right.0.address_length()))
+            .then(left.0.max_length().cmp(&right.0.max_length()))
+        });
+
+        ExpiryForecast { now, deadline, objects, origins }
+    }
+
+    /// Returns the objects expiring within the time frame.
+    pub fn objects(&self) -> &[Expiry] {
+        &self.objects
+    }
+
+    /// Returns the route origins disappearing within the time frame.
+    pub fn origins(&self) -> &[(AddressOrigin, Expiry)] {
+        &self.origins
+    }
+
+    /// Writes the forecast in human readable form.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target, "{} objects expire before {}.",
+            self.objects.len(), format_time(self.deadline)
+        )?;
+        for item in &self.objects {
+            writeln!(
+                target, "    {}  {} {} ({})",
+                format_time(item.time), item.kind, item.uri, item.tal
+            )?;
+        }
+        writeln!(
+            target, "{} route origins will disappear before {}.",
+            self.origins.len(), format_time(self.deadline)
+        )?;
+        for &(ref origin, ref expiry) in &self.origins {
+            writeln!(
+                target, "    {}  {} {}/{}-{}: {} {} expires ({})",
+                format_time(expiry.time), origin.as_id(),
+                origin.address(), origin.address_length(),
+                origin.max_length(), expiry.kind, expiry.uri, expiry.tal
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the forecast as a JSON value.
+    pub fn to_json(&self) -> JsonValue {
+        let mut objects = JsonValue::new_array();
+        for item in &self.objects {
+            let _ = objects.push(expiry_json(item));
+        }
+        let mut origins = JsonValue::new_array();
+        for &(ref origin, ref expiry) in &self.origins {
+            let mut value = JsonValue::new_object();
+            value["asn"] = origin.as_id().to_string().into();
+            value["prefix"] = format!(
+                "{}/{}", origin.address(), origin.address_length()
+            ).into();
+            value["maxLength"] = origin.max_length().into();
+            value["cause"] = expiry_json(expiry);
+            let _ = origins.push(value);
+        }
+        let mut res = JsonValue::new_object();
+        res["now"] = format_time(self.now).into();
+        res["deadline"] = format_time(self.deadline).into();
+        res["objects"] = objects;
+        res["roas"] = origins;
+        res
+    }
+
+    /// Writes the forecast as metrics in Prometheus text format.
+    pub fn write_metrics<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target,
+            "# HELP routinator_expiry_window_seconds length of the \
+             forecast window\n\
+             # TYPE routinator_expiry_window_seconds gauge\n\
+             routinator_expiry_window_seconds {}",
+            (self.deadline - self.now).num_seconds()
+        )?;
+        writeln!(
+            target,
+            "# HELP routinator_expiring_objects objects expiring within \
+             the forecast window\n\
+             # TYPE routinator_expiring_objects gauge"
+        )?;
+        for kind in ObjectKind::all() {
+            writeln!(
+                target, "routinator_expiring_objects{{kind=\"{}\"}} {}",
+                kind.as_str(),
+                self.objects.iter().filter(|item| {
+                    item.kind == *kind
+                }).count()
+            )?;
+        }
+        writeln!(
+            target,
+            "# HELP routinator_expiring_vrps route origins disappearing \
+             within the forecast window\n\
+             # TYPE routinator_expiring_vrps gauge"
+        )?;
+        for kind in ObjectKind::all() {
+            writeln!(
+                target, "routinator_expiring_vrps{{cause=\"{}\"}} {}",
+                kind.as_str(),
+                self.origins.iter().filter(|item| {
+                    item.1.kind == *kind
+                }).count()
+            )?;
+        }
+        if let Some(item) = self.objects.first() {
+            writeln!(
+                target,
+                "# HELP routinator_next_expiry_timestamp_seconds time the \
+                 next object expires\n\
+                 # TYPE routinator_next_expiry_timestamp_seconds gauge\n\
+                 routinator_next_expiry_timestamp_seconds {}",
+                item.time.timestamp()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn expiry_json(expiry: &Expiry) -> JsonValue {
+    let mut res = JsonValue::new_object();
+    res["type"] = expiry.kind.as_str().into();
+    res["uri"] = expiry.uri.clone().into();
+    res["ta"] = expiry.tal.clone().into();
+    res["expires"] = format_time(expiry.time).into();
+    res
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use chrono::TimeZone;
+    use rpki::asres::AsId;
+
+    fn make_expiry(kind: ObjectKind, uri: &str, hours: i64) -> Expiry {
+        Expiry::new(
+            kind, uri.into(), "test".into(),
+            Utc.ymd(2018, 11, 1).and_hms(0, 0, 0) + Duration::hours(hours)
+        )
+    }
+
+    fn make_roa(uri: &str, expiry: Expiry, prefixes: &[&str]) -> RoaExpiry {
+        RoaExpiry {
+            uri: uri.into(),
+            expiry,
+            origins: prefixes.iter().map(|prefix| {
+                AddressOrigin::new(
+                    AsId::from(64496), prefix.parse().unwrap(), 24
+                )
+            }).collect()
+        }
+    }
+
+    #[test]
+    fn should_forecast_expiry() {
+        let mut report = ValidationReport::new();
+        let soon = make_expiry(ObjectKind::EeCertificate, "a.roa", 2);
+        let late = make_expiry(ObjectKind::EeCertificate, "b.roa", 48);
+        let ca = make_expiry(ObjectKind::CaCertificate, "c.cer", 12);
+        report.push_object(soon.clone());
+        report.push_object(late.clone());
+        report.push_object(ca.clone());
+        report.push_roa(make_roa("a.roa", soon.clone(), &["10.0.0.0/24"]));
+        report.push_roa(make_roa("b.roa", late, &["10.0.1.0/24"]));
+        report.push_roa(make_roa("d.roa", ca.clone(), &["10.0.2.0/24"]));
+
+        // The same origin in another, longer lived ROA survives.
+        report.push_roa(make_roa(
+            "e.roa", make_expiry(ObjectKind::EeCertificate, "e.roa", 30),
+            &["10.0.0.0/24"]
+        ));
+
+        let forecast = ExpiryForecast::new(
+            &report, Utc.ymd(2018, 11, 1).and_hms(0, 0, 0),
+            Duration::hours(24)
+        );
+        assert_eq!(forecast.objects(), &[soon, ca.clone()][..]);
+        assert_eq!(forecast.origins().len(), 1);
+        assert_eq!(
+            forecast.origins()[0].0.prefix(), "10.0.2.0/24".parse().unwrap()
+        );
+        assert_eq!(forecast.origins()[0].1, ca);
+
+        let forecast = ExpiryForecast::new(
+            &report, Utc.ymd(2018, 11, 1).and_hms(0, 0, 0),
+            Duration::max_value()
+        );
+        assert_eq!(forecast.objects().len(), 3);
+    }
+}
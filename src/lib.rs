@@ -20,6 +20,8 @@
 //! [`Repository`]: repository/struct.Repository.html
 
 extern crate bytes;
+extern crate chrono;
+extern crate daemonize;
 #[macro_use] extern crate clap;
 extern crate dirs;
 #[macro_use] extern crate failure;
@@ -33,10 +35,29 @@ extern crate slab;
 extern crate tokio;
 extern crate tokio_process;
 
+#[cfg(test)] #[macro_use] extern crate quickcheck;
+
+pub mod atomic;
+pub mod bgp;
 pub mod config;
+pub mod expiry;
+pub mod maxlen;
+pub mod mrt;
+pub mod operation;
 pub mod origins;
+pub mod output;
+pub mod provenance;
+pub mod report;
 pub mod repository;
+pub mod resources;
 pub mod rtr;
+pub mod serial;
 pub mod slurm;
+pub mod snapshot;
+pub mod state;
+pub mod summary;
+pub mod tals;
+pub mod validity;
+pub mod vendor;
 
 
@@ -0,0 +1,405 @@
+//! Analysis of the maximum length of route origins.
+//!
+//! A ROA can authorize not only the prefix it lists but, via the maximum
+//! length, also all more specific prefixes up to that length. If these
+//! more specific prefixes aren’t actually announced by the authorized AS,
+//! anyone can announce them with a forged origin AS and the announcement
+//! will be considered valid. RFC 9319 therefore recommends to only use a
+//! maximum length if all covered more specific prefixes are announced.
+//!
+//! This module provides [`MaxLengthReport`] which finds the validated
+//! route origins that don’t follow this recommendation. If a list of
+//! announcements is given, route origins whose more specific prefixes are
+//! all announced are not reported and the suggested replacements consider
+//! the announced prefixes.
+//!
+//! [`MaxLengthReport`]: struct.MaxLengthReport.html
+
+use std::{io, slice};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use json::JsonValue;
+use rpki::asres::AsId;
+use super::bgp::Announcements;
+use super::origins::{AddressOrigin, AddressPrefix};
+
+
+//------------ MaxLengthReport -----------------------------------------------
+
+/// The route origins with a maximum length creating exposure.
+///
+/// The route origins are grouped by trust anchor and AS number.
+#[derive(Clone, Debug)]
+pub struct MaxLengthReport {
+    /// The groups of affected route origins.
+    groups: Vec<MaxLengthGroup>,
+
+    /// The number of route origins analysed.
+    total: usize,
+
+    /// The number of route origins reported.
+    loose: usize,
+}
+
+impl MaxLengthReport {
+    /// Analyses a set of route origins.
+    ///
+    /// If `announcements` is given, it is used to determine which more
+    /// specific prefixes are actually announced.
+    pub fn analyse<I>(
+        origins: I,
+        announcements: Option<&Announcements>
+    ) -> Self
+    where I: IntoIterator, I::Item: Borrow<AddressOrigin> {
+        let announced = announcements.map(|announcements| {
+            let mut res: HashMap<AsId, Vec<AddressPrefix>> = HashMap::new();
+            for item in announcements.iter() {
+                res.entry(item.origin).or_insert_with(Vec::new)
+                   .push(item.prefix)
+            }
+            res
+        });
+        let mut groups = BTreeMap::new();
+        let mut total = 0;
+        let mut loose = 0;
+        for origin in origins {
+            let origin = origin.borrow();
+            total += 1;
+            if origin.max_length() <= origin.address_length() {
+                continue
+            }
+            let item = match announced {
+                Some(ref announced) => {
+                    let item = LooseOrigin::new(
+                        origin, announced.get(&origin.as_id())
+                    );
+                    if item.exposed == 0 {
+                        continue
+                    }
+                    item
+                }
+                None => LooseOrigin::new(origin, None)
+            };
+            loose += 1;
+            groups.entry(
+                (origin.tal_list(), u32::from(origin.as_id()))
+            ).or_insert_with(Vec::new).push(item);
+        }
+        MaxLengthReport {
+            groups: groups.into_iter().map(|((tal, as_id), mut items)| {
+                items.sort_by(|left, right| {
+                    left.origin.address().cmp(&right.origin.address())
+                    .then(
+                        left.origin.address_length().cmp(
+                            &right.origin.address_length()
+                        )
+                    )
+                    .then(
+                        left.origin.max_length().cmp(
+                            &right.origin.max_length()
+                        )
+                    )
+                });
+                MaxLengthGroup { tal, as_id: AsId::from(as_id), items }
+            }).collect(),
+            total,
+            loose
+        }
+    }
+
+    /// Returns the number of route origins analysed.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the number of route origins reported.
+    pub fn loose(&self) -> usize {
+        self.loose
+    }
+
+    /// Returns an iterator over the groups of reported route origins.
+    pub fn iter(&self) -> slice::Iter<MaxLengthGroup> {
+        self.groups.iter()
+    }
+
+    /// Writes the report in human readable form.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        for group in &self.groups {
+            writeln!(target, "{} {}", group.tal, group.as_id)?;
+            for item in &group.items {
+                writeln!(
+                    target, "    {}/{}-{}: {} more specific prefixes exposed",
+                    item.origin.address(), item.origin.address_length(),
+                    item.origin.max_length(), item.exposed
+                )?;
+                write!(target, "        suggested:")?;
+                for prefix in item.suggested() {
+                    write!(
+                        target, " {}/{}",
+                        prefix.address(), prefix.address_length()
+                    )?;
+                }
+                writeln!(target)?;
+            }
+        }
+        writeln!(
+            target,
+            "{} of {} route origins have a maxLength creating exposure.",
+            self.loose, self.total
+        )
+    }
+
+    /// Returns the report as a JSON value.
+    pub fn to_json(&self) -> JsonValue {
+        let mut groups = JsonValue::new_array();
+        for group in &self.groups {
+            let mut items = JsonValue::new_array();
+            for item in &group.items {
+                let mut suggested = JsonValue::new_array();
+                for prefix in item.suggested() {
+                    let _ = suggested.push(format!(
+                        "{}/{}", prefix.address(), prefix.address_length()
+                    ));
+                }
+                let mut value = JsonValue::new_object();
+                value["prefix"] = format!(
+                    "{}/{}",
+                    item.origin.address(), item.origin.address_length()
+                ).into();
+                value["maxLength"] = item.origin.max_length().into();
+                // The counts can be too large for JSON numbers to be
+                // precise, so they are given as decimal strings.
+                value["authorized"] = item.authorized.to_string().into();
+                value["exposed"] = item.exposed.to_string().into();
+                value["suggested"] = suggested;
+                let _ = items.push(value);
+            }
+            let mut value = JsonValue::new_object();
+            value["ta"] = group.tal.clone().into();
+            value["asn"] = group.as_id.to_string().into();
+            value["roas"] = items;
+            let _ = groups.push(value);
+        }
+        let mut res = JsonValue::new_object();
+        res["total"] = self.total.into();
+        res["loose"] = self.loose.into();
+        res["groups"] = groups;
+        res
+    }
+}
+
+
+//------------ MaxLengthGroup ------------------------------------------------
+
+/// The reported route origins of one AS under one trust anchor.
+#[derive(Clone, Debug)]
+pub struct MaxLengthGroup {
+    tal: String,
+    as_id: AsId,
+    items: Vec<LooseOrigin>,
+}
+
+impl MaxLengthGroup {
+    pub fn tal_name(&self) -> &str {
+        &self.tal
+    }
+
+    pub fn as_id(&self) -> AsId {
+        self.as_id
+    }
+
+    pub fn iter(&self) -> slice::Iter<LooseOrigin> {
+        self.items.iter()
+    }
+}
+
+
+//------------ LooseOrigin ---------------------------------------------------
+
+/// A route origin whose maximum length creates exposure.
+#[derive(Clone, Debug)]
+pub struct LooseOrigin {
+    /// The route origin in question.
+    origin: AddressOrigin,
+
+    /// The number of more specific prefixes authorized.
+    ///
+    /// This saturates at `u128::MAX`.
+    authorized: u128,
+
+    /// The more specific prefixes actually announced.
+    announced: Vec<AddressPrefix>,
+
+    /// The number of more specific prefixes authorized but not announced.
+    exposed: u128,
+}
+
+impl LooseOrigin {
+    fn new(
+        origin: &AddressOrigin,
+        announced: Option<&Vec<AddressPrefix>>
+    ) -> Self {
+        let authorized = more_specifics(
+            origin.address_length(), origin.max_length()
+        );
+        let mut res: Vec<_> = match announced {
+            Some(announced) => {
+                announced.iter().filter(|prefix| {
+                    **prefix != origin.prefix()
+                    && prefix.address_length() <= origin.max_length()
+                    && origin.prefix().covers(**prefix)
+                }).cloned().collect::<HashSet<_>>().into_iter().collect()
+            }
+            None => Vec::new()
+        };
+        res.sort_by(|left, right| {
+            left.address().cmp(&right.address())
+            .then(left.address_length().cmp(&right.address_length()))
+        });
+        LooseOrigin {
+            origin: origin.clone(),
+            authorized,
+            exposed: authorized.saturating_sub(res.len() as u128),
+            announced: res,
+        }
+    }
+
+    pub fn origin(&self) -> &AddressOrigin {
+        &self.origin
+    }
+
+    pub fn authorized(&self) -> u128 {
+        self.authorized
+    }
+
+    pub fn exposed(&self) -> u128 {
+        self.exposed
+    }
+
+    pub fn announced(&self) -> &[AddressPrefix] {
+        &self.announced
+    }
+
+    /// Returns the prefixes a tightened ROA should contain.
+    ///
+    /// These are the prefix itself and all announced more specific
+    /// prefixes, each to be used with a maximum length equal to its
+    /// prefix length.
+    pub fn suggested(&self) -> Vec<AddressPrefix> {
+        let mut res = vec![self.origin.prefix()];
+        res.extend_from_slice(&self.announced);
+        res
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the number of more specific prefixes up to `max_len`.
+///
+/// This is 2^(max_len - len + 1) - 2. The result saturates at `u128::MAX`
+/// which only happens for a difference of 128, i.e., an IPv6 default
+/// route with a maximum length of 128.
+fn more_specifics(len: u8, max_len: u8) -> u128 {
+    if max_len <= len {
+        return 0
+    }
+    match u32::from(max_len - len) {
+        diff if diff >= 128 => ::std::u128::MAX,
+        127 => ::std::u128::MAX - 1,
+        diff => (2u128 << diff) - 2
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use bgp::Announcement;
+
+    fn make_origin(asn: u32, prefix: &str, max_len: u8) -> AddressOrigin {
+        let prefix: AddressPrefix = prefix.parse().unwrap();
+        AddressOrigin::new(AsId::from(asn), prefix, max_len)
+    }
+
+    fn make_announcements(items: &[(&str, u32)]) -> Announcements {
+        let mut res = Announcements::new();
+        for &(prefix, asn) in items {
+            res.push(Announcement::new(
+                prefix.parse().unwrap(), AsId::from(asn)
+            ))
+        }
+        res
+    }
+
+    #[test]
+    fn should_count_more_specifics() {
+        assert_eq!(more_specifics(24, 24), 0);
+        assert_eq!(more_specifics(23, 24), 2);
+        assert_eq!(more_specifics(22, 24), 6);
+        assert_eq!(more_specifics(0, 128), ::std::u128::MAX);
+        assert_eq!(more_specifics(1, 128), ::std::u128::MAX - 1);
+        assert_eq!(more_specifics(2, 128), (1u128 << 127) - 2);
+    }
+
+    #[test]
+    fn should_report_loose_origins() {
+        let origins = vec![
+            make_origin(64496, "10.0.0.0/24", 24),
+            make_origin(64496, "10.1.0.0/16", 24),
+            make_origin(64497, "2001:db8::/32", 48),
+        ];
+        let report = MaxLengthReport::analyse(&origins, None);
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.loose(), 2);
+        let groups: Vec<_> = report.iter().collect();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].as_id(), AsId::from(64496));
+        assert_eq!(groups[0].iter().next().unwrap().exposed(), 510);
+        assert_eq!(groups[1].as_id(), AsId::from(64497));
+    }
+
+    #[test]
+    fn should_write_exact_counts() {
+        let origins = vec![make_origin(64497, "2001:db8::/32", 128)];
+        let json = MaxLengthReport::analyse(&origins, None).to_json();
+        let item = &json["groups"][0]["roas"][0];
+        assert_eq!(item["authorized"], "158456325028528675187087900670");
+        assert_eq!(item["exposed"], "158456325028528675187087900670");
+    }
+
+    #[test]
+    fn should_consider_announcements() {
+        let origins = vec![
+            make_origin(64496, "10.0.0.0/23", 24),
+            make_origin(64496, "10.1.0.0/23", 24),
+        ];
+        let announcements = make_announcements(&[
+            ("10.0.0.0/23", 64496),
+            ("10.0.0.0/24", 64496),
+            ("10.0.1.0/24", 64496),
+            ("10.1.0.0/24", 64496),
+            ("10.1.1.0/24", 64511),
+        ]);
+        let report = MaxLengthReport::analyse(
+            &origins, Some(&announcements)
+        );
+        assert_eq!(report.loose(), 1);
+        let item = report.iter().next().unwrap().iter().next().unwrap();
+        assert_eq!(item.origin().prefix(), "10.1.0.0/23".parse().unwrap());
+        assert_eq!(item.exposed(), 1);
+        assert_eq!(
+            item.suggested(),
+            vec![
+                "10.1.0.0/23".parse().unwrap(),
+                "10.1.0.0/24".parse().unwrap()
+            ]
+        );
+    }
+}
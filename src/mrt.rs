@@ -0,0 +1,402 @@
+//! Reading route announcements from MRT RIB dumps.
+//!
+//! Route collectors such as RIPE RIS and RouteViews publish snapshots of
+//! their routing tables in the MRT format defined in RFC 6396. This module
+//! extracts route announcements from the TABLE_DUMP_V2 records of such a
+//! dump, including the ADD-PATH variants defined in RFC 8050. All other
+//! record types are skipped.
+//!
+//! A RIB record contains the routes for a single prefix as seen by all
+//! peers of the collector. Each distinct origin AS found in the AS paths of
+//! these routes results in one announcement. Following RFC 6811, the origin
+//! of a path that ends in an AS_SET is unknown. Such routes are reported
+//! with an origin of AS0 which never matches any route origin. Routes with
+//! an empty AS path are skipped.
+//!
+//! Dumps are usually published compressed. They need to be decompressed
+//! before they can be read.
+
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use rpki::asres::AsId;
+use super::bgp::{Announcement, Announcements};
+use super::origins::AddressPrefix;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The MRT type for TABLE_DUMP_V2.
+const TABLE_DUMP_V2: u16 = 13;
+
+/// The TABLE_DUMP_V2 subtypes we are interested in.
+const RIB_IPV4_UNICAST: u16 = 2;
+const RIB_IPV6_UNICAST: u16 = 4;
+const RIB_IPV4_UNICAST_ADDPATH: u16 = 8;
+const RIB_IPV6_UNICAST_ADDPATH: u16 = 9;
+
+/// The BGP path attribute type of AS_PATH.
+const AS_PATH: u8 = 2;
+
+/// The AS_PATH segment type of AS_SET.
+const AS_SET: u8 = 1;
+
+
+//------------ read_announcements --------------------------------------------
+
+/// Reads all announcements from an MRT dump and adds them to `target`.
+pub fn read_announcements<R: io::Read>(
+    reader: &mut R,
+    target: &mut Announcements
+) -> Result<(), MrtError> {
+    let mut record = 0;
+    loop {
+        record += 1;
+        let header = match read_header(reader) {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(()),
+            Err(err) => return Err(MrtError::new(record, err.into()))
+        };
+        // The length comes straight from the dump, so don’t trust it for
+        // allocating the buffer and let it grow with the data instead.
+        let mut body = Vec::new();
+        match reader.by_ref().take(header.length).read_to_end(&mut body) {
+            Ok(len) if len as u64 == header.length => { }
+            Ok(_) => {
+                let err = io::Error::from(io::ErrorKind::UnexpectedEof);
+                return Err(MrtError::new(record, err.into()))
+            }
+            Err(err) => return Err(MrtError::new(record, err.into()))
+        }
+        if header.mrt_type != TABLE_DUMP_V2 {
+            continue
+        }
+        let res = match header.subtype {
+            RIB_IPV4_UNICAST => parse_rib(&body, false, false, target),
+            RIB_IPV6_UNICAST => parse_rib(&body, true, false, target),
+            RIB_IPV4_UNICAST_ADDPATH => parse_rib(&body, false, true, target),
+            RIB_IPV6_UNICAST_ADDPATH => parse_rib(&body, true, true, target),
+            _ => Ok(())
+        };
+        if let Err(err) = res {
+            return Err(MrtError::new(record, err))
+        }
+    }
+}
+
+
+//------------ Header --------------------------------------------------------
+
+/// The parts of the common MRT header we need.
+struct Header {
+    mrt_type: u16,
+    subtype: u16,
+    length: u64,
+}
+
+/// Reads the header of the next record.
+///
+/// Returns `Ok(None)` if the reader is at its end.
+fn read_header<R: io::Read>(
+    reader: &mut R
+) -> Result<Option<Header>, io::Error> {
+    let mut buf = [0u8; 12];
+    let mut pos = 0;
+    while pos < buf.len() {
+        match reader.read(&mut buf[pos..]) {
+            Ok(0) => {
+                if pos == 0 {
+                    return Ok(None)
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into())
+            }
+            Ok(len) => pos += len,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => { }
+            Err(err) => return Err(err)
+        }
+    }
+    // The first four octets are the timestamp which we don’t need.
+    let mut parser = Parser(&buf[4..]);
+    Ok(Some(Header {
+        mrt_type: parser.u16().unwrap(),
+        subtype: parser.u16().unwrap(),
+        length: u64::from(parser.u32().unwrap()),
+    }))
+}
+
+
+//------------ RIB Records ---------------------------------------------------
+
+/// Parses a RIB record and adds its announcements to `target`.
+fn parse_rib(
+    data: &[u8],
+    v6: bool,
+    add_path: bool,
+    target: &mut Announcements
+) -> Result<(), ErrorKind> {
+    let mut parser = Parser(data);
+    let _sequence = parser.u32()?;
+    let prefix = parse_prefix(&mut parser, v6)?;
+    let mut origins: Vec<AsId> = Vec::new();
+    for _ in 0..parser.u16()? {
+        let _peer_index = parser.u16()?;
+        let _originated = parser.u32()?;
+        if add_path {
+            let _path_id = parser.u32()?;
+        }
+        let attr_len = parser.u16()?;
+        let attrs = parser.take(attr_len as usize)?;
+        if let Some(origin) = find_origin(attrs)? {
+            if !origins.contains(&origin) {
+                origins.push(origin)
+            }
+        }
+    }
+    for origin in origins {
+        target.push(Announcement::new(prefix, origin))
+    }
+    Ok(())
+}
+
+/// Parses the prefix of a RIB record.
+fn parse_prefix(
+    parser: &mut Parser,
+    v6: bool
+) -> Result<AddressPrefix, ErrorKind> {
+    let len = parser.u8()?;
+    if len > if v6 { 128 } else { 32 } {
+        return Err(ErrorKind::BadPrefixLength(len))
+    }
+    let bytes = parser.take((len as usize + 7) / 8)?;
+    let mut addr = 0u128;
+    for (idx, byte) in bytes.iter().enumerate() {
+        addr |= u128::from(*byte) << (120 - idx * 8)
+    }
+    if len < 128 {
+        addr &= !(::std::u128::MAX >> len)
+    }
+    let addr = if v6 {
+        IpAddr::V6(Ipv6Addr::from(addr))
+    }
+    else {
+        IpAddr::V4(Ipv4Addr::from((addr >> 96) as u32))
+    };
+    Ok(AddressPrefix::new(addr, len))
+}
+
+/// Returns the origin AS from the path attributes of a route.
+///
+/// In TABLE_DUMP_V2, AS numbers in the AS_PATH are always four octets.
+fn find_origin(attrs: &[u8]) -> Result<Option<AsId>, ErrorKind> {
+    let mut parser = Parser(attrs);
+    while !parser.is_empty() {
+        let flags = parser.u8()?;
+        let attr_type = parser.u8()?;
+        let len = if flags & 0x10 != 0 {
+            parser.u16()? as usize
+        }
+        else {
+            parser.u8()? as usize
+        };
+        let value = parser.take(len)?;
+        if attr_type == AS_PATH {
+            return origin_from_path(value)
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the origin AS of an AS_PATH attribute value.
+fn origin_from_path(path: &[u8]) -> Result<Option<AsId>, ErrorKind> {
+    let mut parser = Parser(path);
+    let mut res = None;
+    while !parser.is_empty() {
+        let segment_type = parser.u8()?;
+        let count = parser.u8()?;
+        let mut last = None;
+        for _ in 0..count {
+            last = Some(parser.u32()?);
+        }
+        if let Some(last) = last {
+            res = Some(if segment_type == AS_SET {
+                AsId::from(0)
+            }
+            else {
+                AsId::from(last)
+            })
+        }
+    }
+    Ok(res)
+}
+
+
+//------------ Parser --------------------------------------------------------
+
+/// A simple parser for big-endian binary data.
+struct Parser<'a>(&'a [u8]);
+
+impl<'a> Parser<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ErrorKind> {
+        if self.0.len() < len {
+            return Err(ErrorKind::Short)
+        }
+        let (res, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(res)
+    }
+
+    fn u8(&mut self) -> Result<u8, ErrorKind> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ErrorKind> {
+        let data = self.take(2)?;
+        Ok(u16::from(data[0]) << 8 | u16::from(data[1]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ErrorKind> {
+        let data = self.take(4)?;
+        Ok(
+            u32::from(data[0]) << 24 | u32::from(data[1]) << 16
+            | u32::from(data[2]) << 8 | u32::from(data[3])
+        )
+    }
+}
+
+
+//------------ MrtError ------------------------------------------------------
+
+/// An error happened while reading an MRT dump.
+#[derive(Debug, Fail)]
+#[fail(display="record {}: {}", record, kind)]
+pub struct MrtError {
+    /// The number of the record, starting at 1.
+    record: usize,
+
+    /// What went wrong.
+    kind: ErrorKind,
+}
+
+impl MrtError {
+    fn new(record: usize, kind: ErrorKind) -> Self {
+        MrtError { record, kind }
+    }
+
+    pub fn record(&self) -> usize {
+        self.record
+    }
+}
+
+
+//------------ ErrorKind -----------------------------------------------------
+
+#[derive(Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display="{}", _0)]
+    Io(io::Error),
+
+    #[fail(display="record too short")]
+    Short,
+
+    #[fail(display="bad prefix length {}", _0)]
+    BadPrefixLength(u8),
+}
+
+impl From<io::Error> for ErrorKind {
+    fn from(err: io::Error) -> ErrorKind {
+        ErrorKind::Io(err)
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn record(subtype: u16, body: &[u8]) -> Vec<u8> {
+        let mut res = vec![0, 0, 0, 0, 0, 13];
+        res.extend_from_slice(&[(subtype >> 8) as u8, subtype as u8]);
+        let len = body.len();
+        res.extend_from_slice(&[
+            (len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8,
+            len as u8
+        ]);
+        res.extend_from_slice(body);
+        res
+    }
+
+    fn entry(path_type: u8, path: &[u32]) -> Vec<u8> {
+        let mut attr = vec![path_type, path.len() as u8];
+        for asn in path {
+            attr.extend_from_slice(&[
+                (asn >> 24) as u8, (asn >> 16) as u8, (asn >> 8) as u8,
+                *asn as u8
+            ]);
+        }
+        // Peer index and originated time, then an ORIGIN attribute
+        // before the AS_PATH.
+        let mut res = vec![0, 1, 0, 0, 0, 0];
+        let attr_len = 4 + 3 + attr.len();
+        res.extend_from_slice(&[(attr_len >> 8) as u8, attr_len as u8]);
+        res.extend_from_slice(&[0x40, 1, 1, 0]);
+        res.extend_from_slice(&[0x40, AS_PATH, attr.len() as u8]);
+        res.extend_from_slice(&attr);
+        res
+    }
+
+    #[test]
+    fn should_read_rib_dump() {
+        let mut data = record(1, &[1, 2, 3, 4]);
+
+        let mut body = vec![0, 0, 0, 1, 23, 10, 0, 3, 0, 3];
+        body.extend(entry(2, &[64511, 64496]));
+        body.extend(entry(2, &[64510, 64511, 64496]));
+        body.extend(entry(2, &[64497]));
+        data.extend(record(RIB_IPV4_UNICAST, &body));
+
+        let mut body = vec![0, 0, 0, 2, 32, 0x20, 0x01, 0x0d, 0xb8, 0, 1];
+        body.extend(entry(1, &[64496, 64497]));
+        data.extend(record(RIB_IPV6_UNICAST, &body));
+
+        let mut res = Announcements::new();
+        read_announcements(&mut &data[..], &mut res).unwrap();
+        assert_eq!(
+            res.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Announcement::new(
+                    "10.0.2.0/23".parse().unwrap(), AsId::from(64496)
+                ),
+                Announcement::new(
+                    "10.0.2.0/23".parse().unwrap(), AsId::from(64497)
+                ),
+                Announcement::new(
+                    "2001:db8::/32".parse().unwrap(), AsId::from(0)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_reject_truncated_records() {
+        let data = record(RIB_IPV4_UNICAST, &[0, 0, 0, 1, 24, 10, 0]);
+        let mut res = Announcements::new();
+        let err = read_announcements(&mut &data[..], &mut res).unwrap_err();
+        assert_eq!(err.record(), 1);
+
+        let mut data = record(RIB_IPV4_UNICAST, &[0, 0, 0, 1, 0, 0, 0]);
+        data.truncate(data.len() - 1);
+        assert!(read_announcements(&mut &data[..], &mut res).is_err());
+
+        // A huge length must not be taken at face value.
+        let mut data = record(RIB_IPV4_UNICAST, &[0, 0, 0, 1, 0, 0, 0]);
+        data[8..12].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        let err = read_announcements(&mut &data[..], &mut res).unwrap_err();
+        assert_eq!(err.record(), 1);
+    }
+}
@@ -0,0 +1,846 @@
+//! Running Routinator.
+//!
+//! This module contains everything the `routinator` binary does once it
+//! has its configuration: processing the repository once or repeatedly,
+//! running the RTR server, writing the outputs, and all the additional
+//! commands. The entry point is [`run`].
+//!
+//! Output formats are taken from the [`OutputRegistry`] given to [`run`].
+//! A binary of your own can thus add its own formats to the registry,
+//! create the configuration with it, and then hand both over.
+//!
+//! [`run`]: fn.run.html
+//! [`OutputRegistry`]: ../output/struct.OutputRegistry.html
+
+use std::{io, process, thread};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use chrono::Utc;
+use daemonize::Daemonize;
+use futures::future;
+use futures::future::Future;
+use json;
+use tokio;
+use tokio::timer::Delay;
+use super::atomic::AtomicFile;
+use super::bgp::Announcements;
+use super::config::{
+    Command, Config, DiffCommand, ExplainCommand, InputFormat, OutputTarget,
+    ReportCommand, ReportFormat, TalCommand, ValidateCommand, ValidateInput,
+};
+use super::expiry::ExpiryForecast;
+use super::maxlen::MaxLengthReport;
+use super::origins::{AddressOrigins, OriginsHistory, RouteOrigins};
+use super::output::{Metadata, OutputRegistry};
+use super::provenance::Explanation;
+use super::report::ValidationReport;
+use super::repository::{ProcessingError, Repository, RepositoryOptions};
+use super::rtr::{rtr_listener, NotifySender};
+use super::slurm::LocalExceptions;
+use super::snapshot::{self, SnapshotDiff};
+use super::state;
+use super::tals::{TalDir, TalStatus};
+use super::validity::ValiditySummary;
+
+
+//------------ run -----------------------------------------------------------
+
+/// Runs the command given by `config`.
+///
+/// Output formats are looked up in `registry` which should be the same
+/// registry that was used to create `config`. Both need to live for the
+/// entire run of the program since the RTR server and the refreshes in
+/// repeat mode hold on to them.
+///
+/// Returns an error if the command failed. The caller should exit with a
+/// non-zero status code in this case.
+pub fn run(
+    config: &'static Config,
+    registry: &'static OutputRegistry
+) -> Result<(), ProcessingError> {
+    match config.command {
+        Command::Tal(ref command) => run_tal(config, command),
+        Command::Report(ref command) => run_report(config, command),
+        Command::Validate(ref command) => run_validate(config, command),
+        Command::Explain(ref command) => run_explain(config, command),
+        Command::Diff(ref command) => run_diff(command),
+        Command::Run => {
+            if config.mode.is_once() {
+                run_once(config, registry)
+            }
+            else {
+                run_forever(config, registry)
+            }
+        }
+    }
+}
+
+
+/// Returns the repository options given by the configuration.
+fn repository_options(config: &Config) -> RepositoryOptions {
+    RepositoryOptions {
+        strict: config.strict,
+        skip_broken_tals: config.skip_broken_tals,
+        .. Default::default()
+    }
+}
+
+/// Creates the repository in the configured directories.
+///
+/// If `rsync` is `false`, the repository will not be updated.
+fn open_repository(
+    config: &Config,
+    options: RepositoryOptions,
+    rsync: bool
+) -> Result<Repository, ProcessingError> {
+    Repository::with_options(
+        config.cache_dir.clone(), config.tal_dir.clone(), options, rsync
+    )
+}
+
+
+fn run_forever(
+    config: &'static Config,
+    registry: &'static OutputRegistry
+) -> Result<(), ProcessingError> {
+    if !config.update {
+        warn!("no-update option ignored in repeat mode");
+    }
+    if !config.process {
+        warn!("no-process option ignored in repeat mode");
+    }
+
+    let repo = open_repository(config, repository_options(config), true)?;
+
+    if config.mode.is_daemon() {
+        if let Err(err) = Daemonize::new().start() {
+            println!("Daemonization failed: {}", err);
+            return Err(ProcessingError::Other)
+        }
+    }
+
+    // Start out with validation so that we only fire up our sockets once we
+    // are actually ready.
+    let start = Instant::now();
+    let last_update = match repo.update() {
+        Ok(()) => Some(Utc::now()),
+        Err(_) => {
+            warn!("Update failed. Continuing anyway.");
+            None
+        }
+    };
+    let update_duration = start.elapsed();
+    let start = Instant::now();
+    let (origins, report) = repo.process()?;
+    let metadata = Metadata::from_route_origins(
+        &origins, last_update
+    ).with_durations(Some(update_duration), Some(start.elapsed()));
+    let exceptions = load_exceptions(&config)?;
+    let history = match restore_history(config) {
+        Some(history) => {
+            if let Err(err) = history.update(Some(origins), &exceptions) {
+                error!("{}\nAborted.", err);
+                return Err(ProcessingError::Other)
+            }
+            history
+        }
+        None => {
+            OriginsHistory::new(
+                address_origins(origins, &exceptions)?,
+                config.history_size
+            )
+        }
+    };
+    save_history(&history, config);
+    let mut hook = UpdateHook::default();
+    refresh_outputs(
+        &history, &report, &metadata, true, &mut hook, config, registry
+    );
+
+    info!("Starting RTR listener...");
+    
+    let (notify, rtr) = rtr_listener(history.clone(), config);
+
+    tokio::runtime::run(
+        update_future(repo, history, notify, hook, config, registry)
+        .select(rtr).map(|_| ()).map_err(|_| ())
+    );
+
+    Ok(())
+}
+
+
+fn update_future(
+    repo: Repository,
+    history: OriginsHistory,
+    notify: NotifySender,
+    hook: UpdateHook,
+    config: &'static Config,
+    registry: &'static OutputRegistry
+) -> impl Future<Item=(), Error=()> {
+    let state = (repo, history, notify, hook);
+    future::loop_fn(state, move |(repo, history, mut notify, mut hook)| {
+        Delay::new(Instant::now() + config.refresh)
+        .map_err(|e| error!("timer failed; err={:?}", e))
+        .and_then(move |_| {
+            repo.start();
+            Ok(repo)
+        })
+        .and_then(|repo| {
+            let start = Instant::now();
+            repo.update_async()
+            .then(move |res| {
+                // Print error but keep going.
+                let last_update = match res {
+                    Ok(()) => Some(Utc::now()),
+                    Err(err) => {
+                        error!("repository update failed: {}", err);
+                        None
+                    }
+                };
+                Ok((repo, last_update, start.elapsed()))
+            })
+        })
+        .and_then(move |(repo, last_update, update_duration)| {
+            let start = Instant::now();
+            repo.process_async()
+            .then(move |origins| {
+                let (origins, report) = match origins {
+                    Ok(res) => res,
+                    Err(err) => {
+                        error!("repository processing failed; err={:?}", err);
+                        return Ok(future::Loop::Continue(
+                            (repo, history, notify, hook)
+                        ))
+                    }
+                };
+                let metadata = Metadata::from_route_origins(
+                    &origins, last_update
+                ).with_durations(
+                    Some(update_duration), Some(start.elapsed())
+                );
+                let exceptions = match load_exceptions(config) {
+                    Ok(exceptions) => exceptions,
+                    Err(err) => {
+                        error!("failed loading exceptions: {}", err);
+                        return Ok(future::Loop::Continue(
+                            (repo, history, notify, hook)
+                        ))
+                    }
+                };
+                let must_notify = match history.update(
+                    Some(origins), &exceptions
+                ) {
+                    Ok(must_notify) => must_notify,
+                    Err(err) => {
+                        error!("failed updating route origins: {}", err);
+                        return Ok(future::Loop::Continue(
+                            (repo, history, notify, hook)
+                        ))
+                    }
+                };
+                debug!("New serial is {}.", history.serial());
+                if must_notify {
+                    save_history(&history, config);
+                    debug!("Sending out notifications.");
+                    notify.notify();
+                }
+                refresh_outputs(
+                    &history, &report, &metadata, must_notify, &mut hook,
+                    config, registry
+                );
+                Ok(future::Loop::Continue((repo, history, notify, hook)))
+            })
+        })
+    })
+}
+
+
+/// Restores the history from the state file if there is one.
+fn restore_history(config: &Config) -> Option<OriginsHistory> {
+    let path = match config.state_file {
+        Some(ref path) => path,
+        None => return None
+    };
+    match state::load(path, config.history_size) {
+        Ok(Some(history)) => {
+            info!(
+                "Restored session {} at serial {} from {}.",
+                history.session(), history.serial(), path.display()
+            );
+            Some(history)
+        }
+        Ok(None) => None,
+        Err(err) => {
+            warn!(
+                "Failed to restore RTR state from {}: {}. Starting afresh.",
+                path.display(), err
+            );
+            None
+        }
+    }
+}
+
+
+/// Writes the history to the state file if there is one.
+fn save_history(history: &OriginsHistory, config: &Config) {
+    if let Some(ref path) = config.state_file {
+        if let Err(err) = state::save(history, path) {
+            error!(
+                "Failed to write RTR state to {}: {}", path.display(), err
+            );
+        }
+    }
+}
+
+
+/// Rewrites the output files and runs the update hook in repeat mode.
+///
+/// Only outputs to files are written. The update hook is only run if all
+/// of them have been written successfully. Whether the route origins have
+/// changed since the last run is given via `changed`.
+fn refresh_outputs(
+    history: &OriginsHistory,
+    report: &ValidationReport,
+    metadata: &Metadata,
+    changed: bool,
+    hook: &mut UpdateHook,
+    config: &Config,
+    registry: &OutputRegistry
+) {
+    let (current, serial) = history.current_and_serial();
+    // Failures have already been logged for each output.
+    if output(&current, report, metadata, true, config, registry).is_err() {
+        return
+    }
+    if let Some(ref command) = config.update_hook {
+        hook.run(command, u32::from(serial), changed)
+    }
+}
+
+
+/// The update hook in repeat mode.
+///
+/// The hook runs on its own thread so a slow hook doesn’t hold up the next
+/// refresh. It is never run more than once at the same time, though: if
+/// it is still running when the next refresh is done, it is postponed to
+/// the refresh after that. Whether the route origins have changed is
+/// remembered across postponed runs.
+#[derive(Default)]
+struct UpdateHook {
+    /// Is the hook currently running?
+    running: Arc<AtomicBool>,
+
+    /// Have the route origins changed since the hook was last started?
+    changed: bool,
+}
+
+impl UpdateHook {
+    /// Runs the hook given by the shell command `hook` if it isn’t running.
+    fn run(&mut self, hook: &str, serial: u32, changed: bool) {
+        self.changed = self.changed || changed;
+        if self.running.swap(true, Ordering::SeqCst) {
+            info!(
+                "Update hook is still running. Postponing it to the next \
+                 refresh."
+            );
+            return
+        }
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg(hook)
+            .env("ROUTINATOR_SERIAL", serial.to_string())
+            .env("ROUTINATOR_CHANGED", if self.changed { "1" } else { "0" });
+        self.changed = false;
+        let hook = String::from(hook);
+        let running = self.running.clone();
+        thread::spawn(move || {
+            match command.status() {
+                Ok(status) if status.success() => {
+                    debug!("Update hook finished.")
+                }
+                Ok(status) => {
+                    warn!("Update hook '{}' failed: {}", hook, status)
+                }
+                Err(err) => {
+                    error!("Failed to run update hook '{}': {}", hook, err)
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+fn run_once(
+    config: &Config,
+    registry: &OutputRegistry
+) -> Result<(), ProcessingError> {
+    let exceptions = load_exceptions(&config)?;
+
+    let repo = open_repository(
+        config, repository_options(config), config.update
+    )?;
+    let start = Instant::now();
+    let last_update = match repo.update() {
+        Ok(()) if config.update => Some(Utc::now()),
+        Ok(()) => None,
+        Err(_) => {
+            warn!("Update failed. Continuing anyway.");
+            None
+        }
+    };
+
+    let update_duration = if config.update {
+        Some(start.elapsed())
+    }
+    else {
+        None
+    };
+
+    if !config.process {
+        return Ok(())
+    }
+    let start = Instant::now();
+    let (roas, report) = match repo.process() {
+        Ok(res) => res,
+        Err(err) => {
+            error!("Fatal error during validation. Aborted.");
+            return Err(err)
+        }
+    };
+    debug!("Found {} ROAs.", roas.len());
+
+    let metadata = Metadata::from_route_origins(
+        &roas, last_update
+    ).with_durations(update_duration, Some(start.elapsed()));
+    let roas = address_origins(roas, &exceptions)?;
+
+    output(&roas, &report, &metadata, false, config, registry)
+}
+
+
+fn run_tal(
+    config: &Config,
+    command: &TalCommand
+) -> Result<(), ProcessingError> {
+    let tals = TalDir::new(config.tal_dir.clone());
+    match *command {
+        TalCommand::List => {
+            for entry in tals.entries()? {
+                let status = entry.status();
+                let uri = match status {
+                    TalStatus::Enabled | TalStatus::Disabled => {
+                        entry.read().ok().and_then(|tal| {
+                            tal.uris().next().map(|uri| uri.to_string())
+                        })
+                    }
+                    _ => None
+                };
+                println!(
+                    "{:<16} {:<12} {}",
+                    entry.name(), status, uri.unwrap_or_default()
+                );
+            }
+        }
+        TalCommand::Check => {
+            let check = tals.check()?;
+            for problem in check.problems() {
+                println!(
+                    "{}: {}",
+                    if problem.is_fatal() { "error" } else { "warning" },
+                    problem
+                );
+            }
+            for entry in check.usable() {
+                println!("{}: ok", entry.name());
+            }
+            for entry in check.broken() {
+                println!("{}: broken", entry.name());
+            }
+            // All problems have been printed already. Since the check
+            // is meant for use in scripts, its result becomes the exit
+            // code via the error.
+            if check.has_fatal() {
+                return Err(ProcessingError::BrokenTals)
+            }
+        }
+        TalCommand::Enable(ref name) => {
+            tals.enable(name)?;
+            info!("Enabled TAL {}.", name);
+        }
+        TalCommand::Disable(ref name) => {
+            tals.disable(name)?;
+            info!("Disabled TAL {}.", name);
+        }
+        TalCommand::Add { ref source, ref name } => {
+            let name = tals.add(source, name.as_ref().map(AsRef::as_ref))?;
+            info!("Added TAL {}.", name);
+        }
+        TalCommand::InstallArin { accept } => {
+            tals.install_arin(accept)?;
+            info!("Installed the ARIN TAL.");
+        }
+    }
+    Ok(())
+}
+
+
+fn run_report(
+    config: &Config,
+    command: &ReportCommand
+) -> Result<(), ProcessingError> {
+    let exceptions = load_exceptions(&config)?;
+    let announcements = match *command {
+        ReportCommand::MaxLength(_, Some(ref path)) => {
+            match Announcements::from_file(path) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    error!(
+                        "Failed to load announcements from {}: {}",
+                        path.display(), err
+                    );
+                    return Err(ProcessingError::Other)
+                }
+            }
+        }
+        _ => None
+    };
+
+    let mut options = repository_options(config);
+    if let ReportCommand::Expiry(..) = *command {
+        options.expiry = true
+    }
+    let (roas, report) = process_once(config, options)?;
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match *command {
+        ReportCommand::Overclaims(ReportFormat::Json) => {
+            writeln!(stdout, "{}", report.overclaims_json().pretty(2))?;
+        }
+        ReportCommand::Overclaims(_) => {
+            report.write_overclaims(&mut stdout)?;
+        }
+        ReportCommand::MaxLength(format, _) => {
+            let roas = address_origins(roas, &exceptions)?;
+            let report = MaxLengthReport::analyse(
+                roas.iter(), announcements.as_ref()
+            );
+            match format {
+                ReportFormat::Json => {
+                    writeln!(stdout, "{}", report.to_json().pretty(2))?
+                }
+                _ => report.write_text(&mut stdout)?,
+            }
+        }
+        ReportCommand::Expiry(format, window) => {
+            let forecast = ExpiryForecast::new(
+                &report, Utc::now(),
+                ::chrono::Duration::from_std(window).map_err(|err| {
+                    error!("Invalid time window: {}", err);
+                    ProcessingError::Other
+                })?
+            );
+            match format {
+                ReportFormat::Text => forecast.write_text(&mut stdout)?,
+                ReportFormat::Json => {
+                    writeln!(stdout, "{}", forecast.to_json().pretty(2))?
+                }
+                ReportFormat::Metrics => forecast.write_metrics(&mut stdout)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+
+fn run_validate(
+    config: &Config,
+    command: &ValidateCommand
+) -> Result<(), ProcessingError> {
+    let exceptions = load_exceptions(&config)?;
+    let announcements = match command.input {
+        ValidateInput::Single(announcement) => {
+            let mut res = Announcements::new();
+            res.push(announcement);
+            Ok(res)
+        }
+        ValidateInput::File(ref path) => match command.input_format {
+            InputFormat::Text => Announcements::from_file(path),
+            InputFormat::Mrt => Announcements::from_mrt_file(path),
+        }
+        ValidateInput::Stdin => {
+            let stdin = io::stdin();
+            match command.input_format {
+                InputFormat::Text => {
+                    Announcements::from_reader(stdin.lock())
+                }
+                InputFormat::Mrt => {
+                    Announcements::from_mrt_reader(stdin.lock())
+                }
+            }
+        }
+    };
+    let announcements = match announcements {
+        Ok(res) => res,
+        Err(err) => {
+            error!("Failed to load announcements: {}", err);
+            return Err(ProcessingError::Other)
+        }
+    };
+
+    let (roas, _) = process_once(config, repository_options(config))?;
+    let roas = address_origins(roas, &exceptions)?;
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if command.summary {
+        let summary = ValiditySummary::new(&roas, announcements.iter());
+        match command.format {
+            ReportFormat::Json => {
+                writeln!(stdout, "{}", summary.to_json().pretty(2))?;
+            }
+            _ => summary.write_text(&mut stdout)?
+        }
+        return Ok(())
+    }
+    match command.format {
+        ReportFormat::Json => {
+            let mut routes = json::JsonValue::new_array();
+            for announcement in announcements.iter() {
+                let _ = routes.push(roas.validate(*announcement).to_json());
+            }
+            let mut res = json::JsonValue::new_object();
+            res["validated_routes"] = routes;
+            writeln!(stdout, "{}", res.pretty(2))?;
+        }
+        _ => {
+            for announcement in announcements.iter() {
+                roas.validate(*announcement).write_text(&mut stdout)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+
+fn run_explain(
+    config: &Config,
+    command: &ExplainCommand
+) -> Result<(), ProcessingError> {
+    let exceptions = load_exceptions(&config)?;
+    let mut options = repository_options(config);
+    options.provenance = true;
+    let (roas, _) = process_once(config, options)?;
+    let roas = address_origins(roas, &exceptions)?;
+    let explanation = Explanation::new(&roas, command.prefix, command.asn);
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match command.format {
+        ReportFormat::Json => {
+            writeln!(stdout, "{}", explanation.to_json().pretty(2))?;
+        }
+        _ => explanation.write_text(&mut stdout)?
+    }
+    Ok(())
+}
+
+
+fn run_diff(command: &DiffCommand) -> Result<(), ProcessingError> {
+    let load = |path: &Path| {
+        snapshot::load(path).map_err(|err| {
+            error!("Failed to load {}: {}", path.display(), err);
+            ProcessingError::Other
+        })
+    };
+    let diff = SnapshotDiff::new(&load(&command.old)?, &load(&command.new)?);
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match command.format {
+        ReportFormat::Json => {
+            writeln!(stdout, "{}", diff.to_json().pretty(2))?;
+        }
+        _ => diff.write_text(&mut stdout)?
+    }
+    Ok(())
+}
+
+
+/// Updates and processes the repository once.
+fn process_once(
+    config: &Config,
+    options: RepositoryOptions
+) -> Result<(RouteOrigins, ValidationReport), ProcessingError> {
+    let repo = open_repository(config, options, config.update)?;
+    if let Err(_) = repo.update() {
+        warn!("Update failed. Continuing anyway.");
+    }
+    match repo.process() {
+        Ok(res) => Ok(res),
+        Err(err) => {
+            error!("Fatal error during validation. Aborted.");
+            Err(err)
+        }
+    }
+}
+
+
+/// Creates the set of address origins from the validated route origins.
+fn address_origins(
+    origins: RouteOrigins,
+    exceptions: &LocalExceptions
+) -> Result<AddressOrigins, ProcessingError> {
+    AddressOrigins::from_route_origins(origins, exceptions).map_err(|err| {
+        error!("{}\nAborted.", err);
+        ProcessingError::Other
+    })
+}
+
+fn load_exceptions(
+    config: &Config
+) -> Result<LocalExceptions, ProcessingError> {
+    match config.exceptions {
+        Some(ref path) => match LocalExceptions::from_file(path) {
+            Ok(res) => Ok(res),
+            Err(err) => {
+                error!("Failed to load exceptions: {}\nAborted.", err);
+                Err(ProcessingError::Other)
+            }
+        }
+        None => Ok(LocalExceptions::empty())
+    }
+}
+
+
+/// Writes all outputs.
+///
+/// If `files_only` is `true`, outputs to stdout are skipped. A failing
+/// output doesn’t stop the remaining ones from being written. Each failure
+/// is logged and an error is returned once all outputs have been tried.
+fn output(
+    roas: &AddressOrigins,
+    report: &ValidationReport,
+    metadata: &Metadata,
+    files_only: bool,
+    config: &Config,
+    registry: &OutputRegistry
+) -> Result<(), ProcessingError> {
+    let metadata = metadata.clone().with_origins(roas, &config.selection);
+    let mut failed = false;
+    for target in &config.outputs {
+        if files_only && target.path.is_none() {
+            continue
+        }
+        if let Err(err) = write_output(
+            roas, report, &metadata, target, registry, config
+        ) {
+            match target.path {
+                Some(ref path) => {
+                    error!(
+                        "Failed to write output file {}: {}",
+                        path.display(), err
+                    )
+                }
+                None => error!("Failed to write output: {}", err)
+            }
+            failed = true;
+        }
+    }
+    if failed {
+        Err(ProcessingError::Other)
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Writes a single output.
+///
+/// Files are written atomically: the output is first written to a
+/// temporary file which replaces the target file only if everything went
+/// well.
+fn write_output(
+    roas: &AddressOrigins,
+    report: &ValidationReport,
+    metadata: &Metadata,
+    target: &OutputTarget,
+    registry: &OutputRegistry,
+    config: &Config
+) -> Result<(), ProcessingError> {
+    let mut writer = match registry.writer(
+        &target.format, &config.output_options, report
+    ) {
+        Some(writer) => writer,
+        // Config has checked the names, so this is the `none` format.
+        None => return Ok(())
+    };
+    let output = FileOrStdout::open(target.path.as_ref())?;
+    let res = {
+        let mut output = output.lock();
+        writer.write_origins(metadata, roas, &config.selection, &mut output)
+    };
+    match res {
+        Ok(()) => output.commit().map_err(Into::into),
+        Err(err) => {
+            output.abort();
+            Err(err.into())
+        }
+    }
+}
+
+
+enum FileOrStdout<F, S> {
+    File(F),
+    Stdout(S)
+}
+
+impl FileOrStdout<AtomicFile, io::Stdout> {
+    fn open<P: AsRef<Path>>(path: Option<P>) -> Result<Self, io::Error> {
+        match path {
+            Some(path) => AtomicFile::create(path).map(FileOrStdout::File),
+            None => Ok(FileOrStdout::Stdout(io::stdout())),
+        }
+    }
+
+    fn lock(&self) -> FileOrStdout<&File, io::StdoutLock> {
+        match *self {
+            FileOrStdout::File(ref file) => FileOrStdout::File(file.file()),
+            FileOrStdout::Stdout(ref s) => FileOrStdout::Stdout(s.lock())
+        }
+    }
+
+    /// Finishes writing successfully.
+    fn commit(self) -> Result<(), io::Error> {
+        match self {
+            FileOrStdout::File(file) => file.commit(),
+            FileOrStdout::Stdout(s) => s.lock().flush(),
+        }
+    }
+
+    /// Abandons writing after an error.
+    fn abort(self) {
+        if let FileOrStdout::File(file) = self {
+            file.abort()
+        }
+    }
+}
+
+impl<'a> io::Write for FileOrStdout<&'a File, io::StdoutLock<'a>> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        match *self {
+            FileOrStdout::File(ref mut file) => file.write(buf),
+            FileOrStdout::Stdout(ref mut lock) => lock.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        match *self {
+            FileOrStdout::File(ref mut file) => file.flush(),
+            FileOrStdout::Stdout(ref mut lock) => lock.flush(),
+        }
+    }
+}
@@ -3,15 +3,21 @@
 /// The types in this module store route origins, sets of route origins, and
 /// the history of changes necessary for RTR.
 
-use std::{hash, ops, slice, vec};
-use std::collections::{HashSet, VecDeque};
+use std::{hash, mem, ops, slice, vec};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 use rpki::asres::AsId;
 use rpki::roa::{FriendlyRoaIpAddress, RouteOriginAttestation};
 use rpki::tal::TalInfo;
-use super::slurm::LocalExceptions;
+use super::bgp::Announcement;
+use super::provenance::Provenance;
+use super::serial::Serial;
+use super::slurm::{LocalExceptions, PrefixFilter};
+use super::validity::RouteValidity;
 
 
 //------------ RouteOrigins --------------------------------------------------
@@ -22,25 +28,47 @@ use super::slurm::LocalExceptions;
 /// of RPKI repository validation. It is an intermediary type used as input
 /// for generating the real origins kept in [`AddressOrigins`].
 ///
+/// Alongside each attestation, the list keeps the URI of the ROA object it
+/// was taken from and, if it was recorded, the [`Provenance`] of that ROA.
+///
 /// [`AddressOrigins`]: struct.AddressOrigins.html
+/// [`Provenance`]: ../provenance/struct.Provenance.html
 #[derive(Clone, Debug)]
 pub struct RouteOrigins {
     /// The list of route origin attestations.
-    origins: Vec<RouteOriginAttestation>
+    origins: Vec<RouteOriginAttestation>,
+
+    /// The URIs of the ROAs of the attestations in `origins`.
+    uris: Vec<Arc<str>>,
+
+    /// The provenance of the ROAs of the attestations in `origins`.
+    provenance: Vec<Option<Arc<Provenance>>>,
 }
 
 impl RouteOrigins {
     /// Creates a new, empty list of route origins.
     pub fn new() -> Self {
-        RouteOrigins { origins: Vec::new() }
+        RouteOrigins {
+            origins: Vec::new(),
+            uris: Vec::new(),
+            provenance: Vec::new()
+        }
     }
 
-    /// Appends the given attestation to the set.
+    /// Appends the given attestation taken from the ROA at `uri`.
     ///
-    /// The attestation will simply be added to the end of the list. No
-    /// checking for duplicates is being done.
-    pub fn push(&mut self, attestation: RouteOriginAttestation) {
-        self.origins.push(attestation)
+    /// If known, `provenance` describes where the ROA came from. The
+    /// attestation will simply be added to the end of the list. No checking
+    /// for duplicates is being done.
+    pub fn push(
+        &mut self,
+        attestation: RouteOriginAttestation,
+        uri: Arc<str>,
+        provenance: Option<Provenance>
+    ) {
+        self.origins.push(attestation);
+        self.uris.push(uri);
+        self.provenance.push(provenance.map(Arc::new));
     }
 
     /// Merges another list of route origins into this one.
@@ -48,7 +76,9 @@ impl RouteOrigins {
     /// Despite the name, this method doesn’t do any duplicate checking,
     /// either.
     pub fn merge(&mut self, mut other: RouteOrigins) {
-        self.origins.append(&mut other.origins)
+        self.origins.append(&mut other.origins);
+        self.uris.append(&mut other.uris);
+        self.provenance.append(&mut other.provenance);
     }
 
     /// Returns the number of attestations in the list.
@@ -85,86 +115,797 @@ impl<'a> IntoIterator for &'a RouteOrigins {
 
 /// A set of address origin statements.
 ///
-/// This type contains a list of [`AddressOrigin`] statements. While it is
-/// indeed a set, that is, it doesn’t contain duplicates, it is accessible
-/// like a slice of address origins, to which it even derefs. This is so that
-/// we can iterate over the set using indexes instead of references.
+/// This type contains a set of [`AddressOrigin`] statements. Since the set
+/// for all the RPKI is rather large and we keep it around for as long as it
+/// is current, it uses a compact representation: the origins are kept in
+/// two arrays, one for each address family, of small fixed-size entries
+/// sorted by address, prefix length, max length, and AS number.
+///
+/// The sources of the origins, i.e., the trust anchor and ROA they have
+/// been derived from, are kept in a separate list and each entry refers to
+/// its source by index. Since the same origin can be derived from more
+/// than one ROA, an entry can alternatively refer to a list of source
+/// indexes.
+///
+/// Because the entries are sorted, finding the origins covering or covered
+/// by a prefix only needs a few binary searches and two sets can be compared
+/// by walking through both of them side by side.
+///
+/// The individual origins are handed out as owned [`AddressOrigin`] values
+/// via the `get` and `iter` methods. Iteration will produce all IPv4 origins
+/// before the IPv6 origins, each in the order given above.
+///
+/// A set is created from validated route origins via `from_route_origins`
+/// or from a list of address origins via `try_from_iter`. Both fail if
+/// there are more sources or trust anchors than the set can refer to.
 ///
 /// [`AddressOrigin`]: struct.AddressOrigin.html
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct AddressOrigins {
-    /// A list of (unique) address origins.
-    origins: Vec<AddressOrigin>,
+    /// The sorted and unique IPv4 origins.
+    v4: Vec<Entry<u32>>,
+
+    /// The sorted and unique IPv6 origins.
+    v6: Vec<Entry<[u32; 4]>>,
+
+    /// The trust anchors referenced by the sources.
+    tals: Vec<Arc<TalInfo>>,
+
+    /// The sources referenced by the entries.
+    sources: Vec<Source>,
+
+    /// The lists of sources for entries with more than one source.
+    multi: Vec<Box<[u32]>>,
 }
 
 impl AddressOrigins {
     /// Creates a new, empty set of address origins.
     pub fn new() -> Self {
-        AddressOrigins {
-            origins: Vec::new()
+        Self::default()
+    }
+
+    /// Creates a set from address origins.
+    ///
+    /// Fails if there are more sources or trust anchors than the compact
+    /// representation can refer to.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, CapacityError>
+    where I: IntoIterator<Item=AddressOrigin> {
+        let mut res = Builder::default();
+        for addr in iter {
+            res.push_origin(&addr)?
         }
+        res.finish()
     }
 
     /// Creates a set from the raw route origins and exceptions.
     ///
-    /// The function will take all the address origins in `origins`, drop
+    /// The function will take all the address origins in `origins`, merge
     /// duplicates, drop the origins filtered in `execptions` and add the
     /// assertions from `exceptions`.
+    ///
+    /// Fails if there are more sources or trust anchors than the compact
+    /// representation can refer to.
     pub fn from_route_origins(
         origins: RouteOrigins,
         exceptions: &LocalExceptions,
-    ) -> Self {
-        let mut res = HashSet::new();
-        for roa in origins {
+    ) -> Result<Self, CapacityError> {
+        let mut res = Builder::default();
+        let iter = origins.origins.into_iter().zip(
+            origins.uris.into_iter().zip(origins.provenance)
+        );
+        for (roa, (uri, provenance)) in iter {
+            let source = res.source(
+                roa.status().tal(), Some(uri), provenance
+            )?;
             for addr in roa.iter() {
-                let addr = AddressOrigin::from_roa(
-                    roa.as_id(), addr, roa.status().tal().map(Clone::clone)
+                res.push(
+                    roa.as_id(), AddressPrefix::from(&addr),
+                    addr.max_length(), source
                 );
-                if exceptions.keep_origin(&addr) {
-                    let _ = res.insert(addr);
-                }
             }
         }
+        if !exceptions.filters().is_empty() {
+            res.filter(exceptions.filters());
+        }
         for addr in exceptions.assertions() {
-            let _ = res.insert(addr.clone());
+            res.push_origin(addr)?
         }
-        AddressOrigins {
-            origins: res.into_iter().collect()
+        res.finish()
+    }
+
+    /// Returns the number of address origins in the set.
+    pub fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    /// Returns the address origin with the given index.
+    ///
+    /// Indexes start with the IPv4 origins and continue with IPv6.
+    pub fn get(&self, idx: usize) -> Option<AddressOrigin> {
+        if idx < self.v4.len() {
+            Some(self.v4[idx].origin(self))
+        }
+        else {
+            self.v6.get(idx - self.v4.len()).map(|entry| {
+                entry.origin(self)
+            })
         }
     }
 
     /// Returns an iterator over the address orgins.
-    pub fn iter(&self) -> slice::Iter<AddressOrigin> {
-        self.origins.iter()
+    pub fn iter(&self) -> Iter {
+        Iter { origins: self, idx: 0 }
+    }
+
+    /// Returns all address origins whose prefix covers `prefix`.
+    ///
+    /// This includes address origins for `prefix` itself. The origins are
+    /// ordered from the least to the most specific prefix.
+    pub fn covering(&self, prefix: AddressPrefix) -> Vec<AddressOrigin> {
+        let mut res = Vec::new();
+        match prefix.address() {
+            IpAddr::V4(_) => {
+                for entry in covering(&self.v4, prefix) {
+                    res.push(entry.origin(self))
+                }
+            }
+            IpAddr::V6(_) => {
+                for entry in covering(&self.v6, prefix) {
+                    res.push(entry.origin(self))
+                }
+            }
+        }
+        res
+    }
+
+    /// Returns all address origins whose prefix is covered by `prefix`.
+    ///
+    /// This includes address origins for `prefix` itself.
+    pub fn covered(&self, prefix: AddressPrefix) -> Vec<AddressOrigin> {
+        let len = prefix.address_length();
+        match prefix.address() {
+            IpAddr::V4(_) => {
+                self.v4[covered_range(&self.v4, prefix)].iter()
+                    .filter(|entry| entry.len >= len)
+                    .map(|entry| entry.origin(self))
+                    .collect()
+            }
+            IpAddr::V6(_) => {
+                self.v6[covered_range(&self.v6, prefix)].iter()
+                    .filter(|entry| entry.len >= len)
+                    .map(|entry| entry.origin(self))
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns all address origins for exactly `prefix`.
+    ///
+    /// If `as_id` is given, only origins for this AS are returned. The
+    /// sources of the returned origins carry the provenance of their ROAs
+    /// if it is known.
+    pub fn matching(
+        &self,
+        prefix: AddressPrefix,
+        as_id: Option<AsId>
+    ) -> Vec<AddressOrigin> {
+        let mut res = self.covering(prefix);
+        res.retain(|origin| {
+            origin.prefix().address_length() == prefix.address_length()
+            && as_id.map(|as_id| origin.as_id() == as_id).unwrap_or(true)
+        });
+        res
+    }
+
+    /// Validates a route announcement as described in RFC 6811.
+    pub fn validate(&self, announcement: Announcement) -> RouteValidity {
+        RouteValidity::new(announcement, self.covering(announcement.prefix))
+    }
+
+    /// Returns the changes necessary to get from `self` to `next`.
+    ///
+    /// Returns a pair of the origins present in `next` but not in `self`
+    /// and those present in `self` but not in `next`. As with
+    /// [`AddressOrigin`] itself, the sources are not considered when
+    /// comparing origins.
+    ///
+    /// [`AddressOrigin`]: struct.AddressOrigin.html
+    pub fn diff(
+        &self,
+        next: &AddressOrigins
+    ) -> (Vec<AddressOrigin>, Vec<AddressOrigin>) {
+        let mut announce = Vec::new();
+        let mut withdraw = Vec::new();
+        diff_entries(
+            (&self.v4, self), (&next.v4, next),
+            &mut announce, &mut withdraw
+        );
+        diff_entries(
+            (&self.v6, self), (&next.v6, next),
+            &mut announce, &mut withdraw
+        );
+        (announce, withdraw)
+    }
+
+    /// Returns the number of bytes of memory occupied by the set.
+    ///
+    /// The trust anchor information shared with other values is not
+    /// included.
+    pub fn memory_size(&self) -> usize {
+        mem::size_of::<Self>()
+        + self.v4.capacity() * mem::size_of::<Entry<u32>>()
+        + self.v6.capacity() * mem::size_of::<Entry<[u32; 4]>>()
+        + self.tals.capacity() * mem::size_of::<Arc<TalInfo>>()
+        + self.sources.capacity() * mem::size_of::<Source>()
+        + self.sources.iter().map(|source| {
+            source.uri.as_ref().map(|uri| uri.len()).unwrap_or(0)
+        }).sum::<usize>()
+        + self.multi.capacity() * mem::size_of::<Box<[u32]>>()
+        + self.multi.iter().map(|list| {
+            list.len() * mem::size_of::<u32>()
+        }).sum::<usize>()
+    }
+}
+
+
+//--- FromIterator
+
+/// Collecting is only available in tests where the origins are known to fit.
+#[cfg(test)]
+impl ::std::iter::FromIterator<AddressOrigin> for AddressOrigins {
+    fn from_iter<I>(iter: I) -> Self
+    where I: IntoIterator<Item=AddressOrigin> {
+        Self::try_from_iter(iter).unwrap()
     }
 }
 
 
-//--- From
+//--- IntoIterator
+
+impl<'a> IntoIterator for &'a AddressOrigins {
+    type Item = AddressOrigin;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+
+//------------ Iter ----------------------------------------------------------
+
+/// An iterator over the address origins of a set.
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    origins: &'a AddressOrigins,
+    idx: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = AddressOrigin;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.origins.get(self.idx)?;
+        self.idx += 1;
+        Some(res)
+    }
 
-impl From<HashSet<AddressOrigin>> for AddressOrigins {
-    fn from(set: HashSet<AddressOrigin>) -> Self {
-        AddressOrigins {
-            origins: set.into_iter().collect()
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.origins.len() - self.idx;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> { }
+
+
+//------------ Builder -------------------------------------------------------
+
+/// Collects address origins for creating a set.
+///
+/// Origins are simply pushed to the builder in any order. When done, the
+/// `finish` method sorts them and merges the sources of duplicates.
+#[derive(Default)]
+struct Builder {
+    /// The set we are building.
+    set: AddressOrigins,
+
+    /// The trust anchor and URI of sources already seen and their index.
+    known: HashMap<(u16, Option<Arc<str>>), u32>,
+}
+
+impl Builder {
+    /// Returns the source index for the given trust anchor and URI.
+    ///
+    /// Sources are identified by trust anchor and URI only. The provenance
+    /// is only kept for the first source with a given trust anchor and URI.
+    fn source(
+        &mut self,
+        tal: Option<&Arc<TalInfo>>,
+        uri: Option<Arc<str>>,
+        provenance: Option<Arc<Provenance>>,
+    ) -> Result<u32, CapacityError> {
+        let key = (self.tal_index(tal)?, uri);
+        if let Some(idx) = self.known.get(&key) {
+            return Ok(*idx)
+        }
+        let idx = self.set.sources.len() as u32;
+        if idx >= MULTI_SOURCE {
+            return Err(CapacityError::Sources)
         }
+        self.set.sources.push(Source {
+            tal: key.0, uri: key.1.clone(), provenance
+        });
+        self.known.insert(key, idx);
+        Ok(idx)
     }
+
+    /// Returns the index to use for the given trust anchor.
+    ///
+    /// Index 0 means no trust anchor at all. Every other index `n` refers
+    /// to `self.set.tals[n - 1]`.
+    fn tal_index(
+        &mut self,
+        tal: Option<&Arc<TalInfo>>
+    ) -> Result<u16, CapacityError> {
+        let tal = match tal {
+            Some(tal) => tal,
+            None => return Ok(0)
+        };
+        let tals = &mut self.set.tals;
+        let idx = match tals.iter().position(|item| {
+            Arc::ptr_eq(item, tal) || item.name() == tal.name()
+        }) {
+            Some(idx) => idx,
+            None => {
+                tals.push(tal.clone());
+                tals.len() - 1
+            }
+        };
+        if idx >= usize::from(::std::u16::MAX) {
+            // Don’t keep the TAL around if we can’t refer to it.
+            tals.truncate(idx);
+            return Err(CapacityError::Tals)
+        }
+        Ok(idx as u16 + 1)
+    }
+
+    /// Adds an origin with the given source index.
+    fn push(
+        &mut self,
+        as_id: AsId,
+        prefix: AddressPrefix,
+        max_length: u8,
+        source: u32,
+    ) {
+        match prefix.address() {
+            IpAddr::V4(_) => {
+                self.set.v4.push(
+                    Entry::new(as_id, prefix, max_length, source)
+                )
+            }
+            IpAddr::V6(_) => {
+                self.set.v6.push(
+                    Entry::new(as_id, prefix, max_length, source)
+                )
+            }
+        }
+    }
+
+    /// Adds an address origin including all its sources.
+    fn push_origin(
+        &mut self,
+        origin: &AddressOrigin
+    ) -> Result<(), CapacityError> {
+        let mut sources = origin.sources().iter().map(|source| {
+            self.source(
+                source.tal(), source.uri.clone(), source.provenance.clone()
+            )
+        }).collect::<Result<Vec<_>, _>>()?;
+        let source = match sources.len() {
+            0 => NO_SOURCE,
+            1 => sources[0],
+            _ => {
+                sources.sort_by(|left, right| {
+                    self.cmp_sources(*left, *right)
+                });
+                self.multi_source(sources)?
+            }
+        };
+        self.push(
+            origin.as_id(), origin.prefix(), origin.max_length(), source
+        );
+        Ok(())
+    }
+
+    /// Adds a list of sources and returns the index for it.
+    fn multi_source(
+        &mut self,
+        sources: Vec<u32>
+    ) -> Result<u32, CapacityError> {
+        let idx = self.set.multi.len() as u32;
+        if idx >= MULTI_SOURCE {
+            return Err(CapacityError::Sources)
+        }
+        self.set.multi.push(sources.into_boxed_slice());
+        Ok(idx | MULTI_SOURCE)
+    }
+
+    /// Compares two sources by their trust anchor name and URI.
+    ///
+    /// Sources without a trust anchor are ordered after those with one.
+    fn cmp_sources(&self, left: u32, right: u32) -> Ordering {
+        let left = &self.set.sources[left as usize];
+        let right = &self.set.sources[right as usize];
+        cmp_none_last(
+            tal_name(left.tal, &self.set.tals),
+            tal_name(right.tal, &self.set.tals)
+        ).then_with(|| cmp_none_last(left.uri.as_ref(), right.uri.as_ref()))
+    }
+
+    /// Drops all origins added so far that are filtered by `filters`.
+    ///
+    /// Origins added later are not affected.
+    fn filter(&mut self, filters: &[PrefixFilter]) {
+        self.set.v4.sort_by_key(Entry::key);
+        filter_entries(&mut self.set.v4, filters);
+        self.set.v6.sort_by_key(Entry::key);
+        filter_entries(&mut self.set.v6, filters);
+    }
+
+    /// Sorts the entries and merges the sources of duplicates.
+    fn finish(mut self) -> Result<AddressOrigins, CapacityError> {
+        let v4 = mem::replace(&mut self.set.v4, Vec::new());
+        self.set.v4 = self.merge_entries(v4)?;
+        let v6 = mem::replace(&mut self.set.v6, Vec::new());
+        self.set.v6 = self.merge_entries(v6)?;
+        Ok(self.set)
+    }
+
+    fn merge_entries<A: Address>(
+        &mut self,
+        mut entries: Vec<Entry<A>>
+    ) -> Result<Vec<Entry<A>>, CapacityError> {
+        entries.sort_by_key(Entry::key);
+        let mut res = Vec::with_capacity(entries.len());
+        let mut start = 0;
+        while start < entries.len() {
+            let mut entry = entries[start];
+            let end = start + entries[start..].iter().take_while(|item| {
+                item.key() == entry.key()
+            }).count();
+            if end - start > 1 {
+                let mut sources = Vec::new();
+                for item in &entries[start..end] {
+                    sources.extend_from_slice(
+                        item.source_indexes(&self.set.multi)
+                    )
+                }
+                sources.sort_by(|left, right| {
+                    self.cmp_sources(*left, *right)
+                });
+                sources.dedup_by(|left, right| {
+                    self.cmp_sources(*left, *right) == Ordering::Equal
+                });
+                entry.source = match sources.len() {
+                    0 => NO_SOURCE,
+                    1 => sources[0],
+                    _ => self.multi_source(sources)?
+                };
+            }
+            res.push(entry);
+            start = end;
+        }
+        Ok(res)
+    }
+}
+
+
+//------------ Source --------------------------------------------------------
+
+/// The compact representation of an origin source.
+#[derive(Clone, Debug)]
+struct Source {
+    /// The index of the trust anchor.
+    ///
+    /// See `Builder::tal_index` for the meaning of the value.
+    tal: u16,
+
+    /// The URI of the ROA if known.
+    uri: Option<Arc<str>>,
+
+    /// The provenance of the ROA if known.
+    provenance: Option<Arc<Provenance>>,
 }
 
+/// The source value of an entry without any sources.
+const NO_SOURCE: u32 = ::std::u32::MAX;
+
+/// The bit marking a source value as an index into the multi source lists.
+const MULTI_SOURCE: u32 = 1 << 31;
 
-//--- Deref and AsRef
 
-impl ops::Deref for AddressOrigins {
-    type Target = [AddressOrigin];
+//------------ Entry ---------------------------------------------------------
 
-    fn deref(&self) -> &Self::Target {
-        self.origins.as_ref()
+/// The compact representation of an address origin.
+///
+/// The address is stored with all bits beyond the prefix length cleared.
+///
+/// The source is either `NO_SOURCE`, an index into the list of sources of
+/// the set or, if `MULTI_SOURCE` is set, an index into the list of lists
+/// of sources.
+#[derive(Clone, Copy, Debug)]
+struct Entry<A> {
+    addr: A,
+    asn: u32,
+    len: u8,
+    max_len: u8,
+    source: u32,
+}
+
+impl<A: Address> Entry<A> {
+    fn new(
+        as_id: AsId, prefix: AddressPrefix, max_len: u8, source: u32
+    ) -> Self {
+        let len = prefix.address_length();
+        Entry {
+            addr: A::from_bits(ip_bits(prefix.address()) & !host_mask(len)),
+            asn: as_id.into(),
+            len, max_len, source
+        }
+    }
+
+    /// Returns the key the entries of a set are sorted by.
+    fn key(&self) -> (A, u8, u8, u32) {
+        (self.addr, self.len, self.max_len, self.asn)
+    }
+
+    /// Returns the indexes of the sources of the entry.
+    fn source_indexes<'a>(&'a self, multi: &'a [Box<[u32]>]) -> &'a [u32] {
+        if self.source == NO_SOURCE {
+            &[]
+        }
+        else if self.source & MULTI_SOURCE != 0 {
+            &multi[(self.source & !MULTI_SOURCE) as usize]
+        }
+        else {
+            slice::from_ref(&self.source)
+        }
+    }
+
+    /// Converts the entry into an address origin without sources.
+    fn bare_origin(&self) -> AddressOrigin {
+        AddressOrigin::new(
+            AsId::from(self.asn),
+            AddressPrefix::new(self.addr.to_ip(), self.len),
+            self.max_len,
+        )
+    }
+
+    /// Converts the entry into a full address origin.
+    fn origin(&self, set: &AddressOrigins) -> AddressOrigin {
+        let mut res = self.bare_origin();
+        res.sources = self.source_indexes(&set.multi).iter().map(|idx| {
+            let source = &set.sources[*idx as usize];
+            OriginSource {
+                tal: match source.tal {
+                    0 => None,
+                    idx => Some(set.tals[usize::from(idx) - 1].clone())
+                },
+                uri: source.uri.clone(),
+                provenance: source.provenance.clone(),
+            }
+        }).collect();
+        res
     }
 }
 
-impl AsRef<[AddressOrigin]> for AddressOrigins {
-    fn as_ref(&self) -> &[AddressOrigin] {
-        self.origins.as_ref()
+
+//------------ Address -------------------------------------------------------
+
+/// The address of an entry.
+///
+/// This is implemented by `u32` for IPv4 and by `[u32; 4]` for IPv6 which,
+/// unlike `u128`, doesn’t force 16 byte alignment onto the entries. For
+/// calculations, addresses are converted into a `u128` with the address
+/// bits at the most significant end.
+trait Address: Copy + Ord {
+    fn is_family(addr: IpAddr) -> bool;
+    fn from_bits(bits: u128) -> Self;
+    fn to_bits(self) -> u128;
+    fn to_ip(self) -> IpAddr;
+}
+
+impl Address for u32 {
+    fn is_family(addr: IpAddr) -> bool {
+        addr.is_ipv4()
     }
+
+    fn from_bits(bits: u128) -> Self {
+        (bits >> 96) as u32
+    }
+
+    fn to_bits(self) -> u128 {
+        u128::from(self) << 96
+    }
+
+    fn to_ip(self) -> IpAddr {
+        IpAddr::V4(self.into())
+    }
+}
+
+impl Address for [u32; 4] {
+    fn is_family(addr: IpAddr) -> bool {
+        addr.is_ipv6()
+    }
+
+    fn from_bits(bits: u128) -> Self {
+        [
+            (bits >> 96) as u32, (bits >> 64) as u32,
+            (bits >> 32) as u32, bits as u32
+        ]
+    }
+
+    fn to_bits(self) -> u128 {
+        u128::from(self[0]) << 96 | u128::from(self[1]) << 64
+        | u128::from(self[2]) << 32 | u128::from(self[3])
+    }
+
+    fn to_ip(self) -> IpAddr {
+        IpAddr::V6(self.to_bits().into())
+    }
+}
+
+/// Returns the name of the trust anchor with the given index.
+fn tal_name(idx: u16, tals: &[Arc<TalInfo>]) -> Option<&str> {
+    match idx {
+        0 => None,
+        idx => Some(tals[usize::from(idx) - 1].name())
+    }
+}
+
+/// Compares two optional values ordering missing values last.
+fn cmp_none_last<T: Ord>(left: Option<T>, right: Option<T>) -> Ordering {
+    match (left, right) {
+        (Some(left), Some(right)) => left.cmp(&right),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Returns the bits of an address with the address bits at the top.
+fn ip_bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u128::from(u32::from(addr)) << 96,
+        IpAddr::V6(addr) => u128::from(addr),
+    }
+}
+
+/// Returns a mask with all the bits after the first `len` bits set.
+fn host_mask(len: u8) -> u128 {
+    ::std::u128::MAX.checked_shr(u32::from(len)).unwrap_or(0)
+}
+
+
+//------------ Helper Functions for Entries ----------------------------------
+
+/// Returns the index of the first entry not less than `addr` and `len`.
+fn lower_bound<A: Address>(entries: &[Entry<A>], addr: A, len: u8) -> usize {
+    // The closure never returns `Equal`, so we always get `Err`.
+    match entries.binary_search_by(|entry| {
+        if (entry.addr, entry.len) < (addr, len) {
+            Ordering::Less
+        }
+        else {
+            Ordering::Greater
+        }
+    }) {
+        Ok(idx) | Err(idx) => idx
+    }
+}
+
+/// Returns an iterator over the entries covering `prefix`.
+///
+/// Since a covering prefix has the address of `prefix` cut to its own
+/// length, we simply look up each possible length.
+fn covering<'a, A: Address>(
+    entries: &'a [Entry<A>],
+    prefix: AddressPrefix
+) -> impl Iterator<Item=&'a Entry<A>> {
+    let bits = ip_bits(prefix.address());
+    (0..prefix.address_length() + 1).flat_map(move |len| {
+        let addr = A::from_bits(bits & !host_mask(len));
+        entries[lower_bound(entries, addr, len)..].iter()
+            .take_while(move |entry| entry.addr == addr && entry.len == len)
+    })
+}
+
+/// Returns the range of entries with an address within `prefix`.
+///
+/// All entries covered by `prefix` are within the range. However, the
+/// range may contain entries with a shorter prefix length.
+fn covered_range<A: Address>(
+    entries: &[Entry<A>],
+    prefix: AddressPrefix
+) -> ops::Range<usize> {
+    let len = prefix.address_length();
+    let bits = ip_bits(prefix.address());
+    let start = lower_bound(
+        entries, A::from_bits(bits & !host_mask(len)), len
+    );
+    let last = A::from_bits(bits | host_mask(len));
+    let end = start + entries[start..].iter().take_while(|entry| {
+        entry.addr <= last
+    }).count();
+    start..end
+}
+
+/// Removes all entries dropped by one of the filters.
+fn filter_entries<A: Address>(
+    entries: &mut Vec<Entry<A>>,
+    filters: &[PrefixFilter]
+) {
+    let mut keep = vec![true; entries.len()];
+    for filter in filters {
+        let range = match filter.prefix() {
+            Some(prefix) if A::is_family(prefix.address()) => {
+                covered_range(entries, prefix)
+            }
+            Some(_) => continue,
+            None => 0..entries.len()
+        };
+        for idx in range {
+            if keep[idx]
+                && filter.filter_origin(&entries[idx].bare_origin())
+            {
+                keep[idx] = false
+            }
+        }
+    }
+    let mut keep = keep.into_iter();
+    entries.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Adds the differences between two sorted lists of entries.
+///
+/// Entries only present in `next` are added to `announce`, those only
+/// present in `current` to `withdraw`.
+fn diff_entries<A: Address>(
+    current: (&[Entry<A>], &AddressOrigins),
+    next: (&[Entry<A>], &AddressOrigins),
+    announce: &mut Vec<AddressOrigin>,
+    withdraw: &mut Vec<AddressOrigin>,
+) {
+    let ((current, current_set), (next, next_set)) = (current, next);
+    let (mut left, mut right) = (0, 0);
+    while left < current.len() && right < next.len() {
+        match current[left].key().cmp(&next[right].key()) {
+            Ordering::Less => {
+                withdraw.push(current[left].origin(current_set));
+                left += 1;
+            }
+            Ordering::Greater => {
+                announce.push(next[right].origin(next_set));
+                right += 1;
+            }
+            Ordering::Equal => {
+                left += 1;
+                right += 1;
+            }
+        }
+    }
+    withdraw.extend(
+        current[left..].iter().map(|entry| entry.origin(current_set))
+    );
+    announce.extend(
+        next[right..].iter().map(|entry| entry.origin(next_set))
+    );
 }
 
 
@@ -172,13 +913,21 @@ impl AsRef<[AddressOrigin]> for AddressOrigins {
 
 #[derive(Clone, Debug)]
 pub struct OriginsDiff {
-    serial: u32,
+    serial: Serial,
     announce: Vec<AddressOrigin>,
     withdraw: Vec<AddressOrigin>,
 }
 
 impl OriginsDiff {
-    pub fn empty(serial: u32) -> Self {
+    pub fn new(
+        serial: Serial,
+        announce: Vec<AddressOrigin>,
+        withdraw: Vec<AddressOrigin>
+    ) -> Self {
+        OriginsDiff { serial, announce, withdraw }
+    }
+
+    pub fn empty(serial: Serial) -> Self {
         OriginsDiff {
             serial,
             announce: Vec::new(),
@@ -191,49 +940,25 @@ impl OriginsDiff {
     }
 
     pub fn construct(
-        mut current: HashSet<AddressOrigin>,
+        current: &AddressOrigins,
         origins: Option<RouteOrigins>,
         exceptions: &LocalExceptions,
-        serial: u32
-    ) -> (AddressOrigins, Self) {
-        let mut next = HashSet::new();
-        let mut announce = HashSet::new();
-
-        if let Some(origins) = origins {
-            for roa in origins {
-                for addr in roa.iter() {
-                    let addr = AddressOrigin::from_roa(
-                        roa.as_id(), addr, roa.status().tal().map(Clone::clone)
-                    );
-                    if !exceptions.keep_origin(&addr) {
-                        continue
-                    }
-                    if next.insert(addr.clone()) {
-                        if !current.remove(&addr) {
-                            let _ = announce.insert(addr);
-                        }
-                    }
-                }
-            }
-        }
-        for addr in exceptions.assertions() {
-            // Exceptions could have changed, so let’s be thorough here.
-            if next.insert(addr.clone()) {
-                if !current.remove(addr) {
-                    announce.insert(addr.clone());
-                }
-            }
-        }
-        let withdraw: Vec<_> = current.into_iter().collect();
-        let announce: Vec<_> = announce.into_iter().collect();
+        serial: Serial
+    ) -> Result<(AddressOrigins, Self), CapacityError> {
+        // Exceptions could have changed, so we apply them to everything
+        // rather than only to the new origins.
+        let next = AddressOrigins::from_route_origins(
+            origins.unwrap_or_else(RouteOrigins::new), exceptions
+        )?;
+        let (announce, withdraw) = current.diff(&next);
         debug!(
             "Diff with {} announced and {} withdrawn.",
             announce.len(), withdraw.len()
         );
-        (next.into(), OriginsDiff { serial, announce, withdraw })
+        Ok((next, OriginsDiff { serial, announce, withdraw }))
     }
 
-    pub fn serial(&self) -> u32 {
+    pub fn serial(&self) -> Serial {
         self.serial
     }
 
@@ -247,7 +972,7 @@ impl OriginsDiff {
 
     pub fn unwrap(
         self
-    ) -> (u32, Vec<AddressOrigin>, Vec<AddressOrigin>) {
+    ) -> (Serial, Vec<AddressOrigin>, Vec<AddressOrigin>) {
         (self.serial, self.announce, self.withdraw)
     }
 }
@@ -257,7 +982,7 @@ impl OriginsDiff {
 
 #[derive(Clone, Debug)]
 struct DiffMerger {
-    serial: u32,
+    serial: Serial,
     announce: HashSet<AddressOrigin>,
     withdraw: HashSet<AddressOrigin>,
 }
@@ -286,10 +1011,13 @@ impl DiffMerger {
     }
 
     fn into_diff(self) -> Arc<OriginsDiff> {
+        let mut announce: Vec<_> = self.announce.into_iter().collect();
+        let mut withdraw: Vec<_> = self.withdraw.into_iter().collect();
+        announce.sort();
+        withdraw.sort();
         Arc::new(OriginsDiff {
             serial: self.serial,
-            announce: self.announce.into_iter().collect(),
-            withdraw: self.withdraw.into_iter().collect(),
+            announce, withdraw
         })
     }
 }
@@ -312,15 +1040,43 @@ pub struct HistoryInner {
 
     /// The number of diffs to keep.
     keep: usize,
+
+    /// The serial number of the current set.
+    serial: Serial,
+
+    /// The RTR session ID.
+    session: u16,
 }
 
 impl OriginsHistory {
+    /// Creates a new history starting with serial 0 and a new session.
+    ///
+    /// The session ID is derived from the current time.
     pub fn new(current: AddressOrigins, keep: usize) -> Self {
+        let session = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH).unwrap()
+            .as_secs() as u16;
+        Self::from_parts(current, Vec::new(), Serial(0), session, keep)
+    }
+
+    /// Creates a history from its parts.
+    ///
+    /// The diffs need to be ordered from newest to oldest. Only the `keep`
+    /// newest diffs are retained.
+    pub fn from_parts(
+        current: AddressOrigins,
+        diffs: Vec<OriginsDiff>,
+        serial: Serial,
+        session: u16,
+        keep: usize
+    ) -> Self {
         OriginsHistory(Arc::new(RwLock::new(
             HistoryInner {
                 current: Arc::new(current),
-                diffs: VecDeque::with_capacity(keep),
-                keep
+                diffs: diffs.into_iter().take(keep).map(Arc::new).collect(),
+                keep,
+                serial,
+                session,
             }
         )))
     }
@@ -329,50 +1085,56 @@ impl OriginsHistory {
         self.0.read().unwrap().current.clone()
     }
 
-    pub fn get(&self, serial: u32) -> Option<Arc<OriginsDiff>> {
-        debug!("Fetching diff for serial {}", serial);
+    /// Returns the diffs kept, newest first.
+    pub fn diffs(&self) -> Vec<Arc<OriginsDiff>> {
+        self.0.read().unwrap().diffs.iter().cloned().collect()
+    }
+
+    pub fn session(&self) -> u16 {
+        self.0.read().unwrap().session
+    }
+
+    /// Returns the diff from `serial` to the current serial.
+    ///
+    /// Returns `None` if `serial` isn’t older than or equal to the current
+    /// serial or if it is too old for the diffs we keep. In this case, the
+    /// client needs to reset.
+    pub fn get(&self, serial: Serial) -> Option<Arc<OriginsDiff>> {
         let history = self.0.read().unwrap();
-        if let Some(diff) = history.diffs.front() {
-            debug!("Our current serial is {}", diff.serial);
-            if diff.serial() < serial {
-                // If they give us a future serial, we reset.
-                debug!("Future, forcing reset.");
-                return None
-            }
-            else if diff.serial() == serial {
+        debug!(
+            "Fetching diff for serial {}, our serial is {}.",
+            serial, history.serial
+        );
+        match serial.partial_cmp(&history.serial) {
+            Some(Ordering::Less) => { }
+            Some(Ordering::Equal) => {
                 debug!("Same, producing empty diff.");
                 return Some(Arc::new(OriginsDiff::empty(serial)))
             }
-            else if diff.serial() == serial + 1 {
-                // This relies on serials increasing by one always.
-                debug!("One behind, just clone.");
-                return Some(diff.clone())
-            }
-        }
-        else {
-            debug!("We are at serial 0.");
-            if serial == 0 {
-                debug!("Same, returning empty diff.");
-                return Some(Arc::new(OriginsDiff::empty(serial)))
-            }
-            else {
-                // That pesky future serial again.
-                debug!("Future, forcing reset.");
+            _ => {
+                debug!("Future serial, forcing reset.");
                 return None
             }
         }
-        debug!("Merging diffs.");
-        let mut iter = history.diffs.iter().rev();
-        while let Some(diff) = iter.next() {
-            if serial < diff.serial() {
-                return None
-            }
-            else if diff.serial() == serial {
-                break
-            }
+
+        // Diffs are kept newest first and each diff brings the set to its
+        // serial from the one before, so we need all diffs with a serial
+        // newer than the requested one and the oldest of them needs to be
+        // for the serial right after it.
+        let count = history.diffs.iter().take_while(|diff| {
+            diff.serial() > serial
+        }).count();
+        if count == 0 || history.diffs[count - 1].serial() != serial.next() {
+            debug!("Serial too old, forcing reset.");
+            return None
+        }
+        if count == 1 {
+            debug!("One behind, just clone.");
+            return Some(history.diffs[0].clone())
         }
-        // We already know that the serial’s diff wasn’t last, so unwrap is
-        // fine.
+        debug!("Merging {} diffs.", count);
+        let mut iter = history.diffs.iter().take(count).rev();
+        // We know there are at least two diffs, so unwrap is fine.
         let mut res = DiffMerger::new(iter.next().unwrap().as_ref());
         for diff in iter {
             res.merge(diff.as_ref())
@@ -380,52 +1142,73 @@ impl OriginsHistory {
         Some(res.into_diff())
     }
 
-    pub fn serial(&self) -> u32 {
+    pub fn serial(&self) -> Serial {
         self.0.read().unwrap().serial()
     }
 
-    pub fn current_and_serial(&self) -> (Arc<AddressOrigins>, u32) {
+    pub fn current_and_serial(&self) -> (Arc<AddressOrigins>, Serial) {
         let history = self.0.read().unwrap();
         (history.current.clone(), history.serial())
     }
 
+    /// Returns the current set, the diffs, the serial, and the session.
+    ///
+    /// Since all parts are taken at once, they are consistent.
+    pub fn snapshot(
+        &self
+    ) -> (Arc<AddressOrigins>, Vec<Arc<OriginsDiff>>, Serial, u16) {
+        let history = self.0.read().unwrap();
+        (
+            history.current.clone(),
+            history.diffs.iter().cloned().collect(),
+            history.serial,
+            history.session
+        )
+    }
+
+    /// Updates the history with a new set of route origins.
+    ///
+    /// The new set always replaces the current one since the sources of
+    /// the origins may have changed even if the origins themselves didn’t.
+    /// Only if there is a difference in the origins, the serial number is
+    /// increased and the diff added to the history. Returns whether that
+    /// happened, i.e., whether clients need to be notified.
     pub fn update(
         &self,
         origins: Option<RouteOrigins>,
         exceptions: &LocalExceptions
-    ) -> bool {
+    ) -> Result<bool, CapacityError> {
         let (serial, current) = {
             let history = self.0.read().unwrap();
-            let serial = history.serial().wrapping_add(1);
+            let serial = history.serial().next();
             let current = history.current.clone();
             (serial, current)
         };
-        let current: HashSet<_> = current.iter().map(Clone::clone).collect();
         let (next, diff) = OriginsDiff::construct(
-            current, origins, exceptions, serial
-        );
+            &current, origins, exceptions, serial
+        )?;
+        let mut history = self.0.write().unwrap();
+        history.current = Arc::new(next);
         if !diff.is_empty() {
-            let mut history = self.0.write().unwrap();
-            history.current = Arc::new(next);
             history.push_diff(diff);
-            true
+            Ok(true)
         }
         else {
-            false
+            Ok(false)
         }
     }
-
 }
 
 impl HistoryInner {
-    pub fn serial(&self) -> u32 {
-        match self.diffs.front() {
-            Some(diff) => diff.serial(),
-            None => 0
-        }
+    pub fn serial(&self) -> Serial {
+        self.serial
     }
 
     pub fn push_diff(&mut self, diff: OriginsDiff) {
+        self.serial = diff.serial();
+        if self.keep == 0 {
+            return
+        }
         if self.diffs.len() == self.keep {
             let _ = self.diffs.pop_back();
         }
@@ -441,27 +1224,44 @@ pub struct AddressOrigin {
     as_id: AsId,
     prefix: AddressPrefix,
     max_length: u8,
-    tal: Option<Arc<TalInfo>>
+
+    /// The sources of the origin ordered by trust anchor name and URI.
+    sources: Vec<OriginSource>,
 }
 
 impl AddressOrigin {
     pub fn new(as_id: AsId, prefix: AddressPrefix, max_length: u8) -> Self {
-        AddressOrigin { as_id, prefix, max_length, tal: None }
+        AddressOrigin { as_id, prefix, max_length, sources: Vec::new() }
     }
 
-    fn from_roa(
+    pub fn with_tal(
         as_id: AsId,
-        addr: FriendlyRoaIpAddress,
+        prefix: AddressPrefix,
+        max_length: u8,
         tal: Option<Arc<TalInfo>>
     ) -> Self {
         AddressOrigin {
-            as_id,
-            prefix: AddressPrefix::from(&addr),
-            max_length: addr.max_length(),
-            tal
+            as_id, prefix, max_length,
+            sources: tal.map(|tal| {
+                OriginSource::new(Some(tal), None)
+            }).into_iter().collect()
         }
     }
 
+    /// Creates an address origin derived from the given sources.
+    ///
+    /// The sources will be ordered and duplicates removed.
+    pub fn with_sources(
+        as_id: AsId,
+        prefix: AddressPrefix,
+        max_length: u8,
+        mut sources: Vec<OriginSource>
+    ) -> Self {
+        sources.sort();
+        sources.dedup();
+        AddressOrigin { as_id, prefix, max_length, sources }
+    }
+
     pub fn as_id(&self) -> AsId {
         self.as_id
     }
@@ -482,12 +1282,51 @@ impl AddressOrigin {
         self.max_length
     }
 
+    /// Returns the sources the origin was derived from.
+    pub fn sources(&self) -> &[OriginSource] {
+        &self.sources
+    }
+
+    /// Returns the name of the first trust anchor of the origin.
+    ///
+    /// Returns `"N/A"` if the origin doesn’t have a trust anchor.
     pub fn tal_name(&self) -> &str {
-        match self.tal {
-            Some(ref tal) => tal.name(),
+        match self.tal() {
+            Some(tal) => tal.name(),
             None => "N/A"
         }
     }
+
+    /// Returns the first trust anchor of the origin.
+    pub fn tal(&self) -> Option<&Arc<TalInfo>> {
+        self.sources.first().and_then(OriginSource::tal)
+    }
+
+    /// Returns the names of all trust anchors of the origin.
+    ///
+    /// Each name is only included once.
+    pub fn tal_names(&self) -> Vec<&str> {
+        let mut res: Vec<&str> = Vec::new();
+        for tal in self.sources.iter().filter_map(OriginSource::tal) {
+            if res.last() != Some(&tal.name()) {
+                res.push(tal.name())
+            }
+        }
+        res
+    }
+
+    /// Returns the names of all trust anchors separated by semicolons.
+    ///
+    /// Returns `"N/A"` if the origin doesn’t have a trust anchor.
+    pub fn tal_list(&self) -> String {
+        let names = self.tal_names();
+        if names.is_empty() {
+            "N/A".into()
+        }
+        else {
+            names.join(";")
+        }
+    }
 }
 
 
@@ -504,6 +1343,27 @@ impl PartialEq for AddressOrigin {
 impl Eq for AddressOrigin { }
 
 
+//--- PartialOrd and Ord
+
+/// Address origins are ordered canonically.
+///
+/// The order is by address family, address, prefix length, max length,
+/// and finally AS number. As with equality, the sources are ignored.
+impl PartialOrd for AddressOrigin {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AddressOrigin {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.prefix.cmp(&other.prefix)
+        .then(self.max_length.cmp(&other.max_length))
+        .then(u32::from(self.as_id).cmp(&u32::from(other.as_id)))
+    }
+}
+
+
 //--- Hash
 
 impl hash::Hash for AddressOrigin {
@@ -515,9 +1375,99 @@ impl hash::Hash for AddressOrigin {
 }
 
 
+//------------ OriginSource --------------------------------------------------
+
+/// A source an address origin was derived from.
+///
+/// A source consists of the trust anchor and the URI of the ROA. Either of
+/// them may be missing, for instance for origins added via local
+/// exceptions. Sources are ordered by the name of their trust anchor and
+/// then their URI with missing values ordered last.
+///
+/// Sources taken directly from validation also carry the [`Provenance`] of
+/// the ROA. It does not take part in comparisons.
+///
+/// [`Provenance`]: ../provenance/struct.Provenance.html
+#[derive(Clone, Debug)]
+pub struct OriginSource {
+    tal: Option<Arc<TalInfo>>,
+    uri: Option<Arc<str>>,
+    provenance: Option<Arc<Provenance>>,
+}
+
+impl OriginSource {
+    pub fn new(tal: Option<Arc<TalInfo>>, uri: Option<Arc<str>>) -> Self {
+        OriginSource { tal, uri, provenance: None }
+    }
+
+    /// Creates a source from a ROA with known provenance.
+    pub fn with_provenance(
+        tal: Option<Arc<TalInfo>>,
+        provenance: Arc<Provenance>
+    ) -> Self {
+        OriginSource {
+            tal,
+            uri: Some(provenance.roa_uri_arc().clone()),
+            provenance: Some(provenance)
+        }
+    }
+
+    pub fn tal(&self) -> Option<&Arc<TalInfo>> {
+        self.tal.as_ref()
+    }
+
+    /// Returns the name of the trust anchor or `"N/A"` if there is none.
+    pub fn tal_name(&self) -> &str {
+        match self.tal {
+            Some(ref tal) => tal.name(),
+            None => "N/A"
+        }
+    }
+
+    /// Returns the URI of the ROA if known.
+    pub fn uri(&self) -> Option<&str> {
+        self.uri.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns the provenance of the ROA if known.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref().map(AsRef::as_ref)
+    }
+}
+
+
+//--- PartialEq and Eq
+
+impl PartialEq for OriginSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OriginSource { }
+
+
+//--- PartialOrd and Ord
+
+impl PartialOrd for OriginSource {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OriginSource {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_none_last(
+            self.tal.as_ref().map(|tal| tal.name()),
+            other.tal.as_ref().map(|tal| tal.name())
+        ).then_with(|| cmp_none_last(self.uri(), other.uri()))
+    }
+}
+
+
 //------------ AddressPrefix -------------------------------------------------
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct AddressPrefix {
     addr: IpAddr,
     len: u8,
@@ -542,17 +1492,19 @@ impl AddressPrefix {
                 if self.len > other.len {
                     return false
                 }
-                let left = u32::from(left) & !(::std::u32::MAX >> self.len);
-                let right = u32::from(right) & !(::std::u32::MAX >> self.len);
-                left == right
+                let mask = !::std::u32::MAX.checked_shr(
+                    u32::from(self.len)
+                ).unwrap_or(0);
+                u32::from(left) & mask == u32::from(right) & mask
             }
             (IpAddr::V6(left), IpAddr::V6(right)) => {
                 if self.len > other.len {
                     return false
                 }
-                let left = u128::from(left) & !(::std::u128::MAX >> self.len);
-                let right = u128::from(right) & !(::std::u128::MAX >> self.len);
-                left == right
+                let mask = !::std::u128::MAX.checked_shr(
+                    u32::from(self.len)
+                ).unwrap_or(0);
+                u128::from(left) & mask == u128::from(right) & mask
             }
             _ => false
         }
@@ -579,11 +1531,65 @@ impl FromStr for AddressPrefix {
                           .map_err(|_| FromStrError(s.into()))?;
         let len = u8::from_str(len)
                      .map_err(|_| FromStrError(s.into()))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        if len > max_len {
+            return Err(FromStrError(s.into()))
+        }
         Ok(AddressPrefix { addr, len })
     }
 }
 
 
+//------------ TalCache ------------------------------------------------------
+
+/// Makes sure that origins of the same TAL share their TAL info.
+///
+/// This is used when reading route origins back from a file where trust
+/// anchors are only given by name.
+#[derive(Default)]
+pub(crate) struct TalCache(HashMap<String, Arc<TalInfo>>);
+
+impl TalCache {
+    /// Returns the TAL info for `name`.
+    pub fn get(&mut self, name: &str) -> Arc<TalInfo> {
+        self.0.entry(name.into()).or_insert_with(|| {
+            Arc::new(TalInfo::from_name(name.into()))
+        }).clone()
+    }
+
+    /// Returns the TAL info for `name` unless it is `"N/A"` or empty.
+    pub fn lookup(&mut self, name: &str) -> Option<Arc<TalInfo>> {
+        if name.is_empty() || name == "N/A" {
+            None
+        }
+        else {
+            Some(self.get(name))
+        }
+    }
+
+    /// Returns sources for a semicolon separated list of TAL names.
+    pub fn sources(&mut self, names: &str) -> Vec<OriginSource> {
+        names.split(';').filter_map(|name| {
+            self.lookup(name.trim())
+        }).map(|tal| OriginSource::new(Some(tal), None)).collect()
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses an AS number with or without the `AS` prefix.
+pub(crate) fn parse_as_id(s: &str) -> Option<AsId> {
+    let number = if s.starts_with("AS") || s.starts_with("as") {
+        &s[2..]
+    }
+    else {
+        s
+    };
+    u32::from_str(number).ok().map(AsId::from)
+}
+
+
 //------------ FromStrError --------------------------------------------------
 
 #[derive(Clone, Debug, Fail)]
@@ -591,6 +1597,19 @@ impl FromStr for AddressPrefix {
 pub struct FromStrError(String);
 
 
+//------------ CapacityError -------------------------------------------------
+
+/// A set of address origins cannot hold all the origins given to it.
+#[derive(Clone, Copy, Debug, Eq, Fail, PartialEq)]
+pub enum CapacityError {
+    #[fail(display="too many origin sources")]
+    Sources,
+
+    #[fail(display="too many TALs")]
+    Tals,
+}
+
+
 //------------ Tests ---------------------------------------------------------
 
 #[cfg(test)]
@@ -614,9 +1633,405 @@ pub mod tests {
         assert!(outer.covers(inner_low));
         assert!(outer.covers(inner_mid));
         assert!(outer.covers(inner_hi));
+
+        let host = make_pfx("10.0.0.1", 32);
+        assert!(host.covers(host));
+        assert!(!host.covers(make_pfx("10.0.0.2", 32)));
+        assert!(outer.covers(host));
+        let host = make_pfx("2001:db8::1", 128);
+        assert!(host.covers(host));
+        assert!(!host.covers(make_pfx("2001:db8::2", 128)));
+        assert!(make_pfx("::", 0).covers(host));
     }
 
+    fn make_origin(asn: u32, prefix: &str, max_len: u8) -> AddressOrigin {
+        AddressOrigin::new(AsId::from(asn), prefix.parse().unwrap(), max_len)
+    }
 
+    fn make_set(origins: &[(u32, &str, u8)]) -> AddressOrigins {
+        origins.iter().map(|&(asn, prefix, max_len)| {
+            make_origin(asn, prefix, max_len)
+        }).collect()
+    }
 
+    fn prefixes(origins: &[AddressOrigin]) -> Vec<String> {
+        origins.iter().map(|origin| {
+            format!("{}/{}", origin.address(), origin.address_length())
+        }).collect()
+    }
+
+    #[test]
+    fn should_sort_and_dedup_origins() {
+        let tal = Arc::new(TalInfo::from_name("test".into()));
+        let origins: AddressOrigins = vec![
+            make_origin(64497, "2001:db8::/32", 48),
+            make_origin(64496, "10.0.0.0/16", 24),
+            AddressOrigin::with_tal(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 20,
+                Some(tal.clone())
+            ),
+            make_origin(64496, "10.0.0.0/16", 20),
+            make_origin(64496, "10.0.0.0/8", 8),
+        ].into_iter().collect();
+        assert_eq!(origins.len(), 4);
+        assert_eq!(
+            origins.iter().map(|origin| {
+                (origin.address_length(), origin.max_length())
+            }).collect::<Vec<_>>(),
+            vec![(8, 8), (16, 20), (16, 24), (32, 48)]
+        );
+        assert_eq!(origins.get(1).unwrap().tal_name(), "test");
+        assert_eq!(origins.get(3).unwrap().as_id(), AsId::from(64497));
+        assert!(origins.get(4).is_none());
+    }
+
+    #[test]
+    fn should_order_origins_canonically() {
+        let first = Arc::new(TalInfo::from_name("first".into()));
+        let second = Arc::new(TalInfo::from_name("second".into()));
+        let mut origins = vec![
+            make_origin(64497, "2001:db8::/32", 48),
+            make_origin(64497, "10.0.0.0/16", 20),
+            AddressOrigin::with_tal(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 20,
+                Some(second)
+            ),
+            AddressOrigin::with_tal(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 20,
+                Some(first)
+            ),
+            make_origin(64496, "10.0.0.0/16", 24),
+            make_origin(64496, "10.0.0.0/8", 24),
+            make_origin(64496, "192.0.2.0/24", 24),
+        ];
+        let forward: AddressOrigins = origins.iter().cloned().collect();
+        origins.reverse();
+        let backward: AddressOrigins = origins.iter().cloned().collect();
+        let describe = |set: &AddressOrigins| -> Vec<String> {
+            set.iter().map(|origin| {
+                format!(
+                    "{} {}/{}-{} {}",
+                    origin.as_id(), origin.address(),
+                    origin.address_length(), origin.max_length(),
+                    origin.tal_list()
+                )
+            }).collect()
+        };
+        assert_eq!(describe(&forward), describe(&backward));
+        assert_eq!(
+            describe(&forward),
+            vec![
+                "AS64496 10.0.0.0/8-24 N/A",
+                "AS64496 10.0.0.0/16-20 first;second",
+                "AS64497 10.0.0.0/16-20 N/A",
+                "AS64496 10.0.0.0/16-24 N/A",
+                "AS64496 192.0.2.0/24-24 N/A",
+                "AS64497 2001:db8::/32-48 N/A",
+            ]
+        );
+        origins.sort();
+        origins.dedup();
+        assert_eq!(forward.iter().collect::<Vec<_>>(), origins);
+    }
+
+    #[test]
+    fn should_merge_origin_sources() {
+        let tal = Arc::new(TalInfo::from_name("tal".into()));
+        let source = |uri: &str| {
+            OriginSource::new(Some(tal.clone()), Some(uri.into()))
+        };
+        let origin = |sources: Vec<OriginSource>| {
+            AddressOrigin::with_sources(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 16,
+                sources
+            )
+        };
+        let origins: AddressOrigins = vec![
+            origin(vec![source("rsync://b/b.roa")]),
+            origin(vec![
+                source("rsync://c/c.roa"), source("rsync://a/a.roa")
+            ]),
+            origin(vec![source("rsync://b/b.roa")]),
+            origin(Vec::new()),
+        ].into_iter().collect();
+        assert_eq!(origins.len(), 1);
+        let merged = origins.get(0).unwrap();
+        assert_eq!(
+            merged.sources().iter().map(|source| {
+                source.uri().unwrap()
+            }).collect::<Vec<_>>(),
+            vec!["rsync://a/a.roa", "rsync://b/b.roa", "rsync://c/c.roa"]
+        );
+        assert_eq!(merged.tal_names(), vec!["tal"]);
+
+        // Sources don’t make a difference.
+        let other: AddressOrigins = vec![origin(Vec::new())]
+            .into_iter().collect();
+        let (announce, withdraw) = origins.diff(&other);
+        assert!(announce.is_empty() && withdraw.is_empty());
+        assert_eq!(other.get(0).unwrap().tal_name(), "N/A");
+    }
 
+    #[test]
+    fn should_keep_provenance() {
+        use ::provenance::tests::provenance;
+
+        let tal = Arc::new(TalInfo::from_name("tal".into()));
+        let origin = |as_id: u32, sources: Vec<OriginSource>| {
+            AddressOrigin::with_sources(
+                AsId::from(as_id), "10.0.0.0/16".parse().unwrap(), 16,
+                sources
+            )
+        };
+        let origins: AddressOrigins = vec![
+            origin(64496, vec![OriginSource::with_provenance(
+                Some(tal.clone()),
+                Arc::new(provenance("rsync://a/a.roa"))
+            )]),
+            origin(64497, vec![OriginSource::new(None, None)]),
+            origin(64496, vec![OriginSource::new(None, None)]),
+        ].into_iter().collect();
+        let found = origins.matching(
+            "10.0.0.0/16".parse().unwrap(), Some(AsId::from(64496))
+        );
+        assert_eq!(found.len(), 1);
+        let sources = found[0].sources();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].uri(), Some("rsync://a/a.roa"));
+        assert_eq!(
+            sources[0].provenance().unwrap().roa_uri(), "rsync://a/a.roa"
+        );
+        assert!(sources[1].provenance().is_none());
+        assert_eq!(
+            origins.matching("10.0.0.0/16".parse().unwrap(), None).len(), 2
+        );
+        assert!(
+            origins.matching("10.0.0.0/24".parse().unwrap(), None).is_empty()
+        );
+    }
+
+    #[test]
+    fn should_filter_before_adding_assertions() {
+        let tal = Arc::new(TalInfo::from_name("tal".into()));
+        let origin = |as_id: u32, prefix: &str, uris: &[&str]| {
+            AddressOrigin::with_sources(
+                AsId::from(as_id), prefix.parse().unwrap(), 16,
+                uris.iter().map(|uri| {
+                    OriginSource::new(Some(tal.clone()), Some((*uri).into()))
+                }).collect()
+            )
+        };
+        let exceptions = LocalExceptions::from_json(::json::parse(
+            r#"{
+                "slurmVersion": 1,
+                "validationOutputFilters": {
+                    "prefixFilters": [ { "prefix": "10.1.0.0/16" } ],
+                    "bgpsecFilters": []
+                },
+                "locallyAddedAssertions": {
+                    "prefixAssertions": [
+                        { "asn": 64496, "prefix": "10.0.0.0/16" },
+                        { "asn": 64497, "prefix": "10.1.0.0/16" }
+                    ],
+                    "bgpsecAssertions": []
+                }
+            }"#
+        ).unwrap()).unwrap();
+
+        let mut builder = Builder::default();
+        let validated = vec![
+            origin(64496, "10.0.0.0/16", &["rsync://a/a.roa"]),
+            origin(64496, "10.0.0.0/16", &["rsync://b/b.roa"]),
+            origin(64496, "10.1.0.0/16", &["rsync://a/a.roa"]),
+        ];
+        for addr in &validated {
+            builder.push_origin(addr).unwrap()
+        }
+        builder.filter(exceptions.filters());
+        for addr in exceptions.assertions() {
+            builder.push_origin(addr).unwrap()
+        }
+        let origins = builder.finish().unwrap();
+
+        assert_eq!(origins.len(), 2);
+        let first = origins.get(0).unwrap();
+        assert_eq!(
+            first.sources().iter().map(|source| {
+                source.uri().unwrap()
+            }).collect::<Vec<_>>(),
+            vec!["rsync://a/a.roa", "rsync://b/b.roa"]
+        );
+        let second = origins.get(1).unwrap();
+        assert_eq!(second.as_id(), AsId::from(64497));
+        assert!(second.sources().is_empty());
+
+        // Each source and list of sources is only stored once.
+        assert_eq!(origins.sources.len(), 2);
+        assert_eq!(origins.multi.len(), 1);
+    }
+
+    #[test]
+    fn should_find_covering_and_covered_origins() {
+        let origins = make_set(&[
+            (1, "10.0.0.0/8", 8), (2, "10.0.0.0/16", 16),
+            (3, "10.0.0.0/24", 24), (4, "10.0.1.0/24", 24),
+            (5, "10.1.0.0/16", 16), (6, "0.0.0.0/0", 0),
+            (7, "192.0.2.1/32", 32), (8, "2001:db8::/32", 32),
+        ]);
+        assert_eq!(
+            prefixes(&origins.covering("10.0.0.0/24".parse().unwrap())),
+            vec!["0.0.0.0/0", "10.0.0.0/8", "10.0.0.0/16", "10.0.0.0/24"]
+        );
+        assert_eq!(
+            prefixes(&origins.covering("10.1.2.0/24".parse().unwrap())),
+            vec!["0.0.0.0/0", "10.0.0.0/8", "10.1.0.0/16"]
+        );
+        assert_eq!(
+            prefixes(&origins.covering("192.0.2.1/32".parse().unwrap())),
+            vec!["0.0.0.0/0", "192.0.2.1/32"]
+        );
+        assert_eq!(
+            prefixes(&origins.covering("2001:db8:1::/48".parse().unwrap())),
+            vec!["2001:db8::/32"]
+        );
+        assert_eq!(
+            prefixes(&origins.covered("10.0.0.0/15".parse().unwrap())),
+            vec!["10.0.0.0/16", "10.0.0.0/24", "10.0.1.0/24", "10.1.0.0/16"]
+        );
+        assert_eq!(
+            prefixes(&origins.covered("10.0.0.0/16".parse().unwrap())),
+            vec!["10.0.0.0/16", "10.0.0.0/24", "10.0.1.0/24"]
+        );
+        assert_eq!(origins.covered("0.0.0.0/0".parse().unwrap()).len(), 7);
+        assert_eq!(origins.covered("::/0".parse().unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn should_diff_origins() {
+        let current = make_set(&[
+            (1, "10.0.0.0/8", 8), (2, "10.0.0.0/16", 16),
+            (3, "2001:db8::/32", 32),
+        ]);
+        let next = make_set(&[
+            (1, "10.0.0.0/8", 8), (2, "10.0.0.0/16", 24),
+            (3, "2001:db8::/32", 32), (4, "2001:db8::/32", 32),
+        ]);
+        let (announce, withdraw) = current.diff(&next);
+        assert_eq!(
+            announce,
+            vec![
+                make_origin(2, "10.0.0.0/16", 24),
+                make_origin(4, "2001:db8::/32", 32)
+            ]
+        );
+        assert_eq!(withdraw, vec![make_origin(2, "10.0.0.0/16", 16)]);
+        let (announce, withdraw) = next.diff(&AddressOrigins::new());
+        assert!(announce.is_empty());
+        assert_eq!(withdraw.len(), 4);
+    }
+
+    /// Creates a history with `count` diffs after `start`.
+    ///
+    /// Each diff announces a single origin with the serial as AS number.
+    fn make_history(start: u32, count: u8, keep: usize) -> OriginsHistory {
+        let history = OriginsHistory::from_parts(
+            AddressOrigins::new(), Vec::new(), Serial(start), 1, keep
+        );
+        {
+            let mut inner = history.0.write().unwrap();
+            for _ in 0..count {
+                let serial = inner.serial().next();
+                inner.push_diff(OriginsDiff::new(
+                    serial,
+                    vec![AddressOrigin::new(
+                        AsId::from(serial.0), make_pfx("10.0.0.0", 8), 8
+                    )],
+                    Vec::new()
+                ));
+            }
+        }
+        history
+    }
+
+    #[test]
+    fn should_merge_diffs_across_wrap() {
+        let history = make_history(::std::u32::MAX - 2, 5, 10);
+        assert_eq!(history.serial(), Serial(2));
+        let diff = history.get(Serial(::std::u32::MAX - 1)).unwrap();
+        assert_eq!(diff.serial(), Serial(2));
+        assert_eq!(diff.announce().len(), 4);
+        assert!(diff.announce().windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(history.get(Serial(3)).is_none());
+        assert!(history.get(Serial(::std::u32::MAX - 3)).is_none());
+    }
+
+    #[test]
+    fn should_replace_current_without_changes() {
+        let make_origin = |uri: &str| {
+            AddressOrigin::with_sources(
+                AsId::from(64496), make_pfx("10.0.0.0", 16), 16,
+                vec![OriginSource::new(None, Some(uri.into()))]
+            )
+        };
+        let history = OriginsHistory::from_parts(
+            vec![make_origin("rsync://example.net/old.roa")]
+                .into_iter().collect(),
+            Vec::new(), Serial(5), 1, 10
+        );
+
+        // The same route origin as a local assertion has no sources.
+        let exceptions = LocalExceptions::from_json(::json::parse(
+            r#"{
+                "slurmVersion": 1,
+                "validationOutputFilters": {
+                    "prefixFilters": [], "bgpsecFilters": []
+                },
+                "locallyAddedAssertions": {
+                    "prefixAssertions": [
+                        { "asn": 64496, "prefix": "10.0.0.0/16" }
+                    ],
+                    "bgpsecAssertions": []
+                }
+            }"#
+        ).unwrap()).unwrap();
+        assert_eq!(history.update(None, &exceptions), Ok(false));
+        assert_eq!(history.serial(), Serial(5));
+        assert!(history.diffs().is_empty());
+        assert!(history.current().get(0).unwrap().sources().is_empty());
+
+        assert_eq!(
+            history.update(None, &LocalExceptions::empty()), Ok(true)
+        );
+        assert_eq!(history.serial(), Serial(6));
+        assert!(history.current().is_empty());
+        assert_eq!(history.diffs()[0].withdraw().len(), 1);
+    }
+
+    quickcheck! {
+        fn history_provides_kept_diffs(
+            start: u32, count: u8, keep: u8, behind: u8
+        ) -> bool {
+            let (count, keep) = (count % 20, usize::from(keep % 10));
+            let history = make_history(start, count, keep);
+            let current = Serial(start) + u32::from(count);
+            let behind = behind % (count + 1);
+            let serial = Serial(current.0.wrapping_sub(u32::from(behind)));
+            match history.get(serial) {
+                Some(diff) => {
+                    usize::from(behind) <= keep
+                    && diff.serial() == current
+                    && diff.announce().len() == usize::from(behind)
+                }
+                None => usize::from(behind) > keep
+            }
+        }
+
+        fn history_resets_future_serials(
+            start: u32, count: u8, ahead: u32
+        ) -> bool {
+            let history = make_history(start, count % 20, 10);
+            let ahead = (ahead & Serial::MAX_ADD).max(1);
+            history.get(history.serial() + ahead).is_none()
+        }
+    }
 }
@@ -0,0 +1,1221 @@
+//! Writing route origins to output files.
+//!
+//! All output formats are produced by types implementing the
+//! [`OutputWriter`] trait. Writers are fed the route origins one by one so
+//! that the complete output never needs to be kept in memory. The
+//! [`OutputRegistry`] provides writers for all formats by name and allows
+//! adding new formats.
+//!
+//! The JSON formats are produced via the JSON serializer rather than by
+//! hand, so all values are properly escaped. They start with a `metadata`
+//! object describing the data set created from [`Metadata`]. The
+//! `rpki-client` format mimics the output of rpki-client for the benefit
+//! of tools written for it.
+//!
+//! Independently of the format, output can be limited to certain route
+//! origins via a [`Selection`].
+//!
+//! [`OutputWriter`]: trait.OutputWriter.html
+//! [`OutputRegistry`]: struct.OutputRegistry.html
+//! [`Metadata`]: struct.Metadata.html
+//! [`Selection`]: struct.Selection.html
+
+use std::io;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use chrono::{DateTime, SecondsFormat, Utc};
+use json::JsonValue;
+use rpki::asres::AsId;
+use super::origins::{
+    AddressOrigin, AddressOrigins, AddressPrefix, OriginSource, RouteOrigins
+};
+use super::report::ValidationReport;
+use super::slurm::SlurmWriter;
+use super::summary::SummaryWriter;
+use super::vendor::{IosXrWriter, JunosWriter};
+
+
+//------------ Metadata ------------------------------------------------------
+
+/// Information about a set of route origins included in output files.
+#[derive(Clone, Debug)]
+pub struct Metadata {
+    /// The time the output was generated.
+    generated: DateTime<Utc>,
+
+    /// The time the repository was last updated if it was updated at all.
+    last_update: Option<DateTime<Utc>>,
+
+    /// The number of valid ROAs.
+    roas: usize,
+
+    /// The number of route origins before removing duplicates.
+    vrps: usize,
+
+    /// How long updating the repository took if it was updated.
+    update_duration: Option<Duration>,
+
+    /// How long processing the repository took.
+    process_duration: Option<Duration>,
+
+    /// The number of unique route origins in total and per trust anchor.
+    ///
+    /// This is only available once the final set of route origins has
+    /// been added via `with_origins`.
+    counts: Option<OriginCounts>,
+}
+
+impl Metadata {
+    pub fn new(
+        generated: DateTime<Utc>,
+        last_update: Option<DateTime<Utc>>,
+        roas: usize,
+        vrps: usize,
+    ) -> Self {
+        Metadata {
+            generated, last_update, roas, vrps,
+            update_duration: None, process_duration: None, counts: None
+        }
+    }
+
+    /// Adds the numbers of the final set of route origins.
+    ///
+    /// These are the route origins after removing duplicates and applying
+    /// local exceptions that are included in `selection`.
+    pub fn with_origins(
+        self,
+        origins: &AddressOrigins,
+        selection: &Selection
+    ) -> Self {
+        let mut unique = 0;
+        let mut tals = BTreeMap::new();
+        for origin in origins.iter() {
+            if !selection.contains(&origin) {
+                continue
+            }
+            unique += 1;
+            for name in origin.tal_names() {
+                *tals.entry(name.to_string()).or_insert(0) += 1;
+            }
+        }
+        Metadata {
+            counts: Some(OriginCounts { unique, tals }),
+            .. self
+        }
+    }
+
+    /// Adds the durations of the update and processing stages.
+    pub fn with_durations(
+        self,
+        update: Option<Duration>,
+        process: Option<Duration>
+    ) -> Self {
+        Metadata {
+            update_duration: update,
+            process_duration: process,
+            .. self
+        }
+    }
+
+    /// Creates the metadata for the raw route origins generated just now.
+    pub fn from_route_origins(
+        origins: &RouteOrigins,
+        last_update: Option<DateTime<Utc>>
+    ) -> Self {
+        Metadata::new(
+            Utc::now(), last_update, origins.len(),
+            origins.iter().map(|roa| roa.iter().count()).sum()
+        )
+    }
+
+    /// Returns the time the output was generated.
+    pub fn generated(&self) -> DateTime<Utc> {
+        self.generated
+    }
+
+    /// Returns the time of the last repository update if there was one.
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        self.last_update
+    }
+
+    /// Returns the number of valid ROAs.
+    pub fn roas(&self) -> usize {
+        self.roas
+    }
+
+    /// Returns the number of route origins before removing duplicates.
+    pub fn vrps(&self) -> usize {
+        self.vrps
+    }
+
+    /// Returns how long updating the repository took.
+    pub fn update_duration(&self) -> Option<Duration> {
+        self.update_duration
+    }
+
+    /// Returns how long processing the repository took.
+    pub fn process_duration(&self) -> Option<Duration> {
+        self.process_duration
+    }
+
+    /// Returns the number of unique route origins if available.
+    pub fn unique_vrps(&self) -> Option<usize> {
+        self.counts.as_ref().map(|counts| counts.unique)
+    }
+
+    /// Returns the number of unique route origins per trust anchor.
+    ///
+    /// A route origin derived from more than one trust anchor is counted
+    /// for each of them.
+    pub fn tal_vrps(&self) -> Option<&BTreeMap<String, usize>> {
+        self.counts.as_ref().map(|counts| &counts.tals)
+    }
+
+    /// Returns the metadata object for our own JSON formats.
+    fn to_json(&self) -> JsonValue {
+        let mut res = JsonValue::new_object();
+        res["generator"] = concat!(
+            "Routinator ", env!("CARGO_PKG_VERSION")
+        ).into();
+        res["generated"] = self.generated.timestamp().into();
+        res["generatedTime"] = time(self.generated).into();
+        res["lastUpdate"] = self.last_update.map(time).into();
+        res["roas"] = self.roas.into();
+        res["vrps"] = self.vrps.into();
+        if let Some(ref counts) = self.counts {
+            let mut tas = JsonValue::new_object();
+            for (name, count) in &counts.tals {
+                tas[name.as_str()] = (*count).into();
+            }
+            res["uniqueVrps"] = counts.unique.into();
+            res["tas"] = tas;
+        }
+        res
+    }
+
+    /// Returns the metadata object for the rpki-client format.
+    ///
+    /// Only the members we actually have information for are included.
+    fn to_rpki_client_json(&self) -> JsonValue {
+        let mut res = JsonValue::new_object();
+        res["buildtime"] = time(self.generated).into();
+        res["roas"] = self.roas.into();
+        if let Some(ref counts) = self.counts {
+            res["tals"] = counts.tals.len().into();
+            res["talfiles"] = counts.tals.keys().map(String::as_str)
+                .collect::<Vec<_>>().join(" ").into();
+        }
+        res["vrps"] = self.vrps.into();
+        if let Some(ref counts) = self.counts {
+            res["uniquevrps"] = counts.unique.into();
+        }
+        res
+    }
+}
+
+
+//------------ OriginCounts --------------------------------------------------
+
+/// The number of unique route origins in total and per trust anchor.
+#[derive(Clone, Debug)]
+struct OriginCounts {
+    unique: usize,
+    tals: BTreeMap<String, usize>,
+}
+
+
+//------------ JsonFormat ----------------------------------------------------
+
+/// The flavours of JSON output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JsonFormat {
+    /// Each route origin with the names of all its trust anchors.
+    ///
+    /// Multiple names are separated by semicolons.
+    Standard,
+
+    /// Each route origin with a list of the ROAs it was derived from.
+    Extended,
+
+    /// Output compatible with rpki-client.
+    ///
+    /// Each route origin only lists the first of its trust anchors.
+    RpkiClient,
+}
+
+
+//------------ AsnFormat -----------------------------------------------------
+
+/// How AS numbers are represented in JSON output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsnFormat {
+    /// As a string with the `AS` prefix, e.g., `"AS64496"`.
+    String,
+
+    /// As a plain number.
+    Number,
+}
+
+impl AsnFormat {
+    /// Returns the default format for the given JSON format.
+    pub fn default_for(format: JsonFormat) -> Self {
+        match format {
+            JsonFormat::RpkiClient => AsnFormat::Number,
+            _ => AsnFormat::String,
+        }
+    }
+}
+
+
+//------------ Selection -----------------------------------------------------
+
+/// A selection of route origins to be included in the output.
+///
+/// Route origins can be selected by their AS number, by their prefix, and
+/// by the trust anchor they have been derived from. A route origin is
+/// selected if it matches at least one of the given values for each of
+/// these. If no values are given for one of them, all route origins match.
+/// Consequently, an empty selection selects all route origins.
+///
+/// A prefix matches if it either covers or is covered by the prefix of
+/// the route origin. That is, selecting a prefix includes both the route
+/// origins for less specific and more specific prefixes.
+///
+/// Since route origins from local exceptions don’t have a trust anchor,
+/// they are never included if trust anchors are selected.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// The selected AS numbers.
+    asns: Vec<AsId>,
+
+    /// The selected prefixes.
+    prefixes: Vec<AddressPrefix>,
+
+    /// The names of the selected trust anchors.
+    tals: Vec<String>,
+}
+
+impl Selection {
+    /// Creates a new, empty selection that includes all route origins.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an AS number to the selection.
+    pub fn add_asn(&mut self, asn: AsId) {
+        self.asns.push(asn)
+    }
+
+    /// Adds a prefix to the selection.
+    pub fn add_prefix(&mut self, prefix: AddressPrefix) {
+        self.prefixes.push(prefix)
+    }
+
+    /// Adds the name of a trust anchor to the selection.
+    pub fn add_tal(&mut self, tal: String) {
+        self.tals.push(tal)
+    }
+
+    /// Returns whether the selection includes all route origins.
+    pub fn is_empty(&self) -> bool {
+        self.asns.is_empty() && self.prefixes.is_empty()
+            && self.tals.is_empty()
+    }
+
+    /// Returns whether the selection includes the given route origin.
+    pub fn contains(&self, origin: &AddressOrigin) -> bool {
+        if !self.asns.is_empty()
+            && !self.asns.iter().any(|asn| *asn == origin.as_id())
+        {
+            return false
+        }
+        if !self.prefixes.is_empty() {
+            let prefix = origin.prefix();
+            if !self.prefixes.iter().any(|item| {
+                item.covers(prefix) || prefix.covers(*item)
+            }) {
+                return false
+            }
+        }
+        if !self.tals.is_empty() {
+            let names = origin.tal_names();
+            if !self.tals.iter().any(|tal| names.contains(&tal.as_str())) {
+                return false
+            }
+        }
+        true
+    }
+}
+
+
+//------------ OutputWriter --------------------------------------------------
+
+/// A type producing output in a certain format.
+///
+/// Output is produced by calling [`begin`] once, then [`entry`] for each
+/// route origin in the order they should appear, and finally [`end`]
+/// once. Since a writer only ever sees one route origin at a time, the
+/// route origins don’t need to be kept in memory all at once.
+///
+/// Writers for all the formats supported by Routinator can be created via
+/// an [`OutputRegistry`]. Additional formats can be added to a registry by
+/// implementing this trait.
+///
+/// [`begin`]: #method.begin
+/// [`entry`]: #tymethod.entry
+/// [`end`]: #method.end
+/// [`OutputRegistry`]: struct.OutputRegistry.html
+pub trait OutputWriter {
+    /// Starts the output.
+    ///
+    /// The default implementation writes nothing.
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        _target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// Writes a single route origin.
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error>;
+
+    /// Finishes the output.
+    ///
+    /// The default implementation writes nothing.
+    fn end(&mut self, _target: &mut dyn io::Write) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// Writes those route origins of a set included in `selection`.
+    fn write_origins(
+        &mut self,
+        metadata: &Metadata,
+        origins: &AddressOrigins,
+        selection: &Selection,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        self.begin(metadata, target)?;
+        for origin in origins.iter() {
+            if selection.contains(&origin) {
+                self.entry(&origin, target)?;
+            }
+        }
+        self.end(target)
+    }
+}
+
+
+//------------ OutputOptions -------------------------------------------------
+
+/// Format specific options for creating output writers.
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    /// How to represent AS numbers in JSON output.
+    ///
+    /// If this is `None`, the default of the JSON format is used.
+    pub json_asn: Option<AsnFormat>,
+
+    /// The name of the ROA table for the BIRD output formats.
+    ///
+    /// If this is `None`, the BIRD 1 output only contains the statements
+    /// for the route origins for inclusion into an existing table. Since
+    /// the BIRD 2 output always needs to define its tables, it fails with
+    /// an error instead.
+    pub bird_table: Option<String>,
+
+    /// Should the OpenBGPD output omit the `roa-set` block?
+    pub openbgpd_bare: bool,
+
+    /// The local AS for the `router bgp` section of IOS-XR output.
+    ///
+    /// If this is `None`, only the `rpki route` statements are produced.
+    pub iosxr_bgp_as: Option<AsId>,
+}
+
+
+//------------ OutputRegistry ------------------------------------------------
+
+/// A function creating an output writer.
+///
+/// The function receives the format specific options and the validation
+/// report for formats that include statistics.
+pub type WriterFactory = Box<
+    dyn Fn(&OutputOptions, &ValidationReport) -> Box<dyn OutputWriter>
+        + Send + Sync
+>;
+
+/// A collection of output formats available by name.
+#[derive(Default)]
+pub struct OutputRegistry {
+    formats: BTreeMap<String, WriterFactory>,
+}
+
+impl OutputRegistry {
+    /// Creates a new registry without any formats.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new registry with all formats supported by Routinator.
+    ///
+    /// The names of the formats are those used with the `-f` command line
+    /// option.
+    pub fn with_builtin() -> Self {
+        let mut res = Self::new();
+        res.register("csv", |_, _| Box::new(CsvWriter));
+        res.register("csvext", |_, _| Box::new(ExtendedCsvWriter));
+        res.register("json", |options, _| {
+            Box::new(JsonWriter::from_options(JsonFormat::Standard, options))
+        });
+        res.register("jsonext", |options, _| {
+            Box::new(JsonWriter::from_options(JsonFormat::Extended, options))
+        });
+        res.register("rpki-client", |options, _| {
+            Box::new(
+                JsonWriter::from_options(JsonFormat::RpkiClient, options)
+            )
+        });
+        res.register("rpsl", |_, _| Box::new(RpslWriter::new()));
+        res.register("bird1", |options, _| {
+            Box::new(Bird1Writer::new(options.bird_table.clone()))
+        });
+        res.register("bird2", |options, _| {
+            Box::new(Bird2Writer::from_options(options))
+        });
+        res.register("openbgpd", |options, _| {
+            Box::new(OpenBgpdWriter::new(options.openbgpd_bare))
+        });
+        res.register("iosxr", |options, _| {
+            Box::new(IosXrWriter::new(options.iosxr_bgp_as))
+        });
+        res.register("junos", |_, _| Box::new(JunosWriter::new()));
+        res.register("slurm", |_, _| Box::new(SlurmWriter::new()));
+        res.register("summary", |_, report| {
+            Box::new(SummaryWriter::new(report))
+        });
+        res
+    }
+
+    /// Adds a format to the registry.
+    ///
+    /// If there already is a format by this name, it is replaced.
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&OutputOptions, &ValidationReport) -> Box<dyn OutputWriter>
+            + Send + Sync + 'static
+    {
+        self.formats.insert(name.into(), Box::new(factory));
+    }
+
+    /// Returns whether the registry contains a format by the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.formats.contains_key(name)
+    }
+
+    /// Returns an iterator over the names of all formats.
+    pub fn names(&self) -> impl Iterator<Item=&str> {
+        self.formats.keys().map(String::as_str)
+    }
+
+    /// Creates a writer for the format with the given name.
+    ///
+    /// Returns `None` if there is no such format.
+    pub fn writer(
+        &self,
+        name: &str,
+        options: &OutputOptions,
+        report: &ValidationReport
+    ) -> Option<Box<dyn OutputWriter>> {
+        self.formats.get(name).map(|factory| factory(options, report))
+    }
+}
+
+
+//------------ CsvWriter -----------------------------------------------------
+
+/// Writes route origins as comma-separated values.
+///
+/// The fields are the AS number, the prefix, the maximum length, and the
+/// names of the trust anchors.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CsvWriter;
+
+impl OutputWriter for CsvWriter {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        writeln!(target, "ASN,IP Prefix,Max Length,Trust Anchor")
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        writeln!(target, "{},{}/{},{},{}",
+            origin.as_id(),
+            origin.address(), origin.address_length(),
+            origin.max_length(),
+            origin.tal_list(),
+        )
+    }
+}
+
+
+//------------ ExtendedCsvWriter ---------------------------------------------
+
+/// Writes route origins as comma-separated values with their sources.
+///
+/// There is one line for each ROA a route origin was derived from with
+/// the URI of the ROA as the first field.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExtendedCsvWriter;
+
+impl OutputWriter for ExtendedCsvWriter {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        writeln!(target, "URI,ASN,IP Prefix,Max Length,Trust Anchor")
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        // Origins from local exceptions don’t have a source but should
+        // still appear.
+        let unknown = [OriginSource::new(None, None)];
+        let sources = if origin.sources().is_empty() {
+            &unknown[..]
+        }
+        else {
+            origin.sources()
+        };
+        for source in sources {
+            writeln!(target, "{},{},{}/{},{},{}",
+                source.uri().unwrap_or("N/A"),
+                origin.as_id(),
+                origin.address(), origin.address_length(),
+                origin.max_length(),
+                source.tal_name(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ JsonWriter ----------------------------------------------------
+
+/// Writes route origins in one of the JSON formats.
+///
+/// The output starts with a `metadata` object followed by the route
+/// origins in the `roas` array.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonWriter {
+    format: JsonFormat,
+    asn: AsnFormat,
+    first: bool,
+}
+
+impl JsonWriter {
+    /// Creates a writer for the given format and AS number representation.
+    pub fn new(format: JsonFormat, asn: AsnFormat) -> Self {
+        JsonWriter { format, asn, first: true }
+    }
+
+    /// Creates a writer for the given format with the given options.
+    pub fn from_options(format: JsonFormat, options: &OutputOptions) -> Self {
+        Self::new(
+            format,
+            options.json_asn.unwrap_or_else(|| {
+                AsnFormat::default_for(format)
+            })
+        )
+    }
+}
+
+impl OutputWriter for JsonWriter {
+    fn begin(
+        &mut self,
+        metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        let metadata = match self.format {
+            JsonFormat::RpkiClient => metadata.to_rpki_client_json(),
+            _ => metadata.to_json(),
+        };
+        self.first = true;
+        writeln!(
+            target, "{{\n  \"metadata\": {},\n  \"roas\": [",
+            metadata.pretty(2).replace("\n", "\n  ")
+        )
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        if self.first {
+            self.first = false
+        }
+        else {
+            writeln!(target, ",")?;
+        }
+        write!(
+            target, "    {}",
+            origin_to_json(origin, self.format, self.asn).dump()
+        )
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if !self.first {
+            writeln!(target)?;
+        }
+        writeln!(target, "  ]\n}}")
+    }
+}
+
+fn origin_to_json(
+    origin: &AddressOrigin,
+    format: JsonFormat,
+    asn: AsnFormat
+) -> JsonValue {
+    let mut res = JsonValue::new_object();
+    res["asn"] = match asn {
+        AsnFormat::String => origin.as_id().to_string().into(),
+        AsnFormat::Number => u32::from(origin.as_id()).into(),
+    };
+    res["prefix"] = format!(
+        "{}/{}", origin.address(), origin.address_length()
+    ).into();
+    res["maxLength"] = origin.max_length().into();
+    match format {
+        JsonFormat::Standard => {
+            res["ta"] = origin.tal_list().into();
+        }
+        JsonFormat::Extended => {
+            let mut sources = JsonValue::new_array();
+            for source in origin.sources() {
+                let mut item = JsonValue::new_object();
+                item["ta"] = source.tal_name().into();
+                item["uri"] = source.uri().into();
+                let _ = sources.push(item);
+            }
+            res["source"] = sources;
+        }
+        JsonFormat::RpkiClient => {
+            res["ta"] = origin.tal_name().into();
+        }
+    }
+    res
+}
+
+
+//------------ RpslWriter ----------------------------------------------------
+
+/// Writes route origins as RPSL route objects.
+#[derive(Clone, Debug, Default)]
+pub struct RpslWriter {
+    /// The time of creation of the objects.
+    now: String,
+
+    /// The source attribute for each trust anchor.
+    sources: HashMap<String, String>,
+}
+
+impl RpslWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OutputWriter for RpslWriter {
+    fn begin(
+        &mut self,
+        metadata: &Metadata,
+        _target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        self.now = metadata.generated().to_rfc3339();
+        Ok(())
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        let tal = origin.tal_name();
+        let source = self.sources.entry(tal.to_string()).or_insert_with(|| {
+            format!("ROA-{}-RPKI-ROOT", tal.to_uppercase())
+        });
+        writeln!(target,
+            "\r\nroute: {}/{}\r\norigin: {}\r\n\
+            descr: RPKI attestation\r\nmnt-by: NA\r\ncreated: {}\r\n\
+            last-modified: {}\r\nsource: {}\r\n",
+            origin.address(), origin.address_length(),
+            origin.as_id(), self.now, self.now, source
+        )
+    }
+}
+
+
+//------------ Bird1Writer ---------------------------------------------------
+
+/// Writes route origins as BIRD 1 ROA entries.
+///
+/// If a table name is given, the entries are wrapped into a definition of
+/// this ROA table.
+#[derive(Clone, Debug, Default)]
+pub struct Bird1Writer {
+    table: Option<String>,
+}
+
+impl Bird1Writer {
+    pub fn new(table: Option<String>) -> Self {
+        Bird1Writer { table }
+    }
+}
+
+impl OutputWriter for Bird1Writer {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        if let Some(ref table) = self.table {
+            writeln!(target, "roa table {} {{", table)?;
+        }
+        Ok(())
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        writeln!(target, "{}roa {}/{} max {} as {};",
+            if self.table.is_some() { "    " } else { "" },
+            origin.address(), origin.address_length(),
+            origin.max_length(),
+            u32::from(origin.as_id()),
+        )
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if self.table.is_some() {
+            writeln!(target, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Bird2Writer ---------------------------------------------------
+
+/// Writes route origins as BIRD 2 static routes.
+///
+/// Since BIRD 2 keeps IPv4 and IPv6 ROAs in separate tables, the table
+/// name is suffixed with `4` and `6`, respectively, and both tables and a
+/// static protocol for each of them are defined. A single list of routes
+/// can’t be included into either of them, so the definitions are always
+/// produced. This relies on the route origins being ordered by address
+/// family.
+///
+/// Consequently, the writer needs a table name. If it is created from
+/// options that lack one, writing fails with an error.
+#[derive(Clone, Debug)]
+pub struct Bird2Writer {
+    /// The name of the tables without the address family suffix.
+    table: Option<String>,
+
+    /// The address family of the current static protocol.
+    current: Option<u8>,
+}
+
+impl Bird2Writer {
+    /// Creates a writer for tables with the given name.
+    pub fn new(table: String) -> Self {
+        Bird2Writer { table: Some(table), current: None }
+    }
+
+    /// Creates a writer using the table name given in the options.
+    pub fn from_options(options: &OutputOptions) -> Self {
+        Bird2Writer { table: options.bird_table.clone(), current: None }
+    }
+
+    /// Returns the table name or an error if there isn’t one.
+    fn table(&self) -> Result<&str, io::Error> {
+        self.table.as_ref().map(String::as_str).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the bird2 output format needs a table name"
+            )
+        })
+    }
+}
+
+impl OutputWriter for Bird2Writer {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        self.current = None;
+        let table = self.table()?;
+        writeln!(target, "roa4 table {}4;\nroa6 table {}6;", table, table)
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        let family = if origin.address().is_ipv4() { 4 } else { 6 };
+        if self.current != Some(family) {
+            if self.current.is_some() {
+                writeln!(target, "}}")?;
+            }
+            let table = self.table()?;
+            writeln!(
+                target, "\nprotocol static {}{} {{\n    \
+                roa{} {{ table {}{}; }};",
+                table, family, family, table, family
+            )?;
+            self.current = Some(family);
+        }
+        writeln!(target, "    route {}/{} max {} as {};",
+            origin.address(), origin.address_length(),
+            origin.max_length(),
+            u32::from(origin.as_id()),
+        )
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if self.current.is_some() {
+            writeln!(target, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ OpenBgpdWriter ------------------------------------------------
+
+/// Writes route origins as an OpenBGPD `roa-set`.
+///
+/// The output can be used directly as a file included into the bgpd
+/// configuration. If `bare` is true, the entries are produced without the
+/// enclosing `roa-set` block.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenBgpdWriter {
+    bare: bool,
+}
+
+impl OpenBgpdWriter {
+    pub fn new(bare: bool) -> Self {
+        OpenBgpdWriter { bare }
+    }
+}
+
+impl OutputWriter for OpenBgpdWriter {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        if !self.bare {
+            writeln!(target, "roa-set {{")?;
+        }
+        Ok(())
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        write!(
+            target, "\t{}/{}", origin.address(), origin.address_length()
+        )?;
+        if origin.max_length() > origin.address_length() {
+            write!(target, " maxlen {}", origin.max_length())?;
+        }
+        writeln!(target, " source-as {}", u32::from(origin.as_id()))
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if !self.bare {
+            writeln!(target, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+    use chrono::TimeZone;
+    use json;
+    use rpki::tal::TalInfo;
+    use super::*;
+
+    /// Returns the output of `writer` for `origins` as a string.
+    pub fn written<W: OutputWriter>(
+        mut writer: W,
+        origins: &AddressOrigins
+    ) -> String {
+        written_by(&mut writer, origins)
+    }
+
+    /// Returns the output of a writer trait object for `origins`.
+    pub fn written_by(
+        writer: &mut dyn OutputWriter,
+        origins: &AddressOrigins
+    ) -> String {
+        let metadata = Metadata::new(
+            Utc.ymd(2026, 10, 18).and_hms(12, 0, 0), None, 2, 3
+        ).with_origins(origins, &Selection::new());
+        let mut res = Vec::new();
+        writer.write_origins(
+            &metadata, origins, &Selection::new(), &mut res
+        ).unwrap();
+        String::from_utf8(res).unwrap()
+    }
+
+    fn origins() -> AddressOrigins {
+        let ripe = Arc::new(TalInfo::from_name("ripe".into()));
+        let quote = Arc::new(TalInfo::from_name("sp\"ecial".into()));
+        vec![
+            AddressOrigin::with_sources(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 24,
+                vec![
+                    OriginSource::new(
+                        Some(ripe.clone()), Some("rsync://a/a.roa".into())
+                    ),
+                    OriginSource::new(Some(quote), None),
+                ]
+            ),
+            AddressOrigin::with_tal(
+                AsId::from(64497), "2001:db8::/32".parse().unwrap(), 48,
+                Some(ripe)
+            ),
+        ].into_iter().collect()
+    }
+
+    fn write(format: JsonFormat, asn: AsnFormat) -> JsonValue {
+        json::parse(
+            &written(JsonWriter::new(format, asn), &origins())
+        ).unwrap()
+    }
+
+    #[test]
+    fn should_write_json() {
+        let json = write(JsonFormat::Standard, AsnFormat::String);
+        assert_eq!(json["metadata"]["generatedTime"], "2026-10-18T12:00:00Z");
+        assert!(json["metadata"]["lastUpdate"].is_null());
+        assert_eq!(json["metadata"]["uniqueVrps"], 2);
+        assert_eq!(json["metadata"]["tas"]["ripe"], 2);
+        assert_eq!(json["metadata"]["tas"]["sp\"ecial"], 1);
+        assert_eq!(json["roas"].len(), 2);
+        assert_eq!(json["roas"][0]["asn"], "AS64496");
+        assert_eq!(json["roas"][0]["ta"], "ripe;sp\"ecial");
+        assert_eq!(json["roas"][1]["prefix"], "2001:db8::/32");
+
+        let json = write(JsonFormat::Extended, AsnFormat::Number);
+        assert_eq!(json["roas"][0]["asn"], 64496);
+        assert_eq!(json["roas"][0]["source"].len(), 2);
+        assert_eq!(json["roas"][0]["source"][0]["uri"], "rsync://a/a.roa");
+        assert!(json["roas"][0]["source"][1]["uri"].is_null());
+    }
+
+    #[test]
+    fn should_write_rpki_client_json() {
+        let json = write(
+            JsonFormat::RpkiClient,
+            AsnFormat::default_for(JsonFormat::RpkiClient)
+        );
+        assert_eq!(json["metadata"]["buildtime"], "2026-10-18T12:00:00Z");
+        assert_eq!(json["metadata"]["roas"], 2);
+        assert_eq!(json["metadata"]["vrps"], 3);
+        assert_eq!(json["metadata"]["uniquevrps"], 2);
+        assert_eq!(json["metadata"]["tals"], 2);
+        assert_eq!(json["roas"][0]["asn"], 64496);
+        assert_eq!(json["roas"][0]["ta"], "ripe");
+        assert_eq!(json["roas"][1]["maxLength"], 48);
+    }
+
+    #[test]
+    fn should_select_origins() {
+        let origins = origins();
+        let selected = |selection: &Selection| {
+            origins.iter().filter(|origin| {
+                selection.contains(origin)
+            }).collect::<Vec<_>>()
+        };
+        let mut selection = Selection::new();
+        assert!(selection.is_empty());
+        assert_eq!(selected(&selection).len(), 2);
+
+        selection.add_prefix("10.0.0.0/8".parse().unwrap());
+        selection.add_prefix("2001:db8::1/128".parse().unwrap());
+        assert_eq!(selected(&selection).len(), 2);
+        selection.add_asn(AsId::from(64497));
+        let res = selected(&selection);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].as_id(), AsId::from(64497));
+
+        let mut selection = Selection::new();
+        selection.add_prefix("10.0.1.0/24".parse().unwrap());
+        assert_eq!(selected(&selection).len(), 1);
+        selection.add_tal("sp\"ecial".into());
+        assert_eq!(selected(&selection).len(), 1);
+
+        let mut selection = Selection::new();
+        selection.add_tal("sp\"ecial".into());
+        selection.add_tal("apnic".into());
+        assert_eq!(selected(&selection).len(), 1);
+        assert!(
+            !selection.contains(&AddressOrigin::new(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 24
+            ))
+        );
+
+        let metadata = Metadata::new(Utc::now(), None, 2, 3).with_origins(
+            &origins, &selection
+        );
+        assert_eq!(metadata.unique_vrps(), Some(1));
+        assert_eq!(metadata.tal_vrps().unwrap().get("ripe"), Some(&1));
+        let mut res = Vec::new();
+        CsvWriter.write_origins(
+            &metadata, &origins, &selection, &mut res
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(res).unwrap(),
+            "ASN,IP Prefix,Max Length,Trust Anchor\n\
+             AS64496,10.0.0.0/16,24,ripe;sp\"ecial\n"
+        );
+    }
+
+    #[test]
+    fn should_write_empty_json() {
+        let metadata = Metadata::new(Utc::now(), None, 0, 0);
+        let mut res = Vec::new();
+        JsonWriter::new(
+            JsonFormat::Standard, AsnFormat::String
+        ).write_origins(
+            &metadata, &AddressOrigins::new(), &Selection::new(), &mut res
+        ).unwrap();
+        let json = json::parse(&String::from_utf8(res).unwrap()).unwrap();
+        assert!(json["metadata"]["uniqueVrps"].is_null());
+        assert!(json["roas"].is_array());
+        assert!(json["roas"].is_empty());
+    }
+
+    #[test]
+    fn should_write_bird1() {
+        let origins = origins();
+        assert_eq!(
+            written(Bird1Writer::new(None), &origins),
+            "roa 10.0.0.0/16 max 24 as 64496;\n\
+             roa 2001:db8::/32 max 48 as 64497;\n"
+        );
+        assert_eq!(
+            written(Bird1Writer::new(Some("r".into())), &origins),
+            "roa table r {\n\
+             \x20   roa 10.0.0.0/16 max 24 as 64496;\n\
+             \x20   roa 2001:db8::/32 max 48 as 64497;\n}\n"
+        );
+    }
+
+    #[test]
+    fn should_write_bird2() {
+        let origins = origins();
+        assert_eq!(
+            written(Bird2Writer::new("r".into()), &origins),
+            "roa4 table r4;\nroa6 table r6;\n\
+             \nprotocol static r4 {\n    roa4 { table r4; };\n\
+             \x20   route 10.0.0.0/16 max 24 as 64496;\n}\n\
+             \nprotocol static r6 {\n    roa6 { table r6; };\n\
+             \x20   route 2001:db8::/32 max 48 as 64497;\n}\n"
+        );
+
+        let mut writer = Bird2Writer::from_options(&OutputOptions::default());
+        let mut res = Vec::new();
+        assert!(writer.write_origins(
+            &Metadata::new(Utc::now(), None, 0, 0), &origins,
+            &Selection::new(), &mut res
+        ).is_err());
+    }
+
+    #[test]
+    fn should_write_openbgpd() {
+        let origins = origins();
+        assert_eq!(
+            written(OpenBgpdWriter::new(false), &origins),
+            "roa-set {\n\
+             \t10.0.0.0/16 maxlen 24 source-as 64496\n\
+             \t2001:db8::/32 maxlen 48 source-as 64497\n}\n"
+        );
+    }
+
+    #[test]
+    fn should_register_all_formats() {
+        let names = [
+            "bird1", "bird2", "csv", "csvext", "iosxr", "json", "jsonext",
+            "junos", "openbgpd", "rpki-client", "rpsl", "slurm", "summary"
+        ];
+        let registry = OutputRegistry::with_builtin();
+        assert_eq!(registry.names().collect::<Vec<_>>(), names);
+
+        struct Count(usize);
+        impl OutputWriter for Count {
+            fn entry(
+                &mut self,
+                _origin: &AddressOrigin,
+                _target: &mut dyn io::Write
+            ) -> Result<(), io::Error> {
+                self.0 += 1;
+                Ok(())
+            }
+
+            fn end(
+                &mut self,
+                target: &mut dyn io::Write
+            ) -> Result<(), io::Error> {
+                writeln!(target, "{}", self.0)
+            }
+        }
+        let mut registry = OutputRegistry::new();
+        registry.register("count", |_, _| Box::new(Count(0)));
+        assert!(registry.contains("count"));
+        assert!(!registry.contains("csv"));
+        let mut writer = registry.writer(
+            "count", &OutputOptions::default(), &ValidationReport::new()
+        ).unwrap();
+        assert_eq!(written_by(&mut *writer, &origins()), "2\n");
+        assert!(registry.writer(
+            "csv", &OutputOptions::default(), &ValidationReport::new()
+        ).is_none());
+    }
+}
@@ -0,0 +1,737 @@
+//! The provenance of route origins.
+//!
+//! While validating an RPKI repository, [`Repository`] records for every
+//! ROA where it came from: the URI of the ROA itself, the validity of its
+//! EE certificate, and the chain of CA certificates that issued it along
+//! with the manifest each of these CAs published. This information is
+//! kept as a [`Provenance`] alongside each source of an address origin and
+//! can be used to explain why a certain route origin exists. The
+//! [`Explanation`] type does exactly that for all route origins of a given
+//! prefix.
+//!
+//! Since the rpki crate currently doesn’t give access to the manifest
+//! number, [`ManifestNumber`] takes it from the encoded manifest directly.
+//!
+//! Recording provenance costs memory for as long as the route origins are
+//! kept, so [`Repository`] only does so if asked to.
+//!
+//! [`Repository`]: ../repository/struct.Repository.html
+//! [`Provenance`]: struct.Provenance.html
+//! [`Explanation`]: struct.Explanation.html
+//! [`ManifestNumber`]: struct.ManifestNumber.html
+
+use std::{fmt, io, mem};
+use std::sync::Arc;
+use chrono::{DateTime, SecondsFormat, Utc};
+use json::JsonValue;
+use rpki::asres::AsId;
+use super::origins::{AddressOrigin, AddressOrigins, AddressPrefix};
+
+
+//------------ Provenance ----------------------------------------------------
+
+/// Where a route origin attestation came from.
+#[derive(Clone, Debug)]
+pub struct Provenance {
+    /// The URI of the ROA.
+    roa_uri: Arc<str>,
+
+    /// The start of the validity of the ROA’s EE certificate.
+    ee_not_before: DateTime<Utc>,
+
+    /// The end of the validity of the ROA’s EE certificate.
+    ee_not_after: DateTime<Utc>,
+
+    /// The CA that issued the ROA.
+    ca: Arc<CaInfo>,
+}
+
+impl Provenance {
+    pub fn new(
+        roa_uri: Arc<str>,
+        ee_not_before: DateTime<Utc>,
+        ee_not_after: DateTime<Utc>,
+        ca: Arc<CaInfo>,
+    ) -> Self {
+        Provenance { roa_uri, ee_not_before, ee_not_after, ca }
+    }
+
+    /// Returns the URI of the ROA.
+    pub fn roa_uri(&self) -> &str {
+        &self.roa_uri
+    }
+
+    /// Returns the URI of the ROA as a shared string.
+    pub fn roa_uri_arc(&self) -> &Arc<str> {
+        &self.roa_uri
+    }
+
+    /// Returns the start of the validity of the ROA’s EE certificate.
+    pub fn ee_not_before(&self) -> DateTime<Utc> {
+        self.ee_not_before
+    }
+
+    /// Returns the end of the validity of the ROA’s EE certificate.
+    pub fn ee_not_after(&self) -> DateTime<Utc> {
+        self.ee_not_after
+    }
+
+    /// Returns the CA that issued the ROA.
+    pub fn ca(&self) -> &CaInfo {
+        &self.ca
+    }
+
+    /// Returns an iterator over the CAs from the issuer up to the TA.
+    pub fn chain(&self) -> CaChain {
+        CaChain { next: Some(&self.ca) }
+    }
+
+    /// Writes a textual description of the provenance.
+    ///
+    /// Each line of the output is prefixed with `indent`.
+    pub fn write_text<W: io::Write>(
+        &self,
+        indent: &str,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(target, "{}ROA:            {}", indent, self.roa_uri)?;
+        writeln!(
+            target, "{}EE valid from:  {}", indent, time(self.ee_not_before)
+        )?;
+        writeln!(
+            target, "{}EE valid until: {}", indent, time(self.ee_not_after)
+        )?;
+        for ca in self.chain() {
+            if ca.is_trust_anchor() {
+                writeln!(target, "{}TA cert:        {}", indent, ca.uri())?;
+            }
+            else {
+                writeln!(target, "{}CA cert:        {}", indent, ca.uri())?;
+            }
+            if let Some(uri) = ca.manifest_uri() {
+                writeln!(target, "{}  manifest:     {}", indent, uri)?;
+            }
+            if let Some(number) = ca.manifest_number() {
+                writeln!(target, "{}  number:       {}", indent, number)?;
+            }
+            if let Some(update) = ca.manifest_this_update() {
+                writeln!(
+                    target, "{}  this update:  {}", indent, time(update)
+                )?;
+            }
+            if let Some(update) = ca.manifest_next_update() {
+                writeln!(
+                    target, "{}  next update:  {}", indent, time(update)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a JSON representation of the provenance.
+    pub fn to_json(&self) -> JsonValue {
+        let mut res = JsonValue::new_object();
+        res["roa"] = self.roa_uri().into();
+        res["eeNotBefore"] = time(self.ee_not_before).into();
+        res["eeNotAfter"] = time(self.ee_not_after).into();
+        let mut chain = JsonValue::new_array();
+        for ca in self.chain() {
+            let _ = chain.push(ca.to_json());
+        }
+        res["chain"] = chain;
+        res
+    }
+}
+
+
+//------------ CaInfo --------------------------------------------------------
+
+/// Information about a CA certificate and its manifest.
+///
+/// The information is shared between all objects published by the CA and
+/// refers to the information of the issuing CA, forming the chain up to
+/// the trust anchor.
+#[derive(Clone, Debug)]
+pub struct CaInfo {
+    /// The URI of the CA certificate.
+    uri: String,
+
+    /// The URI of the manifest used for the CA’s objects.
+    manifest_uri: Option<String>,
+
+    /// The manifest number of the manifest.
+    manifest_number: Option<ManifestNumber>,
+
+    /// The this update time of the manifest.
+    manifest_this_update: Option<DateTime<Utc>>,
+
+    /// The next update time of the manifest.
+    manifest_next_update: Option<DateTime<Utc>>,
+
+    /// The CA that issued this CA’s certificate.
+    ///
+    /// This is `None` for a trust anchor.
+    parent: Option<Arc<CaInfo>>,
+}
+
+impl CaInfo {
+    /// Creates information for a CA without manifest information.
+    pub fn new(uri: String, parent: Option<Arc<CaInfo>>) -> Self {
+        CaInfo {
+            uri,
+            manifest_uri: None,
+            manifest_number: None,
+            manifest_this_update: None,
+            manifest_next_update: None,
+            parent
+        }
+    }
+
+    /// Adds information about the manifest used for the CA.
+    pub fn with_manifest(
+        self,
+        uri: String,
+        number: Option<ManifestNumber>,
+        this_update: DateTime<Utc>,
+        next_update: DateTime<Utc>
+    ) -> Self {
+        CaInfo {
+            manifest_uri: Some(uri),
+            manifest_number: number,
+            manifest_this_update: Some(this_update),
+            manifest_next_update: Some(next_update),
+            .. self
+        }
+    }
+
+    /// Returns the URI of the CA certificate.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the URI of the manifest if known.
+    pub fn manifest_uri(&self) -> Option<&str> {
+        self.manifest_uri.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns the manifest number of the manifest if known.
+    pub fn manifest_number(&self) -> Option<&ManifestNumber> {
+        self.manifest_number.as_ref()
+    }
+
+    /// Returns the this update time of the manifest if known.
+    pub fn manifest_this_update(&self) -> Option<DateTime<Utc>> {
+        self.manifest_this_update
+    }
+
+    /// Returns the next update time of the manifest if known.
+    pub fn manifest_next_update(&self) -> Option<DateTime<Utc>> {
+        self.manifest_next_update
+    }
+
+    /// Returns the information of the issuing CA.
+    pub fn parent(&self) -> Option<&CaInfo> {
+        self.parent.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns whether this is a trust anchor certificate.
+    pub fn is_trust_anchor(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    /// Returns a JSON representation of the CA information.
+    ///
+    /// The parent is not included.
+    pub fn to_json(&self) -> JsonValue {
+        let mut res = JsonValue::new_object();
+        res["uri"] = self.uri().into();
+        res["manifest"] = self.manifest_uri().into();
+        // Manifest numbers can have up to 160 bits, so they are given as a
+        // string of decimal digits.
+        res["manifestNumber"] = self.manifest_number().map(|number| {
+            number.to_string()
+        }).into();
+        res["thisUpdate"] = self.manifest_this_update.map(time).into();
+        res["nextUpdate"] = self.manifest_next_update.map(time).into();
+        res
+    }
+}
+
+
+//------------ ManifestNumber ------------------------------------------------
+
+/// The manifest number of a manifest.
+///
+/// A manifest number is an unsigned integer of up to 160 bits. It is kept
+/// as its big-endian octets without leading zeros.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ManifestNumber(Box<[u8]>);
+
+impl ManifestNumber {
+    /// Takes the manifest number from an encoded manifest.
+    ///
+    /// This walks through the DER encoding of the signed object described
+    /// in RFC 6488 down to the manifest content of RFC 6486 and returns
+    /// its `manifestNumber` field. Returns `None` if the data isn’t
+    /// encoded as expected or the number is negative.
+    pub fn from_manifest(data: &[u8]) -> Option<Self> {
+        // ContentInfo
+        let mut info = Der(Der(data).take(TAG_SEQUENCE)?);
+        info.take(TAG_OID)?;
+        // SignedData
+        let signed = Der(info.take(TAG_CONTEXT_0)?).take(TAG_SEQUENCE)?;
+        let mut signed = Der(signed);
+        signed.take(TAG_INTEGER)?;
+        signed.take(TAG_SET)?;
+        // EncapsulatedContentInfo
+        let mut encap = Der(signed.take(TAG_SEQUENCE)?);
+        encap.take(TAG_OID)?;
+        let content = encap.take(TAG_CONTEXT_0)?;
+        let content = Der(content).take(TAG_OCTET_STRING)?;
+        // Manifest
+        let mut manifest = Der(Der(content).take(TAG_SEQUENCE)?);
+        if manifest.peek() == Some(TAG_CONTEXT_0) {
+            manifest.take(TAG_CONTEXT_0)?;
+        }
+        let number = manifest.take(TAG_INTEGER)?;
+        match number.first() {
+            None => None,
+            Some(&first) if first & 0x80 != 0 => None,
+            Some(_) => {
+                let start = number.iter().position(|octet| *octet != 0)
+                    .unwrap_or(number.len());
+                Some(ManifestNumber(number[start..].into()))
+            }
+        }
+    }
+}
+
+
+//--- From
+
+impl From<u64> for ManifestNumber {
+    fn from(mut value: u64) -> Self {
+        let mut octets = Vec::new();
+        while value != 0 {
+            octets.push(value as u8);
+            value >>= 8;
+        }
+        octets.reverse();
+        ManifestNumber(octets.into_boxed_slice())
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for ManifestNumber {
+    /// Formats the number in decimal.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Divide the big-endian octets by ten until nothing is left,
+        // collecting the remainders as the digits from the right.
+        let mut octets = self.0.to_vec();
+        let mut digits = Vec::new();
+        while !octets.is_empty() {
+            let mut rem = 0u16;
+            for octet in &mut octets {
+                let cur = rem << 8 | u16::from(*octet);
+                *octet = (cur / 10) as u8;
+                rem = cur % 10;
+            }
+            digits.push(b'0' + rem as u8);
+            let start = octets.iter().position(|octet| *octet != 0)
+                .unwrap_or(octets.len());
+            octets.drain(..start);
+        }
+        if digits.is_empty() {
+            digits.push(b'0')
+        }
+        digits.reverse();
+        // We only pushed ASCII digits.
+        f.write_str(::std::str::from_utf8(&digits).unwrap())
+    }
+}
+
+
+//------------ Der -----------------------------------------------------------
+
+/// A minimal reader for DER encoded data.
+///
+/// It only supports single octet tags and definite lengths which is all
+/// that is needed to find the manifest number.
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    /// Returns the tag of the next value if there is one.
+    fn peek(&self) -> Option<u8> {
+        self.0.first().cloned()
+    }
+
+    /// Takes a value with the given tag and returns its content.
+    fn take(&mut self, tag: u8) -> Option<&'a [u8]> {
+        if self.peek()? != tag {
+            return None
+        }
+        let (&len, mut rest) = self.0[1..].split_first()?;
+        let len = if len < 0x80 {
+            usize::from(len)
+        }
+        else {
+            let count = usize::from(len & 0x7f);
+            if count == 0 || count > mem::size_of::<usize>()
+                || rest.len() < count
+            {
+                return None
+            }
+            let (octets, tail) = rest.split_at(count);
+            rest = tail;
+            octets.iter().fold(0, |res, octet| res << 8 | usize::from(*octet))
+        };
+        if rest.len() < len {
+            return None
+        }
+        let (res, tail) = rest.split_at(len);
+        self.0 = tail;
+        Some(res)
+    }
+}
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xA0;
+
+
+//------------ CaChain -------------------------------------------------------
+
+/// An iterator over a chain of CAs from the bottom up.
+#[derive(Clone, Debug)]
+pub struct CaChain<'a> {
+    next: Option<&'a CaInfo>,
+}
+
+impl<'a> Iterator for CaChain<'a> {
+    type Item = &'a CaInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = self.next?;
+        self.next = res.parent();
+        Some(res)
+    }
+}
+
+
+//------------ Explanation ---------------------------------------------------
+
+/// The provenance of all route origins for a prefix.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// The prefix to explain.
+    prefix: AddressPrefix,
+
+    /// The AS number to explain if given.
+    as_id: Option<AsId>,
+
+    /// The matching route origins.
+    origins: Vec<AddressOrigin>,
+}
+
+impl Explanation {
+    /// Collects the route origins for `prefix` and, if given, `as_id`.
+    pub fn new(
+        origins: &AddressOrigins,
+        prefix: AddressPrefix,
+        as_id: Option<AsId>
+    ) -> Self {
+        Explanation {
+            prefix, as_id,
+            origins: origins.matching(prefix, as_id)
+        }
+    }
+
+    /// Returns the matching route origins.
+    pub fn origins(&self) -> &[AddressOrigin] {
+        &self.origins
+    }
+
+    /// Writes a textual explanation.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        if self.origins.is_empty() {
+            write!(
+                target, "No route origins for {}/{}",
+                self.prefix.address(), self.prefix.address_length()
+            )?;
+            match self.as_id {
+                Some(as_id) => writeln!(target, " and {}.", as_id)?,
+                None => writeln!(target, ".")?,
+            }
+            return Ok(())
+        }
+        for origin in &self.origins {
+            writeln!(
+                target, "{} {}/{}-{}",
+                origin.as_id(), origin.address(), origin.address_length(),
+                origin.max_length()
+            )?;
+            if origin.sources().is_empty() {
+                writeln!(target, "  source unknown")?;
+            }
+            for source in origin.sources() {
+                writeln!(target, "  trust anchor:   {}", source.tal_name())?;
+                match source.provenance() {
+                    Some(provenance) => {
+                        provenance.write_text("  ", target)?
+                    }
+                    None => {
+                        match source.uri() {
+                            Some(uri) => {
+                                writeln!(target, "  ROA:            {}", uri)?
+                            }
+                            None => {
+                                writeln!(target, "  local exception")?
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a JSON representation of the explanation.
+    ///
+    /// Sources without a known provenance only contain the trust anchor
+    /// and, if known, the ROA URI.
+    pub fn to_json(&self) -> JsonValue {
+        let mut origins = JsonValue::new_array();
+        for origin in &self.origins {
+            let mut item = JsonValue::new_object();
+            item["asn"] = format!("{}", origin.as_id()).into();
+            item["prefix"] = format!(
+                "{}/{}", origin.address(), origin.address_length()
+            ).into();
+            item["maxLength"] = origin.max_length().into();
+            let mut sources = JsonValue::new_array();
+            for source in origin.sources() {
+                let mut value = match source.provenance() {
+                    Some(provenance) => provenance.to_json(),
+                    None => {
+                        let mut value = JsonValue::new_object();
+                        value["roa"] = source.uri().into();
+                        value
+                    }
+                };
+                value["ta"] = source.tal().map(|tal| tal.name()).into();
+                let _ = sources.push(value);
+            }
+            item["sources"] = sources;
+            let _ = origins.push(item);
+        }
+        let mut res = JsonValue::new_object();
+        res["roas"] = origins;
+        res
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    pub fn provenance(uri: &str) -> Provenance {
+        let ta = Arc::new(
+            CaInfo::new("rsync://ta.example/ta.cer".into(), None)
+        );
+        let ca = Arc::new(
+            CaInfo::new("rsync://ta.example/repo/ca.cer".into(), Some(ta))
+            .with_manifest(
+                "rsync://ca.example/repo/ca.mft".into(),
+                Some(ManifestNumber::from(42)),
+                Utc.ymd(2026, 10, 1).and_hms(0, 0, 0),
+                Utc.ymd(2026, 10, 2).and_hms(0, 0, 0),
+            )
+        );
+        Provenance::new(
+            uri.into(),
+            Utc.ymd(2026, 1, 1).and_hms(0, 0, 0),
+            Utc.ymd(2027, 1, 1).and_hms(0, 0, 0),
+            ca
+        )
+    }
+
+    #[test]
+    fn should_explain_origins() {
+        use rpki::tal::TalInfo;
+        use super::super::origins::OriginSource;
+
+        let tal = Arc::new(TalInfo::from_name("tal".into()));
+        let origins: AddressOrigins = vec![
+            AddressOrigin::with_sources(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 24,
+                vec![
+                    OriginSource::with_provenance(
+                        Some(tal.clone()),
+                        Arc::new(provenance("rsync://ca.example/repo/a.roa"))
+                    ),
+                    OriginSource::new(None, None),
+                ]
+            ),
+            AddressOrigin::new(
+                AsId::from(64497), "10.0.0.0/16".parse().unwrap(), 16
+            ),
+        ].into_iter().collect();
+
+        let explanation = Explanation::new(
+            &origins, "10.0.0.0/16".parse().unwrap(), Some(AsId::from(64496))
+        );
+        assert_eq!(explanation.origins().len(), 1);
+        let json = explanation.to_json();
+        assert_eq!(json["roas"][0]["maxLength"], 24);
+        assert_eq!(json["roas"][0]["sources"][0]["ta"], "tal");
+        assert_eq!(json["roas"][0]["sources"][0]["chain"].len(), 2);
+        assert!(json["roas"][0]["sources"][1]["roa"].is_null());
+        let mut text = Vec::new();
+        explanation.write_text(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("AS64496 10.0.0.0/16-24\n"));
+        assert!(text.contains("  local exception\n"));
+
+        let explanation = Explanation::new(
+            &origins, "10.0.0.0/24".parse().unwrap(), None
+        );
+        let mut text = Vec::new();
+        explanation.write_text(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "No route origins for 10.0.0.0/24.\n"
+        );
+    }
+
+    #[test]
+    fn should_walk_chain() {
+        let prov = provenance("rsync://ca.example/repo/a.roa");
+        let chain: Vec<_> = prov.chain().map(CaInfo::uri).collect();
+        assert_eq!(
+            chain,
+            vec![
+                "rsync://ta.example/repo/ca.cer", "rsync://ta.example/ta.cer"
+            ]
+        );
+        assert!(!prov.ca().is_trust_anchor());
+        assert!(prov.ca().parent().unwrap().is_trust_anchor());
+    }
+
+    #[test]
+    fn should_describe_provenance() {
+        let prov = provenance("rsync://ca.example/repo/a.roa");
+        let json = prov.to_json();
+        assert_eq!(json["roa"], "rsync://ca.example/repo/a.roa");
+        assert_eq!(json["eeNotAfter"], "2027-01-01T00:00:00Z");
+        assert_eq!(json["chain"].len(), 2);
+        assert_eq!(json["chain"][0]["manifest"],
+                   "rsync://ca.example/repo/ca.mft");
+        assert!(json["chain"][1]["manifest"].is_null());
+        assert_eq!(json["chain"][0]["manifestNumber"], "42");
+
+        let mut text = Vec::new();
+        prov.write_text("  ", &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(
+            text.contains("  ROA:            rsync://ca.example/repo/a.roa")
+        );
+        assert!(text.contains("next update:  2026-10-02T00:00:00Z"));
+        assert!(text.contains("  number:       42\n"));
+        assert!(text.contains("TA cert:        rsync://ta.example/ta.cer"));
+    }
+
+    /// Wraps `content` into a DER value with the given tag.
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut res = vec![tag];
+        if content.len() < 0x80 {
+            res.push(content.len() as u8)
+        }
+        else {
+            res.extend_from_slice(&[
+                0x82, (content.len() >> 8) as u8, content.len() as u8
+            ])
+        }
+        res.extend_from_slice(content);
+        res
+    }
+
+    /// Creates an encoded manifest with the given manifest number.
+    fn manifest(version: bool, number: &[u8]) -> Vec<u8> {
+        let mut content = Vec::new();
+        if version {
+            content.extend(der(TAG_CONTEXT_0, &der(TAG_INTEGER, &[0])));
+        }
+        content.extend(der(TAG_INTEGER, number));
+        // thisUpdate, nextUpdate, fileHashAlg, fileList
+        content.extend(der(0x18, b"20261001000000Z"));
+        content.extend(der(0x18, b"20261002000000Z"));
+        content.extend(der(TAG_OID, &[0x60, 0x86, 0x48, 1, 101, 3, 4, 2, 1]));
+        content.extend(der(TAG_SEQUENCE, &[0; 200]));
+        let content = der(TAG_SEQUENCE, &content);
+
+        let mut encap = der(TAG_OID, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d]);
+        encap.extend(der(
+            TAG_CONTEXT_0, &der(TAG_OCTET_STRING, &content)
+        ));
+        let mut signed = der(TAG_INTEGER, &[3]);
+        signed.extend(der(TAG_SET, &der(TAG_SEQUENCE, &[])));
+        signed.extend(der(TAG_SEQUENCE, &encap));
+        signed.extend(der(TAG_SET, &[0; 10]));
+
+        let mut info = der(TAG_OID, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d]);
+        info.extend(der(TAG_CONTEXT_0, &der(TAG_SEQUENCE, &signed)));
+        der(TAG_SEQUENCE, &info)
+    }
+
+    #[test]
+    fn should_find_manifest_number() {
+        assert_eq!(
+            ManifestNumber::from_manifest(&manifest(false, &[0x01, 0x00])),
+            Some(ManifestNumber::from(256))
+        );
+        assert_eq!(
+            ManifestNumber::from_manifest(&manifest(true, &[0x00, 0x80])),
+            Some(ManifestNumber::from(128))
+        );
+        assert_eq!(
+            ManifestNumber::from_manifest(&manifest(true, &[0x80])), None
+        );
+        let data = manifest(false, &[7]);
+        assert_eq!(
+            ManifestNumber::from_manifest(&data[..data.len() - 1]), None
+        );
+    }
+
+    #[test]
+    fn should_display_manifest_numbers() {
+        assert_eq!(ManifestNumber::from(0).to_string(), "0");
+        assert_eq!(ManifestNumber::from(4711).to_string(), "4711");
+        assert_eq!(
+            ManifestNumber::from(::std::u64::MAX).to_string(),
+            "18446744073709551615"
+        );
+        assert_eq!(
+            ManifestNumber(vec![0xff; 20].into_boxed_slice()).to_string(),
+            "1461501637330902918203684832716283019655932542975"
+        );
+    }
+}
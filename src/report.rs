@@ -0,0 +1,277 @@
+//! Reporting on problems found during validation.
+//!
+//! While processing the repository, [`Repository`] collects information
+//! about noteworthy findings that don’t necessarily show up in the list of
+//! route origins. These are kept in a [`ValidationReport`] which can be
+//! printed in a human readable form or as JSON.
+//!
+//! [`Repository`]: ../repository/struct.Repository.html
+//! [`ValidationReport`]: struct.ValidationReport.html
+
+use std::{fmt, io};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use json::JsonValue;
+use super::expiry::{Expiry, RoaExpiry};
+use super::resources::ResourceSet;
+
+
+//------------ ValidationReport ----------------------------------------------
+
+/// The findings of a validation run.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    /// Objects that claimed resources their issuer doesn’t hold.
+    overclaims: Vec<Overclaim>,
+
+    /// The expiry times of all validated objects.
+    objects: Vec<Expiry>,
+
+    /// The expiry of all valid ROAs.
+    roas: Vec<RoaExpiry>,
+
+    /// Statistics for each processed trust anchor.
+    tals: Vec<TalStats>,
+}
+
+impl ValidationReport {
+    /// Creates a new, empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an overclaim to the report.
+    pub fn push_overclaim(&mut self, overclaim: Overclaim) {
+        self.overclaims.push(overclaim)
+    }
+
+    /// Adds the expiry time of a validated object to the report.
+    pub fn push_object(&mut self, expiry: Expiry) {
+        self.objects.push(expiry)
+    }
+
+    /// Adds the expiry of a valid ROA to the report.
+    pub fn push_roa(&mut self, roa: RoaExpiry) {
+        self.roas.push(roa)
+    }
+
+    /// Adds the statistics of a processed trust anchor to the report.
+    pub fn push_tal_stats(&mut self, stats: TalStats) {
+        self.tals.push(stats)
+    }
+
+    /// Appends the content of another report to this report.
+    pub fn merge(&mut self, mut other: ValidationReport) {
+        self.overclaims.append(&mut other.overclaims);
+        self.objects.append(&mut other.objects);
+        self.roas.append(&mut other.roas);
+        self.tals.append(&mut other.tals);
+    }
+
+    /// Returns the overclaims found.
+    pub fn overclaims(&self) -> &[Overclaim] {
+        &self.overclaims
+    }
+
+    /// Returns the expiry times of all validated objects.
+    ///
+    /// These are only collected if the repository was created with the
+    /// `expiry` option set.
+    pub fn objects(&self) -> &[Expiry] {
+        &self.objects
+    }
+
+    /// Returns the expiry of all valid ROAs.
+    ///
+    /// Like `objects`, these are only collected with the `expiry` option.
+    pub fn roas(&self) -> &[RoaExpiry] {
+        &self.roas
+    }
+
+    /// Returns the statistics of all processed trust anchors.
+    pub fn tal_stats(&self) -> &[TalStats] {
+        &self.tals
+    }
+
+    /// Writes the overclaims in human readable form.
+    pub fn write_overclaims<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        if self.overclaims.is_empty() {
+            return writeln!(target, "No overclaiming objects found.")
+        }
+        for item in &self.overclaims {
+            writeln!(target, "{} ({})", item.uri, item.tal)?;
+            writeln!(target, "    issuer:      {}", item.issuer)?;
+            writeln!(target, "    overclaimed: {}", item.resources)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the overclaims as a JSON value.
+    pub fn overclaims_json(&self) -> JsonValue {
+        let mut list = JsonValue::new_array();
+        for item in &self.overclaims {
+            let mut asns = JsonValue::new_array();
+            for (min, max) in item.resources.asns() {
+                let _ = asns.push(if min == max {
+                    format!("AS{}", min)
+                }
+                else {
+                    format!("AS{}-AS{}", min, max)
+                });
+            }
+            let mut prefixes = JsonValue::new_array();
+            for prefix in item.resources.prefixes() {
+                let _ = prefixes.push(format!(
+                    "{}/{}", prefix.address(), prefix.address_length()
+                ));
+            }
+            let mut value = JsonValue::new_object();
+            value["uri"] = item.uri.clone().into();
+            value["ta"] = item.tal.clone().into();
+            value["issuer"] = item.issuer.clone().into();
+            value["asns"] = asns;
+            value["prefixes"] = prefixes;
+            let _ = list.push(value);
+        }
+        let mut res = JsonValue::new_object();
+        res["overclaims"] = list;
+        res
+    }
+}
+
+
+//------------ TalStats ------------------------------------------------------
+
+/// Statistics about processing a single trust anchor.
+#[derive(Clone, Debug, Default)]
+pub struct TalStats {
+    /// The name of the TAL.
+    pub tal: String,
+
+    /// The number of valid ROAs.
+    pub roas: usize,
+
+    /// The number of valid CA certificates including the trust anchor.
+    pub ca_certs: usize,
+
+    /// The number of publication points with a valid manifest.
+    pub publication_points: usize,
+
+    /// The number of rejected objects by the reason for their rejection.
+    pub rejected: BTreeMap<RejectReason, usize>,
+
+    /// The time it took to process the trust anchor.
+    pub duration: Duration,
+}
+
+impl TalStats {
+    /// Creates new, empty statistics for the TAL with the given name.
+    pub fn new(tal: String) -> Self {
+        TalStats { tal, .. Default::default() }
+    }
+
+    /// Counts a rejected object.
+    pub fn reject(&mut self, reason: RejectReason) {
+        *self.rejected.entry(reason).or_insert(0) += 1
+    }
+
+    /// Returns the number of objects rejected for the given reason.
+    pub fn rejected(&self, reason: RejectReason) -> usize {
+        self.rejected.get(&reason).cloned().unwrap_or(0)
+    }
+
+    /// Returns the total number of rejected objects.
+    pub fn rejected_total(&self) -> usize {
+        self.rejected.values().sum()
+    }
+
+    /// Adds the numbers of another set of statistics to these.
+    ///
+    /// The name of the TAL is kept.
+    pub fn add(&mut self, other: &TalStats) {
+        self.roas += other.roas;
+        self.ca_certs += other.ca_certs;
+        self.publication_points += other.publication_points;
+        for (reason, count) in &other.rejected {
+            *self.rejected.entry(*reason).or_insert(0) += *count
+        }
+        self.duration += other.duration;
+    }
+}
+
+
+//------------ RejectReason --------------------------------------------------
+
+/// The reason an object was rejected during validation.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RejectReason {
+    /// The object could not be loaded from the local copy.
+    Unavailable,
+
+    /// The object’s content doesn’t match the hash given in the manifest.
+    HashMismatch,
+
+    /// The object could not be decoded.
+    Malformed,
+
+    /// The object failed validation.
+    Invalid,
+
+    /// The object’s certificate has been revoked.
+    Revoked,
+
+    /// The object claims resources not held by its issuer.
+    Overclaim,
+}
+
+impl RejectReason {
+    /// Returns a slice with all reasons.
+    pub fn all() -> &'static [RejectReason] {
+        &[
+            RejectReason::Unavailable, RejectReason::HashMismatch,
+            RejectReason::Malformed, RejectReason::Invalid,
+            RejectReason::Revoked, RejectReason::Overclaim,
+        ]
+    }
+
+    /// Returns a short name of the reason for use in machine readable
+    /// output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectReason::Unavailable => "unavailable",
+            RejectReason::HashMismatch => "hash-mismatch",
+            RejectReason::Malformed => "malformed",
+            RejectReason::Invalid => "invalid",
+            RejectReason::Revoked => "revoked",
+            RejectReason::Overclaim => "overclaim",
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ Overclaim -----------------------------------------------------
+
+/// An object that claims resources not held by its issuer.
+#[derive(Clone, Debug)]
+pub struct Overclaim {
+    /// The name of the TAL the object was found under.
+    pub tal: String,
+
+    /// The URI of the offending object.
+    pub uri: String,
+
+    /// The URI of the certificate of the issuing CA.
+    pub issuer: String,
+
+    /// The resources claimed by the object but not held by the issuer.
+    pub resources: ResourceSet,
+}
@@ -12,7 +12,9 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::{ExitStatus, Output};
 use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use futures::future;
 use futures::{Future, IntoFuture};
 use futures_cpupool::CpuPool;
@@ -21,10 +23,14 @@ use rpki::cert::{Cert, ResourceCert};
 use rpki::crl::{Crl, CrlStore};
 use rpki::manifest::{Manifest, ManifestContent, ManifestHash};
 use rpki::roa::Roa;
-use rpki::tal::Tal;
 use rpki::x509::ValidationError;
 use tokio_process::CommandExt;
-use super::origins::RouteOrigins;
+use super::expiry::{Expiry, ObjectKind, RoaExpiry};
+use super::origins::{AddressOrigin, AddressPrefix, RouteOrigins};
+use super::provenance::{CaInfo, ManifestNumber, Provenance};
+use super::report::{Overclaim, RejectReason, TalStats, ValidationReport};
+use super::resources::ResourceSet;
+use super::tals::{TalDir, TalEntry, TalError};
 
 
 //------------ Repository ----------------------------------------------------
@@ -47,11 +53,20 @@ struct RepoInner {
     cache_dir: PathBuf, 
 
     /// The directory the TALs live in.
-    tal_dir: PathBuf,
+    tals: TalDir,
 
     /// Should we be strict when decoding data?
     strict: bool,
 
+    /// Should we continue without broken TALs?
+    skip_broken_tals: bool,
+
+    /// Should we collect the expiry times of all objects?
+    expiry: bool,
+
+    /// Should we record the provenance of route origins?
+    provenance: bool,
+
     /// Number of threads.
     threads: usize,
 
@@ -63,11 +78,28 @@ struct RepoInner {
 
 impl Repository {
     /// Creates a new repository.
+    ///
+    /// The repository uses the default options except for `strict`. If
+    /// `rsync` is `false`, the repository will never be updated.
     pub fn new(
         cache_dir: PathBuf,
         tal_dir: PathBuf,
         strict: bool,
         rsync: bool
+    ) -> Result<Self, ProcessingError> {
+        Self::with_options(
+            cache_dir, tal_dir,
+            RepositoryOptions { strict, .. Default::default() },
+            rsync
+        )
+    }
+
+    /// Creates a new repository with the given options.
+    pub fn with_options(
+        cache_dir: PathBuf,
+        tal_dir: PathBuf,
+        options: RepositoryOptions,
+        rsync: bool
     ) -> Result<Self, ProcessingError> {
         if let Err(err) = fs::read_dir(&cache_dir) {
             return Err(ProcessingError::BadCacheDirectory(
@@ -84,12 +116,23 @@ impl Repository {
 
         // Let’s quickly go over the TALs to break as early as possible if
         // they aren’t good.
-        for _ in Tal::read_dir(&tal_dir)? { }
+        let tals = TalDir::new(tal_dir);
+        let check = tals.check()?;
+        check.log(options.skip_broken_tals);
+        if check.has_fatal() && !options.skip_broken_tals {
+            return Err(ProcessingError::BrokenTals)
+        }
+        if check.usable().is_empty() {
+            warn!("No usable TALs in {}.", tals.path().display());
+        }
 
         Ok(Repository(Arc::new(RepoInner {
             cache_dir,
-            tal_dir,
-            strict,
+            tals,
+            strict: options.strict,
+            skip_broken_tals: options.skip_broken_tals,
+            expiry: options.expiry,
+            provenance: options.provenance,
             threads: ::num_cpus::get(),
             rsync: if rsync {
                 Some((
@@ -134,33 +177,65 @@ impl Repository {
 
     /// Process the local copy and produce a list of validated route origins.
     ///
+    /// Alongside the route origins, the method returns a report of the
+    /// problems found during validation.
+    ///
     /// Note that the method may also do some rsync if it encounters new
     /// modules it hasn’t seen before. This means that if you start out on a
     /// new copy, it will go out and fetch everything it needs.
-    pub fn process(&self) -> Result<RouteOrigins, ProcessingError> {
+    pub fn process(
+        &self
+    ) -> Result<(RouteOrigins, ValidationReport), ProcessingError> {
         self.process_async().wait()
     }
 
     pub fn process_async(
         &self
-    ) -> impl Future<Item=RouteOrigins, Error=ProcessingError> {
+    ) -> impl Future<
+        Item=(RouteOrigins, ValidationReport), Error=ProcessingError
+    > {
         let pool = CpuPool::new(self.0.threads);
         let repo = self.clone();
-        fs::read_dir(&self.0.tal_dir).map_err(Into::into).into_future()
-        .and_then(|dir| {
-            future::join_all(dir.map(move |entry| {
+        self.usable_tals().into_future()
+        .and_then(|tals| {
+            future::join_all(tals.into_iter().map(move |entry| {
                 let repo = repo.clone();
                 pool.spawn(future::lazy(|| repo.process_tal(entry)))
             })).and_then(|x| {
                 let mut res = RouteOrigins::new();
-                x.into_iter().for_each(|item| res.merge(item));
-                Ok(res)
+                let mut report = ValidationReport::new();
+                x.into_iter().for_each(|(routes, item)| {
+                    res.merge(routes);
+                    report.merge(item);
+                });
+                Ok((res, report))
             })
         })
     }
 }
 
 
+/// # Trust Anchors
+///
+impl Repository {
+    /// Returns the TALs to be used for validation.
+    ///
+    /// Since the content of the TAL directory may change while we are
+    /// running, the directory is re-checked every time. Problems are only
+    /// logged if they cause TALs to be left out.
+    fn usable_tals(&self) -> Result<Vec<TalEntry>, ProcessingError> {
+        let check = self.0.tals.check()?;
+        if check.has_fatal() {
+            check.log(self.0.skip_broken_tals);
+            if !self.0.skip_broken_tals {
+                return Err(ProcessingError::BrokenTals)
+            }
+        }
+        Ok(check.usable().to_vec())
+    }
+}
+
+
 /// # Repository Access
 ///
 impl Repository {
@@ -281,90 +356,115 @@ impl Repository {
     /// Processes all data for the given trust anchor.
     pub fn process_tal(
         self,
-        entry: Result<DirEntry, io::Error>
-    ) -> Result<RouteOrigins, ProcessingError> {
-        let entry = entry?;
-        let path = entry.path();
-        let mut res = RouteOrigins::new();
-        if !entry.file_type()?.is_file() {
-            warn!("{}: garbage in TAL directory.", path.display());
-            return Ok(res)
-        }
-        let mut file = match File::open(&path) {
-            Ok(file) => {
-                debug!("Processing TAL {}", path.display());
-                file
-            }
-            Err(err) => {
-                error!("{}: {}. Aborting.", path.display(), err);
-                return Err(err.into())
-            }
-        };
-        let tal = match Tal::read(&path, &mut file) {
+        entry: TalEntry,
+    ) -> Result<(RouteOrigins, ValidationReport), ProcessingError> {
+        let start = Instant::now();
+        let mut run = TalRun::new(entry.name(), self.0.expiry);
+        debug!("Processing TAL {}", entry.path().display());
+        let tal = match entry.read() {
             Ok(tal) => tal,
             Err(err) => {
-                error!("{}: {}. Aborting.", path.display(), err);
-                return Err(ProcessingError::Other)
+                error!("{}. Aborting.", err);
+                return Err(err.into())
             }
         };
         for uri in tal.uris() {
-                let cert = match self.load_ta(&uri) {
-                    Ok(Some(cert)) => cert,
-                    _ => continue,
-                };
-                if cert.subject_public_key_info() != tal.key_info() {
+            let cert = match self.load_ta(&uri) {
+                Ok(Some(cert)) => cert,
+                _ => {
+                    run.stats.reject(RejectReason::Unavailable);
+                    continue
+                }
+            };
+            if cert.subject_public_key_info() != tal.key_info() {
+                run.stats.reject(RejectReason::Invalid);
+                continue;
+            }
+            let resources = ResourceSet::from_cert(&cert, None);
+            let not_after = cert_expires(&cert);
+            let cert = match cert.validate_ta(tal.info().clone(),
+                                              self.0.strict) {
+                Ok(cert) => cert,
+                Err(_) => {
+                    run.stats.reject(RejectReason::Invalid);
                     continue;
                 }
-                let cert = match cert.validate_ta(tal.info().clone(),
-                                                  self.0.strict) {
-                    Ok(cert) => cert,
-                    Err(_) => {
-                        continue;
-                    }
-                };
-                debug!("processing {}", uri);
-                let _ = self.process_ca(cert, &mut res);
-                // We stop once we have had the first working URI.
-                break;
+            };
+            debug!("processing {}", uri);
+            run.stats.ca_certs += 1;
+            let expires = run.ca_expiry(uri, not_after, None);
+            let _ = self.process_ca(
+                CaCert {
+                    cert, uri: uri.clone(), resources, expires,
+                    parent: None
+                },
+                &mut run
+            );
+            // We stop once we have had the first working URI.
+            break;
         }
-        Ok(res)
+        let TalRun { routes, mut report, mut stats, .. } = run;
+        stats.duration = start.elapsed();
+        report.push_tal_stats(stats);
+        Ok((routes, report))
     }
 
     /// Processes all data for the given trust CA.
     fn process_ca(
         &self,
-        cert: ResourceCert,
-        routes: &mut RouteOrigins
+        cert: CaCert,
+        run: &mut TalRun
     ) -> Result<(), ProcessingError> {
         let mut store = CrlStore::new();
 
-        let repo_uri = match cert.repository_uri() {
+        let repo_uri = match cert.cert.repository_uri() {
             Some(uri) => uri,
             None => return Ok(())
         };
-        let manifest = match self.get_manifest(&cert, &mut store)? {
+        let (manifest_uri, number, manifest) = match self.get_manifest(
+            &cert.cert, &mut store, run
+        )? {
             Some(manifest) => manifest,
             None => return Ok(())
         };
+        run.stats.publication_points += 1;
+        let info = if self.0.provenance {
+            Some(Arc::new(
+                CaInfo::new(cert.uri.to_string(), cert.parent.clone())
+                .with_manifest(
+                    manifest_uri.to_string(), number,
+                    *manifest.this_update(), *manifest.next_update()
+                )
+            ))
+        }
+        else {
+            None
+        };
 
         for item in manifest.iter_uris(repo_uri) {
             let (uri, hash) = match item {
                 Ok(item) => item,
                 Err(_) => continue,
             };
-            self.process_object(uri, hash, &cert, &mut store, routes)?;
+            self.process_object(
+                uri, hash, &cert, info.as_ref(), &mut store, run
+            )?;
         }
         Ok(())
     }
 
     /// Processes all an object.
+    ///
+    /// The object has been published by the CA `issuer` with `info` being
+    /// the provenance information for this CA if it is recorded.
     fn process_object(
         &self,
         uri: uri::Rsync,
         hash: ManifestHash,
-        issuer: &ResourceCert,
+        issuer: &CaCert,
+        info: Option<&Arc<CaInfo>>,
         crl: &mut CrlStore,
-        routes: &mut RouteOrigins,
+        run: &mut TalRun,
     ) -> Result<(), ProcessingError> {
         // XXX We should have the directory already from the fetching the
         //     manifest. So we should be fine calling load_file without
@@ -374,53 +474,117 @@ impl Repository {
                 Some(bytes) => bytes,
                 None => {
                     info!("{}: failed to load.", uri);
+                    run.stats.reject(RejectReason::Unavailable);
                     return Ok(())
                 }
             };
             if let Err(_) = hash.verify(&bytes) {
                 info!("{}: file has wrong hash.", uri);
+                run.stats.reject(RejectReason::HashMismatch);
                 return Ok(())
             }
             let cert = match Cert::decode(bytes) {
                 Ok(cert) => cert,
                 Err(_) => {
                     info!("{}: failed to decode.", uri);
+                    run.stats.reject(RejectReason::Malformed);
                     return Ok(())
                 }
             };
-            let cert = match cert.validate_ca(issuer, self.0.strict) {
-                Ok(cert) => cert,
-                Err(_) => {
-                    info!("{}: failed to validate.", uri);
-                    return Ok(())
-                }
+            let cert = match self.validate_ca(cert, uri, issuer, info, run) {
+                Some(cert) => cert,
+                None => return Ok(())
             };
-            if let Err(_) = self.check_crl(&cert, issuer, crl) {
-                info!("{}: certificate has been revoked", uri);
+            if let Err(_) = self.check_crl(
+                &cert.cert, &issuer.cert, crl, run
+            ) {
+                info!("{}: certificate has been revoked", cert.uri);
+                run.stats.reject(RejectReason::Revoked);
                 return Ok(())
             }
-            self.process_ca(cert, routes)
+            run.stats.ca_certs += 1;
+            self.process_ca(cert, run)
         }
         else if uri.ends_with(".roa") {
             let bytes = match self.load_file(&uri, false)? {
                 Some(bytes) => bytes,
-                None => return Ok(())
+                None => {
+                    run.stats.reject(RejectReason::Unavailable);
+                    return Ok(())
+                }
             };
             if let Err(_) = hash.verify(&bytes) {
+                run.stats.reject(RejectReason::HashMismatch);
                 return Ok(())
             }
             let roa = match Roa::decode(bytes, self.0.strict) {
                 Ok(roa) => roa,
                 Err(_) => {
                     info!("Decoding failed for {}", uri);
+                    run.stats.reject(RejectReason::Malformed);
                     return Ok(())
                 }
             };
-            let route = roa.process(issuer, self.0.strict, |cert| {
-                self.check_crl(cert, issuer, crl)
+            let mut ee_validity = None;
+            let route = roa.process(&issuer.cert, self.0.strict, |cert| {
+                let validity = cert.validity();
+                ee_validity = Some(
+                    (*validity.not_before(), *validity.not_after())
+                );
+                self.check_crl(cert, &issuer.cert, crl, run)
             });
-            if let Ok(route) = route {
-                routes.push(route)
+            if let (Ok(route), Some((ee_not_before, ee_expires)))
+                = (route, ee_validity)
+            {
+                // Check the prefixes against the issuer’s resources so that
+                // an overclaiming ROA is reported as such.
+                let overclaimed = roa_overclaim(
+                    route.iter().map(|addr| AddressPrefix::from(&addr)),
+                    &issuer.resources
+                );
+                if overclaimed.is_empty() {
+                    if run.collect_expiry {
+                        let expires = run.expiry(
+                            ObjectKind::EeCertificate, &uri, ee_expires
+                        );
+                        let roa = RoaExpiry {
+                            uri: uri.to_string(),
+                            expiry: issuer.expires.as_ref().map_or(
+                                &expires, |chain| expires.earliest(chain)
+                            ).clone(),
+                            origins: route.iter().map(|addr| {
+                                AddressOrigin::new(
+                                    route.as_id(),
+                                    AddressPrefix::from(&addr),
+                                    addr.max_length()
+                                )
+                            }).collect()
+                        };
+                        run.report.push_object(expires);
+                        run.report.push_roa(roa);
+                    }
+                    run.stats.roas += 1;
+                    let uri: Arc<str> = uri.to_string().into();
+                    let provenance = info.map(|info| {
+                        Provenance::new(
+                            uri.clone(), ee_not_before, ee_expires,
+                            info.clone()
+                        )
+                    });
+                    run.routes.push(route, uri, provenance)
+                }
+                else {
+                    warn!(
+                        "{}: ROA overclaims resources {}. Rejected.",
+                        uri, overclaimed
+                    );
+                    run.report_overclaim(&uri, issuer, overclaimed);
+                    run.stats.reject(RejectReason::Overclaim);
+                }
+            }
+            else {
+                info!("{}: failed to validate.", uri);
+                run.stats.reject(RejectReason::Invalid);
             }
             Ok(())
         }
@@ -433,11 +597,69 @@ impl Repository {
         }
     }
 
+    /// Validates a CA certificate issued by `issuer`.
+    ///
+    /// Before handing the certificate to the actual validation, checks
+    /// whether it claims resources the issuer doesn’t hold. Such an
+    /// overclaim is reported and the certificate rejected as required by
+    /// RFC 6487.
+    ///
+    /// The provenance information of the issuer is given via `parent` if it
+    /// is recorded.
+    ///
+    /// Returns the validated certificate or `None` if it should be ignored.
+    fn validate_ca(
+        &self,
+        cert: Cert,
+        uri: uri::Rsync,
+        issuer: &CaCert,
+        parent: Option<&Arc<CaInfo>>,
+        run: &mut TalRun,
+    ) -> Option<CaCert> {
+        let resources = ResourceSet::from_cert(
+            &cert, Some(&issuer.resources)
+        );
+        let overclaimed = resources.difference(&issuer.resources);
+        if !overclaimed.is_empty() {
+            warn!(
+                "{}: overclaims resources {} of issuer {}. Rejected.",
+                uri, overclaimed, issuer.uri
+            );
+            run.report_overclaim(&uri, issuer, overclaimed);
+            run.stats.reject(RejectReason::Overclaim);
+            return None
+        }
+        let not_after = cert_expires(&cert);
+        let cert = match cert.validate_ca(&issuer.cert, self.0.strict) {
+            Ok(cert) => cert,
+            Err(_) => {
+                info!("{}: failed to validate.", uri);
+                run.stats.reject(RejectReason::Invalid);
+                return None
+            }
+        };
+        let expires = run.ca_expiry(
+            &uri, not_after, issuer.expires.as_ref()
+        );
+        Some(CaCert {
+            cert, uri, resources, expires,
+            parent: parent.cloned()
+        })
+    }
+
+    /// Returns the first valid manifest of the CA `issuer`.
+    ///
+    /// Along with the manifest’s content, returns its URI and, if
+    /// provenance is recorded, its manifest number.
     fn get_manifest(
         &self,
         issuer: &ResourceCert,
         store: &mut CrlStore,
-    ) -> Result<Option<ManifestContent>, ProcessingError> {
+        run: &mut TalRun,
+    ) -> Result<
+        Option<(uri::Rsync, Option<ManifestNumber>, ManifestContent)>,
+        ProcessingError
+    > {
         for uri in issuer.manifest_uris() {
             let uri = match uri.into_rsync_uri() {
                 Some(uri) => uri,
@@ -447,13 +669,21 @@ impl Repository {
                 Some(bytes) => bytes,
                 None => {
                     info!("{}: failed to load.", uri);
+                    run.stats.reject(RejectReason::Unavailable);
                     continue
                 }
             };
+            let number = if self.0.provenance {
+                ManifestNumber::from_manifest(bytes.as_ref())
+            }
+            else {
+                None
+            };
             let manifest = match Manifest::decode(bytes, self.0.strict) {
                 Ok(manifest) => manifest,
                 Err(_) => {
                     info!("{}: failed to decode", uri);
+                    run.stats.reject(RejectReason::Malformed);
                     continue
                 }
             };
@@ -462,14 +692,22 @@ impl Repository {
                 Ok(manifest) => manifest,
                 Err(_) => {
                     info!("{}: failed to validate", uri);
+                    run.stats.reject(RejectReason::Invalid);
                     continue
                 }
             };
-            if let Err(_) = self.check_crl(cert, issuer, store) {
+            if let Err(_) = self.check_crl(&cert, issuer, store, run) {
                 info!("{}: certificate has been revoked", uri);
+                run.stats.reject(RejectReason::Revoked);
                 continue
             }
-            return Ok(Some(manifest))
+            run.record_expiry(
+                ObjectKind::EeCertificate, &uri, cert_expires(&cert)
+            );
+            run.record_expiry(
+                ObjectKind::Manifest, &uri, *manifest.next_update()
+            );
+            return Ok(Some((uri, number, manifest)))
         }
         debug!("No valid manifests");
         Ok(None)
@@ -480,6 +718,7 @@ impl Repository {
         cert: C,
         issuer: &ResourceCert,
         store: &mut CrlStore,
+        run: &mut TalRun,
     ) -> Result<(), ValidationError> {
         let uri_list = match cert.as_ref().crl_distribution() {
             Some(some) => some,
@@ -515,6 +754,9 @@ impl Repository {
             }
 
             let revoked = crl.contains(&cert.as_ref().serial_number());
+            if let Some(next_update) = crl.next_update() {
+                run.record_expiry(ObjectKind::Crl, &uri, *next_update);
+            }
             store.push(uri, crl);
             if revoked {
                 return Err(ValidationError)
@@ -563,6 +805,155 @@ impl Repository {
 }
 
 
+//------------ RepositoryOptions ---------------------------------------------
+
+/// The options for processing the repository.
+///
+/// The default value has all options turned off.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RepositoryOptions {
+    /// Should we be strict when decoding data?
+    pub strict: bool,
+
+    /// Should we continue without broken TALs?
+    pub skip_broken_tals: bool,
+
+    /// Should we collect the expiry times of all objects?
+    ///
+    /// These are only necessary for an expiry forecast and are otherwise
+    /// left out of the validation report to save memory.
+    pub expiry: bool,
+
+    /// Should we record the provenance of route origins?
+    ///
+    /// The provenance is kept with the route origins for as long as they
+    /// are around, so it should only be recorded if it is actually needed.
+    pub provenance: bool,
+}
+
+
+//------------ CaCert --------------------------------------------------------
+
+/// A validated CA certificate and what we need to know about it.
+#[derive(Debug)]
+struct CaCert {
+    /// The certificate itself.
+    cert: ResourceCert,
+
+    /// The URI the certificate was loaded from.
+    uri: uri::Rsync,
+
+    /// The resources the CA is allowed to use.
+    ///
+    /// This is the set of resources claimed by the certificate with
+    /// inherited resources resolved.
+    resources: ResourceSet,
+
+    /// The earliest expiring certificate along the chain.
+    ///
+    /// This is the certificate itself or any of the certificates above it.
+    /// It is only kept if expiry times are collected and `None` otherwise.
+    expires: Option<Expiry>,
+
+    /// The provenance information of the issuing CA.
+    ///
+    /// This is `None` for the trust anchor certificate and if provenance
+    /// isn’t recorded.
+    parent: Option<Arc<CaInfo>>,
+}
+
+
+//------------ TalRun --------------------------------------------------------
+
+/// The result of processing the objects of a single trust anchor.
+#[derive(Debug)]
+struct TalRun {
+    /// The name of the TAL.
+    tal: String,
+
+    /// The route origins found so far.
+    routes: RouteOrigins,
+
+    /// The validation report so far.
+    report: ValidationReport,
+
+    /// The statistics so far.
+    stats: TalStats,
+
+    /// Should the expiry times of objects be added to the report?
+    collect_expiry: bool,
+}
+
+impl TalRun {
+    fn new(tal: &str, collect_expiry: bool) -> Self {
+        TalRun {
+            tal: tal.into(),
+            routes: RouteOrigins::new(),
+            report: ValidationReport::new(),
+            stats: TalStats::new(tal.into()),
+            collect_expiry,
+        }
+    }
+
+    fn expiry(
+        &self,
+        kind: ObjectKind,
+        uri: &uri::Rsync,
+        time: DateTime<Utc>
+    ) -> Expiry {
+        Expiry::new(kind, uri.to_string(), self.tal.clone(), time)
+    }
+
+    /// Adds the expiry time of a CA certificate to the report if requested.
+    ///
+    /// Returns the earliest expiry along the chain given the chain of the
+    /// issuer or `None` if expiry times aren’t collected.
+    fn ca_expiry(
+        &mut self,
+        uri: &uri::Rsync,
+        time: DateTime<Utc>,
+        issuer: Option<&Expiry>,
+    ) -> Option<Expiry> {
+        if !self.collect_expiry {
+            return None
+        }
+        let expires = self.expiry(ObjectKind::CaCertificate, uri, time);
+        let chain = issuer.map_or(
+            &expires, |chain| expires.earliest(chain)
+        ).clone();
+        self.report.push_object(expires);
+        Some(chain)
+    }
+
+    /// Adds the expiry time of an object to the report if requested.
+    fn record_expiry(
+        &mut self,
+        kind: ObjectKind,
+        uri: &uri::Rsync,
+        time: DateTime<Utc>
+    ) {
+        if self.collect_expiry {
+            let expiry = self.expiry(kind, uri, time);
+            self.report.push_object(expiry)
+        }
+    }
+
+    fn report_overclaim(
+        &mut self,
+        uri: &uri::Rsync,
+        issuer: &CaCert,
+        resources: ResourceSet,
+    ) {
+        self.report.push_overclaim(Overclaim {
+            tal: self.tal.clone(),
+            uri: uri.to_string(),
+            issuer: issuer.uri.to_string(),
+            resources,
+        })
+    }
+}
+
+
 //------------ RsyncState ----------------------------------------------------
 
 #[derive(Clone, Debug)]
@@ -727,6 +1118,25 @@ impl RsyncCommand {
 
 //------------ Helper Functions ----------------------------------------------
 
+/// Returns the time the certificate expires.
+fn cert_expires<C: AsRef<Cert>>(cert: C) -> DateTime<Utc> {
+    *cert.as_ref().validity().not_after()
+}
+
+/// Returns the parts of the prefixes of a ROA not covered by `resources`.
+fn roa_overclaim<I: IntoIterator<Item=AddressPrefix>>(
+    prefixes: I,
+    resources: &ResourceSet
+) -> ResourceSet {
+    let mut res = ResourceSet::new();
+    for prefix in prefixes {
+        if !resources.contains_prefix(prefix) {
+            res.insert_prefix(prefix)
+        }
+    }
+    res.difference(resources)
+}
+
 fn entry_to_uri_component(entry: &DirEntry) -> Option<Bytes> {
     let name = entry.file_name();
     name.to_str().and_then(|name| {
@@ -750,6 +1160,15 @@ pub enum ProcessingError {
     #[fail(display="failed to open trust anchor directory {}: {}", _0, _1)]
     BadTalDirectory(String, io::Error),
 
+    #[fail(display="{}", _0)]
+    Tal(TalError),
+
+    #[fail(
+        display="broken TALs found (use --skip-broken-tals to continue \
+                 without them)"
+    )]
+    BrokenTals,
+
     #[fail(display="{}", _0)]
     Rsync(RsyncError),
 
@@ -760,6 +1179,12 @@ pub enum ProcessingError {
     Other
 }
 
+impl From<TalError> for ProcessingError {
+    fn from(err: TalError) -> Self {
+        ProcessingError::Tal(err)
+    }
+}
+
 impl From<RsyncError> for ProcessingError {
     fn from(err: RsyncError) -> Self {
         ProcessingError::Rsync(err)
@@ -790,3 +1215,32 @@ impl From<io::Error> for RsyncError {
     }
 }
 
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn make_set(prefixes: &[(&str, u8)]) -> ResourceSet {
+        let mut res = ResourceSet::new();
+        for &(addr, len) in prefixes {
+            res.insert_prefix(AddressPrefix::new(addr.parse().unwrap(), len))
+        }
+        res
+    }
+
+    #[test]
+    fn should_find_overclaiming_roas() {
+        let resources = make_set(&[("10.0.0.0", 16)]);
+        let in_scope = make_set(&[("10.0.1.0", 24), ("10.0.2.0", 24)]);
+        assert!(
+            roa_overclaim(in_scope.prefixes(), &resources).is_empty()
+        );
+        let out_of_scope = make_set(&[("10.0.1.0", 24), ("10.1.1.0", 24)]);
+        assert_eq!(
+            roa_overclaim(out_of_scope.prefixes(), &resources),
+            make_set(&[("10.1.1.0", 24)])
+        );
+    }
+}
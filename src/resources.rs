@@ -0,0 +1,458 @@
+//! Sets of Internet number resources.
+//!
+//! Certificates in RPKI carry the IP address prefixes and AS numbers their
+//! holder is entitled to. Each certificate must only claim resources that
+//! its issuer holds. This module provides [`ResourceSet`], a set of such
+//! resources that allows checking for and computing overclaimed resources.
+//!
+//! [`ResourceSet`]: struct.ResourceSet.html
+
+use std::{fmt, slice};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use rpki::cert::Cert;
+use super::origins::AddressPrefix;
+
+
+//------------ ResourceSet ---------------------------------------------------
+
+/// A set of AS numbers, IPv4 addresses, and IPv6 addresses.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ResourceSet {
+    /// The AS numbers.
+    asns: RangeSet,
+
+    /// The IPv4 addresses.
+    v4: RangeSet,
+
+    /// The IPv6 addresses.
+    v6: RangeSet,
+}
+
+impl ResourceSet {
+    /// Creates a new, empty resource set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the set of resources claimed by a certificate.
+    ///
+    /// If the certificate inherits some of its resources, these are taken
+    /// from `issuer`. If there is no issuer, inherited resources are
+    /// ignored; they are not allowed in trust anchor certificates.
+    pub fn from_cert(cert: &Cert, issuer: Option<&ResourceSet>) -> Self {
+        let mut res = ResourceSet::new();
+        if let Some(resources) = cert.as_resources() {
+            if resources.is_inherited() {
+                if let Some(issuer) = issuer {
+                    res.asns = issuer.asns.clone()
+                }
+            }
+            else {
+                for block in resources.iter() {
+                    res.asns.insert(
+                        u32::from(block.min()).into(),
+                        u32::from(block.max()).into()
+                    )
+                }
+            }
+        }
+        if let Some(resources) = cert.ip_resources() {
+            for family in resources.iter() {
+                if family.is_inherited() {
+                    if let Some(issuer) = issuer {
+                        if family.is_ipv4() {
+                            res.v4 = issuer.v4.clone()
+                        }
+                        else {
+                            res.v6 = issuer.v6.clone()
+                        }
+                    }
+                    continue
+                }
+                for block in family.iter() {
+                    res.insert_addrs(block.min(), block.max())
+                }
+            }
+        }
+        res
+    }
+
+    /// Adds a range of AS numbers to the set.
+    pub fn insert_asns(&mut self, min: u32, max: u32) {
+        self.asns.insert(min.into(), max.into())
+    }
+
+    /// Adds a range of addresses to the set.
+    ///
+    /// If the two addresses are of different families, nothing happens.
+    pub fn insert_addrs(&mut self, min: IpAddr, max: IpAddr) {
+        match (min, max) {
+            (IpAddr::V4(min), IpAddr::V4(max)) => {
+                self.v4.insert(u32::from(min).into(), u32::from(max).into())
+            }
+            (IpAddr::V6(min), IpAddr::V6(max)) => {
+                self.v6.insert(min.into(), max.into())
+            }
+            _ => { }
+        }
+    }
+
+    /// Adds all addresses of a prefix to the set.
+    pub fn insert_prefix(&mut self, prefix: AddressPrefix) {
+        let (min, max) = prefix_range(prefix);
+        match prefix.address() {
+            IpAddr::V4(_) => self.v4.insert(min, max),
+            IpAddr::V6(_) => self.v6.insert(min, max),
+        }
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.asns.is_empty() && self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    /// Returns whether all addresses of the prefix are in the set.
+    pub fn contains_prefix(&self, prefix: AddressPrefix) -> bool {
+        let (min, max) = prefix_range(prefix);
+        match prefix.address() {
+            IpAddr::V4(_) => self.v4.contains(min, max),
+            IpAddr::V6(_) => self.v6.contains(min, max),
+        }
+    }
+
+    /// Returns whether all resources of `other` are also in this set.
+    pub fn encompasses(&self, other: &ResourceSet) -> bool {
+        other.difference(self).is_empty()
+    }
+
+    /// Returns the resources present in both sets.
+    pub fn intersection(&self, other: &ResourceSet) -> ResourceSet {
+        ResourceSet {
+            asns: self.asns.intersection(&other.asns),
+            v4: self.v4.intersection(&other.v4),
+            v6: self.v6.intersection(&other.v6),
+        }
+    }
+
+    /// Returns the resources of this set that are not in `other`.
+    pub fn difference(&self, other: &ResourceSet) -> ResourceSet {
+        ResourceSet {
+            asns: self.asns.difference(&other.asns),
+            v4: self.v4.difference(&other.v4),
+            v6: self.v6.difference(&other.v6),
+        }
+    }
+
+    /// Returns the AS numbers as a list of ranges.
+    pub fn asns(&self) -> Vec<(u32, u32)> {
+        self.asns.iter().map(|&(min, max)| (min as u32, max as u32)).collect()
+    }
+
+    /// Returns the addresses as a list of prefixes.
+    ///
+    /// IPv4 prefixes come before IPv6 prefixes.
+    pub fn prefixes(&self) -> Vec<AddressPrefix> {
+        let mut res = Vec::new();
+        for &(min, max) in self.v4.iter() {
+            push_prefixes(&mut res, min, max, 32, |addr| {
+                IpAddr::V4(Ipv4Addr::from(addr as u32))
+            })
+        }
+        for &(min, max) in self.v6.iter() {
+            push_prefixes(&mut res, min, max, 128, |addr| {
+                IpAddr::V6(Ipv6Addr::from(addr))
+            })
+        }
+        res
+    }
+}
+
+impl fmt::Display for ResourceSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for (min, max) in self.asns() {
+            if first {
+                first = false
+            }
+            else {
+                f.write_str(", ")?;
+            }
+            if min == max {
+                write!(f, "AS{}", min)?;
+            }
+            else {
+                write!(f, "AS{}-AS{}", min, max)?;
+            }
+        }
+        for prefix in self.prefixes() {
+            if first {
+                first = false
+            }
+            else {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}/{}", prefix.address(), prefix.address_length())?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ RangeSet ------------------------------------------------------
+
+/// A set of integers kept as a sorted list of inclusive ranges.
+///
+/// The ranges never overlap or touch each other.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct RangeSet(Vec<(u128, u128)>);
+
+impl RangeSet {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn iter(&self) -> slice::Iter<(u128, u128)> {
+        self.0.iter()
+    }
+
+    fn insert(&mut self, min: u128, max: u128) {
+        if min > max {
+            return
+        }
+        let idx = match self.0.binary_search(&(min, max)) {
+            Ok(_) => return,
+            Err(idx) => idx
+        };
+        self.0.insert(idx, (min, max));
+        self.normalize();
+    }
+
+    /// Merges overlapping and adjacent ranges.
+    fn normalize(&mut self) {
+        let mut res: Vec<(u128, u128)> = Vec::with_capacity(self.0.len());
+        for &(min, max) in &self.0 {
+            if let Some(last) = res.last_mut() {
+                if last.1 == ::std::u128::MAX || min <= last.1 + 1 {
+                    if max > last.1 {
+                        last.1 = max
+                    }
+                    continue
+                }
+            }
+            res.push((min, max))
+        }
+        self.0 = res;
+    }
+
+    fn contains(&self, min: u128, max: u128) -> bool {
+        self.0.iter().any(|&(left, right)| left <= min && max <= right)
+    }
+
+    fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut res = Vec::new();
+        let mut left = self.0.iter().peekable();
+        let mut right = other.0.iter().peekable();
+        loop {
+            let (lmin, lmax) = match left.peek() {
+                Some(&&item) => item,
+                None => break
+            };
+            let (rmin, rmax) = match right.peek() {
+                Some(&&item) => item,
+                None => break
+            };
+            let min = if lmin > rmin { lmin } else { rmin };
+            let max = if lmax < rmax { lmax } else { rmax };
+            if min <= max {
+                res.push((min, max))
+            }
+            if lmax < rmax {
+                left.next();
+            }
+            else {
+                right.next();
+            }
+        }
+        RangeSet(res)
+    }
+
+    fn difference(&self, other: &RangeSet) -> RangeSet {
+        let mut res = Vec::new();
+        for &(min, max) in &self.0 {
+            let mut min = min;
+            let mut done = false;
+            for &(omin, omax) in &other.0 {
+                if omax < min {
+                    continue
+                }
+                if omin > max {
+                    break
+                }
+                if omin > min {
+                    res.push((min, omin - 1))
+                }
+                if omax >= max {
+                    done = true;
+                    break
+                }
+                min = omax + 1;
+            }
+            if !done {
+                res.push((min, max))
+            }
+        }
+        RangeSet(res)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the first and last address of a prefix as integers.
+pub fn prefix_range(prefix: AddressPrefix) -> (u128, u128) {
+    match prefix.address() {
+        IpAddr::V4(addr) => {
+            let host = host_mask(prefix.address_length(), 32);
+            let addr = u128::from(u32::from(addr));
+            (addr & !host, addr | host)
+        }
+        IpAddr::V6(addr) => {
+            let host = host_mask(prefix.address_length(), 128);
+            let addr = u128::from(addr);
+            (addr & !host, addr | host)
+        }
+    }
+}
+
+/// Returns the mask for the host part of a prefix.
+fn host_mask(len: u8, bits: u8) -> u128 {
+    let len = if len > bits { bits } else { len };
+    let host_bits = u32::from(bits - len);
+    if host_bits == 128 {
+        ::std::u128::MAX
+    }
+    else {
+        (1u128 << host_bits) - 1
+    }
+}
+
+/// Splits a range of addresses into the smallest list of prefixes.
+fn push_prefixes<F: Fn(u128) -> IpAddr>(
+    res: &mut Vec<AddressPrefix>,
+    min: u128,
+    max: u128,
+    bits: u8,
+    addr: F
+) {
+    let mut min = min;
+    loop {
+        // Find the largest block starting at min that fits into the range.
+        let mut size = if min == 0 {
+            u32::from(bits)
+        }
+        else {
+            ::std::cmp::min(min.trailing_zeros(), u32::from(bits))
+        };
+        while size > 0 && host_mask(bits - size as u8, bits) > max - min {
+            size -= 1
+        }
+        res.push(AddressPrefix::new(addr(min), bits - size as u8));
+        let last = min | host_mask(bits - size as u8, bits);
+        if last >= max {
+            break
+        }
+        min = last + 1;
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn make_pfx(s: &str, l: u8) -> AddressPrefix {
+        AddressPrefix::new(s.parse().unwrap(), l)
+    }
+
+    fn make_set(prefixes: &[(&str, u8)], asns: &[(u32, u32)]) -> ResourceSet {
+        let mut res = ResourceSet::new();
+        for &(addr, len) in prefixes {
+            res.insert_prefix(make_pfx(addr, len))
+        }
+        for &(min, max) in asns {
+            res.insert_asns(min, max)
+        }
+        res
+    }
+
+    #[test]
+    fn should_merge_ranges() {
+        let set = make_set(
+            &[("10.0.0.0", 24), ("10.0.1.0", 24), ("10.0.0.128", 25)],
+            &[(10, 20), (21, 30), (15, 25)]
+        );
+        assert_eq!(set.prefixes(), vec![make_pfx("10.0.0.0", 23)]);
+        assert_eq!(set.asns(), vec![(10, 30)]);
+    }
+
+    #[test]
+    fn should_find_overclaim() {
+        let issuer = make_set(
+            &[("10.0.0.0", 16), ("2001:db8::", 32)], &[(64496, 64511)]
+        );
+        let child = make_set(
+            &[("10.0.0.0", 24), ("10.1.0.0", 24), ("2001:db8:1::", 48)],
+            &[(64500, 64520)]
+        );
+        let over = child.difference(&issuer);
+        assert_eq!(over.prefixes(), vec![make_pfx("10.1.0.0", 24)]);
+        assert_eq!(over.asns(), vec![(64512, 64520)]);
+        assert!(!issuer.encompasses(&child));
+
+        let trimmed = child.intersection(&issuer);
+        assert!(issuer.encompasses(&trimmed));
+        assert_eq!(
+            trimmed.prefixes(),
+            vec![make_pfx("10.0.0.0", 24), make_pfx("2001:db8:1::", 48)]
+        );
+        assert_eq!(trimmed.asns(), vec![(64500, 64511)]);
+    }
+
+    #[test]
+    fn should_split_difference() {
+        let outer = make_set(&[("10.0.0.0", 8)], &[]);
+        let inner = make_set(&[("10.128.0.0", 9)], &[]);
+        assert_eq!(
+            outer.difference(&inner).prefixes(),
+            vec![make_pfx("10.0.0.0", 9)]
+        );
+        let inner = make_set(&[("10.64.0.0", 10)], &[]);
+        assert_eq!(
+            outer.difference(&inner).prefixes(),
+            vec![make_pfx("10.0.0.0", 10), make_pfx("10.128.0.0", 9)]
+        );
+    }
+
+    #[test]
+    fn should_check_prefix_containment() {
+        let set = make_set(&[("192.0.2.0", 24), ("2001:db8::", 32)], &[]);
+        assert!(set.contains_prefix(make_pfx("192.0.2.128", 25)));
+        assert!(!set.contains_prefix(make_pfx("192.0.0.0", 16)));
+        assert!(set.contains_prefix(make_pfx("2001:db8:ff00::", 40)));
+        assert!(!set.contains_prefix(make_pfx("2001:db9::", 32)));
+    }
+
+    #[test]
+    fn should_display_resources() {
+        let set = make_set(
+            &[("2001:db8::", 32), ("0.0.0.0", 0)], &[(1, 1), (3, 5)]
+        );
+        assert_eq!(
+            set.to_string(),
+            "AS1, AS3-AS5, 0.0.0.0/0, 2001:db8::/32"
+        );
+    }
+}
@@ -2,7 +2,6 @@
 
 use std::mem;
 use std::net::SocketAddr;
-use std::time::SystemTime;
 use futures::future;
 use futures::{Async, Future, IntoFuture, Stream};
 use tokio;
@@ -21,7 +20,7 @@ pub fn rtr_listener(
     history: OriginsHistory,
     config: &'static Config,
 ) -> (NotifySender, impl Future<Item=(), Error=()>) {
-    let session = session_id();
+    let session = history.session();
     let (dispatch, dispatch_fut) = Dispatch::new();
     let fut = dispatch_fut.select(
         future::select_all(
@@ -36,12 +35,6 @@ pub fn rtr_listener(
     (dispatch.get_sender(), fut)
 }
 
-fn session_id() -> u16 {
-    SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH).unwrap()
-        .as_secs() as u16
-}
-
 fn single_listener(
     addr: SocketAddr,
     session: u16,
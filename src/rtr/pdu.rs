@@ -12,6 +12,7 @@ use tokio::io::{
     AsyncRead, AsyncWrite, ReadExact, WriteAll, read_exact, write_all
 };
 use ::origins::AddressOrigin;
+use ::serial::Serial;
 
 
 //------------ Macro for Common Impls ----------------------------------------
@@ -73,10 +74,10 @@ impl SerialNotify {
     pub const PDU: u8 = 0;
     pub const LEN: u32 = 12;
 
-    pub fn new(version: u8, session: u16, serial: u32) -> Self {
+    pub fn new(version: u8, session: u16, serial: Serial) -> Self {
         SerialNotify {
             header: Header::new(version, Self::PDU, session, Self::LEN),
-            serial
+            serial: serial.to_be()
         }
     }
 }
@@ -142,8 +143,8 @@ impl SerialQueryPayload {
     }
     */
 
-    pub fn serial(&self) -> u32 {
-        u32::from_be(self.serial)
+    pub fn serial(&self) -> Serial {
+        Serial::from_be(self.serial)
     }
 }
 
@@ -400,7 +401,7 @@ impl EndOfData {
     pub fn new(
         version: u8,
         session: u16,
-        serial: u32,
+        serial: Serial,
         refresh: u32,
         retry: u32,
         expire: u32
@@ -452,7 +453,7 @@ pub struct EndOfDataV0 {
 
 #[allow(dead_code)]
 impl EndOfDataV0 {
-    pub fn new(session: u16, serial: u32) -> Self {
+    pub fn new(session: u16, serial: Serial) -> Self {
         EndOfDataV0 {
             header: Header::new(0, 7, session, 12),
             serial: serial.to_be()
@@ -467,8 +468,8 @@ impl EndOfDataV0 {
         u16::from_be(self.header.session)
     }
 
-    pub fn serial(&self) -> u32 {
-        u32::from_be(self.serial)
+    pub fn serial(&self) -> Serial {
+        Serial::from_be(self.serial)
     }
 }
 
@@ -493,7 +494,7 @@ impl EndOfDataV1 {
     pub fn new(
         version: u8,
         session: u16,
-        serial: u32,
+        serial: Serial,
         refresh: u32,
         retry: u32,
         expire: u32
@@ -515,8 +516,8 @@ impl EndOfDataV1 {
         u16::from_be(self.header.session)
     }
 
-    pub fn serial(&self) -> u32 {
-        u32::from_be(self.serial)
+    pub fn serial(&self) -> Serial {
+        Serial::from_be(self.serial)
     }
 
     pub fn refresh(&self) -> u32 {
@@ -6,6 +6,7 @@ use futures::{Async, Future};
 use tokio::io::{AsyncWrite, WriteAll};
 use ::config::Config;
 use ::origins::{AddressOrigins, OriginsDiff};
+use ::serial::Serial;
 use super::pdu;
 
 
@@ -20,7 +21,12 @@ pub enum Sender<A> {
 }
 
 impl<A: AsyncWrite> Sender<A> {
-    pub fn notify(sock: A, version: u8, session: u16, serial: u32) -> Self {
+    pub fn notify(
+        sock: A,
+        version: u8,
+        session: u16,
+        serial: Serial
+    ) -> Self {
         Sender::Notify(
             pdu::SerialNotify::new(version, session, serial).write(sock)
         )
@@ -48,7 +54,7 @@ impl<A: AsyncWrite> Sender<A> {
         sock: A,
         version: u8,
         session: u16,
-        serial: u32,
+        serial: Serial,
         current: Arc<AddressOrigins>,
         config: &Config,
     ) -> Self {
@@ -114,7 +120,7 @@ impl<A: AsyncWrite, D> Wrapped<A, D> {
         sock: A,
         version: u8,
         session: u16,
-        serial: u32,
+        serial: Serial,
         iter: D,
         config: &Config
     ) -> Self {
@@ -250,9 +256,9 @@ impl Iterator for SendFull {
     type Item = pdu::Prefix;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(res ) = self.origins.get(self.next_idx) {
+        if let Some(res) = self.origins.get(self.next_idx) {
             self.next_idx += 1;
-            Some(pdu::Prefix::new(self.version, 1, res))
+            Some(pdu::Prefix::new(self.version, 1, &res))
         }
         else {
             None
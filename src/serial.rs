@@ -0,0 +1,163 @@
+//! Serial numbers.
+//!
+//! RTR identifies versions of the data set by a 32 bit serial number that
+//! is increased with every change and wraps around once it reaches the
+//! maximum value. Such serial numbers can only be compared and added to
+//! following the rules of RFC 1982 which are implemented by [`Serial`].
+//!
+//! [`Serial`]: struct.Serial.html
+
+use std::{cmp, fmt, ops};
+
+
+//------------ Serial --------------------------------------------------------
+
+/// A serial number following the arithmetic of RFC 1982.
+///
+/// Serial numbers are only partially ordered. Two serial numbers that are
+/// exactly 2^31 apart cannot be compared and `partial_cmp` returns `None`
+/// for them. In particular, this means that all of `<`, `>`, and `==` are
+/// false in this case.
+///
+/// Only values up to 2^31 - 1 can be added to a serial number. Adding
+/// larger values panics.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Serial(pub u32);
+
+impl Serial {
+    /// The largest value that can be added to a serial number.
+    pub const MAX_ADD: u32 = 0x7fff_ffff;
+
+    /// Creates a serial number from a value in network byte order.
+    pub fn from_be(value: u32) -> Self {
+        Serial(u32::from_be(value))
+    }
+
+    /// Returns the serial number as a value in network byte order.
+    pub fn to_be(self) -> u32 {
+        self.0.to_be()
+    }
+
+    /// Returns the serial number following this one.
+    pub fn next(self) -> Self {
+        self + 1
+    }
+}
+
+
+//--- From
+
+impl From<u32> for Serial {
+    fn from(value: u32) -> Serial {
+        Serial(value)
+    }
+}
+
+impl From<Serial> for u32 {
+    fn from(serial: Serial) -> u32 {
+        serial.0
+    }
+}
+
+
+//--- Add
+
+impl ops::Add<u32> for Serial {
+    type Output = Serial;
+
+    fn add(self, other: u32) -> Serial {
+        assert!(other <= Self::MAX_ADD, "serial number addition overflow");
+        Serial(self.0.wrapping_add(other))
+    }
+}
+
+
+//--- PartialOrd
+
+impl PartialOrd for Serial {
+    fn partial_cmp(&self, other: &Serial) -> Option<cmp::Ordering> {
+        if self.0 == other.0 {
+            Some(cmp::Ordering::Equal)
+        }
+        else {
+            match other.0.wrapping_sub(self.0) {
+                0x8000_0000 => None,
+                diff if diff < 0x8000_0000 => Some(cmp::Ordering::Less),
+                _ => Some(cmp::Ordering::Greater)
+            }
+        }
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_compare_around_wrap() {
+        assert!(Serial(0) < Serial(1));
+        assert!(Serial(::std::u32::MAX) < Serial(0));
+        assert!(Serial(0xffff_fff0) < Serial(5));
+        assert!(Serial(5) > Serial(0xffff_fff0));
+        assert!(Serial(0) < Serial(0x7fff_ffff));
+        assert!(Serial(0) > Serial(0x8000_0001));
+        assert_eq!(Serial(0).partial_cmp(&Serial(0x8000_0000)), None);
+        assert_eq!(Serial(::std::u32::MAX) + 1, Serial(0));
+        assert_eq!(Serial(::std::u32::MAX).next(), Serial(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_large_addition() {
+        let _ = Serial(0) + 0x8000_0000;
+    }
+
+    quickcheck! {
+        fn added_serials_are_greater(serial: u32, add: u32) -> bool {
+            let add = add & Serial::MAX_ADD;
+            let serial = Serial(serial);
+            if add == 0 {
+                serial + add == serial
+            }
+            else {
+                serial + add > serial
+            }
+        }
+
+        fn comparison_is_antisymmetric(left: u32, right: u32) -> bool {
+            let (left, right) = (Serial(left), Serial(right));
+            match left.partial_cmp(&right) {
+                Some(order) => {
+                    right.partial_cmp(&left) == Some(order.reverse())
+                }
+                None => right.partial_cmp(&left).is_none()
+            }
+        }
+
+        fn comparison_ignores_offset(
+            left: u32, right: u32, offset: u32
+        ) -> bool {
+            let offset = offset & Serial::MAX_ADD;
+            let (left, right) = (Serial(left), Serial(right));
+            left.partial_cmp(&right)
+                == (left + offset).partial_cmp(&(right + offset))
+        }
+
+        fn network_order_round_trips(value: u32) -> bool {
+            Serial::from_be(Serial(value).to_be()) == Serial(value)
+        }
+    }
+}
@@ -1,4 +1,8 @@
 //! Local exceptions per RFC 8416 aka SLURM.
+//!
+//! Besides loading local exceptions, this module can also produce SLURM
+//! files. This allows exporting a set of route origins as locally added
+//! assertions for use by another relying party software.
 
 use std::io;
 use std::fs::File;
@@ -11,6 +15,7 @@ use json::object::Object as JsonObject;
 use rpki::asres::AsId;
 use super::origins;
 use super::origins::{AddressOrigin, AddressPrefix};
+use super::output::{Metadata, OutputWriter};
 
 
 //------------ LocalExceptions -----------------------------------------------
@@ -97,12 +102,76 @@ impl LocalExceptions {
         true
     }
 
+    pub fn filters(&self) -> &[PrefixFilter] {
+        self.filters.as_ref()
+    }
+
     pub fn assertions(&self) -> &[AddressOrigin] {
         self.assertions.as_ref()
     }
 }
 
 
+//------------ SlurmWriter ---------------------------------------------------
+
+/// Writes route origins as a SLURM file with prefix assertions.
+///
+/// The file contains no filters. It can be loaded again as
+/// [`LocalExceptions`].
+///
+/// [`LocalExceptions`]: struct.LocalExceptions.html
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SlurmWriter {
+    /// Are we still waiting for the first route origin?
+    first: bool,
+}
+
+impl SlurmWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OutputWriter for SlurmWriter {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        self.first = true;
+        write!(
+            target,
+            "{{\n  \"slurmVersion\": 1,\n  \
+             \"validationOutputFilters\": {{\n    \
+             \"prefixFilters\": [],\n    \"bgpsecFilters\": []\n  \
+             }},\n  \"locallyAddedAssertions\": {{\n    \
+             \"prefixAssertions\": ["
+        )
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        if self.first {
+            self.first = false;
+        }
+        else {
+            write!(target, ",")?;
+        }
+        write!(target, "\n      {}", origin.to_json().dump())
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if !self.first {
+            write!(target, "\n    ")?;
+        }
+        writeln!(target, "],\n    \"bgpsecAssertions\": []\n  }}\n}}")
+    }
+}
+
+
 //------------ PrefixFilter --------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -182,7 +251,16 @@ impl PrefixFilter {
         }
     }
 
-    fn filter_origin(&self, addr: &AddressOrigin) -> bool {
+    pub fn prefix(&self) -> Option<AddressPrefix> {
+        self.prefix
+    }
+
+    pub fn asn(&self) -> Option<AsId> {
+        self.asn
+    }
+
+    /// Returns whether the filter drops the given address origin.
+    pub fn filter_origin(&self, addr: &AddressOrigin) -> bool {
         match (self.prefix, self.asn) {
             (Some(prefix), Some(asn)) => {
                 prefix.covers(addr.prefix()) && asn == addr.as_id()
@@ -301,6 +379,22 @@ impl AddressOrigin {
         }
     }
 
+    /// Returns the route origin as a SLURM prefix assertion.
+    ///
+    /// The trust anchors the route origin was derived from, if any, are
+    /// given in the comment.
+    fn to_json(&self) -> JsonValue {
+        let mut res = JsonValue::new_object();
+        res["asn"] = u32::from(self.as_id()).into();
+        res["prefix"] = format!(
+            "{}/{}", self.address(), self.address_length()
+        ).into();
+        res["maxPrefixLength"] = self.max_length().into();
+        if !self.sources().is_empty() {
+            res["comment"] = self.tal_list().into();
+        }
+        res
+    }
 }
 
 
@@ -430,6 +524,32 @@ pub mod tests {
 
 
 
+    #[test]
+    fn should_stream_slurm_file() {
+        use super::super::origins::AddressOrigins;
+        use super::super::output::tests::written;
+
+        let origins: AddressOrigins = vec![
+            address_origin(64496, "192.0.2.0", 24, 24),
+            address_origin(64497, "2001:db8::", 32, 48),
+        ].into_iter().collect();
+        let exceptions = LocalExceptions::from_json(
+            json::parse(&written(SlurmWriter::new(), &origins)).unwrap()
+        ).unwrap();
+        assert!(exceptions.filters.is_empty());
+        assert_eq!(
+            exceptions.assertions,
+            origins.iter().collect::<Vec<_>>()
+        );
+
+        let exceptions = LocalExceptions::from_json(
+            json::parse(
+                &written(SlurmWriter::new(), &AddressOrigins::new())
+            ).unwrap()
+        ).unwrap();
+        assert!(exceptions.assertions.is_empty());
+    }
+
     #[test]
     fn should_parse_empty_slurm_file() {
         let empty = include_str!("../test/slurm/empty.json");
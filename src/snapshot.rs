@@ -0,0 +1,452 @@
+//! Comparing snapshots of route origins.
+//!
+//! A snapshot is a set of validated route origins stored in a file. This
+//! can be any of the CSV and JSON output formats, including the extended
+//! ones, or the RTR state file. The format is determined from the content
+//! of the file: a JSON object with a `roas` member is output, one with an
+//! `origins` member is a state file, and anything else is treated as CSV
+//! with a header line.
+//!
+//! Two snapshots can be compared via [`SnapshotDiff`] which lists the
+//! added and removed route origins grouped by trust anchor and AS number.
+//!
+//! [`SnapshotDiff`]: struct.SnapshotDiff.html
+
+use std::{io, slice};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use json;
+use json::JsonValue;
+use rpki::asres::AsId;
+use super::origins::{
+    AddressOrigin, AddressOrigins, AddressPrefix, CapacityError, OriginSource,
+    TalCache, parse_as_id
+};
+use super::state::{StateError, origins_from_state};
+
+
+//------------ Loading -------------------------------------------------------
+
+/// Loads a snapshot from the file at `path`.
+pub fn load(path: &Path) -> Result<AddressOrigins, SnapshotError> {
+    let mut buf = String::new();
+    File::open(path)?.read_to_string(&mut buf)?;
+    parse(&buf)
+}
+
+/// Parses a snapshot in any of the supported formats.
+pub fn parse(data: &str) -> Result<AddressOrigins, SnapshotError> {
+    if data.trim_start().starts_with('{') {
+        let json = json::parse(data)?;
+        if json["roas"].is_array() {
+            from_json(&json["roas"])
+        }
+        else if json["origins"].is_array() {
+            AddressOrigins::try_from_iter(
+                origins_from_state(&json)?
+            ).map_err(Into::into)
+        }
+        else {
+            Err(SnapshotError::UnknownFormat)
+        }
+    }
+    else {
+        from_csv(data)
+    }
+}
+
+/// Creates a snapshot from the `roas` member of JSON output.
+///
+/// The trust anchor is taken from the `ta` member where multiple names
+/// are separated by semicolons or from the `source` list of the extended
+/// format.
+fn from_json(json: &JsonValue) -> Result<AddressOrigins, SnapshotError> {
+    let mut tals = TalCache::default();
+    let mut res = Vec::with_capacity(json.len());
+    for (idx, value) in json.members().enumerate() {
+        let line = idx + 1;
+        let as_id = match value["asn"].as_u32() {
+            Some(asn) => AsId::from(asn),
+            None => {
+                value["asn"].as_str().and_then(parse_as_id)
+                    .ok_or(SnapshotError::Invalid(line, "invalid asn"))?
+            }
+        };
+        let prefix = value["prefix"].as_str().and_then(|prefix| {
+            AddressPrefix::from_str(prefix).ok()
+        }).ok_or(SnapshotError::Invalid(line, "invalid prefix"))?;
+        let max_length = value["maxLength"].as_u8()
+            .ok_or(SnapshotError::Invalid(line, "invalid maxLength"))?;
+        let sources = if value["source"].is_array() {
+            value["source"].members().map(|source| {
+                OriginSource::new(
+                    source["ta"].as_str().and_then(|name| tals.lookup(name)),
+                    source["uri"].as_str().map(Into::into)
+                )
+            }).collect()
+        }
+        else {
+            tals.sources(value["ta"].as_str().unwrap_or(""))
+        };
+        res.push(
+            AddressOrigin::with_sources(as_id, prefix, max_length, sources)
+        );
+    }
+    AddressOrigins::try_from_iter(res).map_err(Into::into)
+}
+
+/// Creates a snapshot from CSV output.
+///
+/// If the header line starts with `URI`, the extended format is assumed.
+/// Otherwise, the columns are AS number, prefix, max length, and,
+/// optionally, trust anchor.
+fn from_csv(data: &str) -> Result<AddressOrigins, SnapshotError> {
+    let mut tals = TalCache::default();
+    let mut lines = data.lines().enumerate();
+    let extended = match lines.next() {
+        Some((_, header)) => header.starts_with("URI,"),
+        None => return Ok(AddressOrigins::new())
+    };
+    let mut res = Vec::new();
+    for (idx, line) in lines {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            continue
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let uri = if extended {
+            match fields.next() {
+                Some("N/A") | None => None,
+                Some(uri) => Some(uri.into()),
+            }
+        }
+        else {
+            None
+        };
+        let as_id = fields.next().and_then(parse_as_id)
+            .ok_or(SnapshotError::Invalid(line_no, "invalid ASN"))?;
+        let prefix = fields.next().and_then(|prefix| {
+            AddressPrefix::from_str(prefix).ok()
+        }).ok_or(SnapshotError::Invalid(line_no, "invalid prefix"))?;
+        let max_length = fields.next().and_then(|len| {
+            u8::from_str(len).ok()
+        }).ok_or(SnapshotError::Invalid(line_no, "invalid max length"))?;
+        let mut sources = tals.sources(fields.next().unwrap_or(""));
+        if let Some(uri) = uri {
+            sources = vec![OriginSource::new(
+                sources.pop().and_then(|source| source.tal().cloned()),
+                Some(uri)
+            )];
+        }
+        res.push(
+            AddressOrigin::with_sources(as_id, prefix, max_length, sources)
+        );
+    }
+    AddressOrigins::try_from_iter(res).map_err(Into::into)
+}
+
+
+
+//------------ SnapshotDiff --------------------------------------------------
+
+/// The differences between two snapshots.
+///
+/// The added and removed route origins are grouped by trust anchor and AS
+/// number. The trust anchor of added route origins is taken from the new
+/// snapshot, that of removed route origins from the old one.
+#[derive(Clone, Debug)]
+pub struct SnapshotDiff {
+    /// The groups of changed route origins.
+    groups: Vec<DiffGroup>,
+
+    /// The number of route origins added.
+    added: usize,
+
+    /// The number of route origins removed.
+    removed: usize,
+}
+
+impl SnapshotDiff {
+    /// Compares the snapshots `old` and `new`.
+    pub fn new(old: &AddressOrigins, new: &AddressOrigins) -> Self {
+        let (announce, withdraw) = old.diff(new);
+        let added = announce.len();
+        let removed = withdraw.len();
+        let mut groups = BTreeMap::new();
+        for origin in announce {
+            groups.entry(
+                (origin.tal_list(), u32::from(origin.as_id()))
+            ).or_insert_with(|| (Vec::new(), Vec::new())).0.push(origin)
+        }
+        for origin in withdraw {
+            groups.entry(
+                (origin.tal_list(), u32::from(origin.as_id()))
+            ).or_insert_with(|| (Vec::new(), Vec::new())).1.push(origin)
+        }
+        SnapshotDiff {
+            groups: groups.into_iter().map(|((tal, as_id), lists)| {
+                let (mut added, mut removed) = lists;
+                added.sort();
+                removed.sort();
+                DiffGroup { tal, as_id: AsId::from(as_id), added, removed }
+            }).collect(),
+            added, removed
+        }
+    }
+
+    /// Returns whether the two snapshots are identical.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Returns the number of route origins added.
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// Returns the number of route origins removed.
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Returns an iterator over the groups of changed route origins.
+    pub fn iter(&self) -> slice::Iter<DiffGroup> {
+        self.groups.iter()
+    }
+
+    /// Writes the differences in human readable form.
+    ///
+    /// Added route origins are marked with a `+`, removed ones with a `-`.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        for group in &self.groups {
+            writeln!(target, "{} {}", group.tal, group.as_id)?;
+            for origin in &group.added {
+                write_origin(target, '+', origin)?;
+            }
+            for origin in &group.removed {
+                write_origin(target, '-', origin)?;
+            }
+        }
+        writeln!(
+            target, "{} route origins added, {} removed.",
+            self.added, self.removed
+        )
+    }
+
+    /// Returns the differences as a JSON value.
+    pub fn to_json(&self) -> JsonValue {
+        let mut groups = JsonValue::new_array();
+        for group in &self.groups {
+            let mut value = JsonValue::new_object();
+            value["ta"] = group.tal.clone().into();
+            value["asn"] = group.as_id.to_string().into();
+            value["added"] = origins_to_json(&group.added);
+            value["removed"] = origins_to_json(&group.removed);
+            let _ = groups.push(value);
+        }
+        let mut res = JsonValue::new_object();
+        res["added"] = self.added.into();
+        res["removed"] = self.removed.into();
+        res["groups"] = groups;
+        res
+    }
+}
+
+fn write_origin<W: io::Write>(
+    target: &mut W,
+    mark: char,
+    origin: &AddressOrigin
+) -> Result<(), io::Error> {
+    writeln!(
+        target, "    {} {}/{}-{}",
+        mark, origin.address(), origin.address_length(), origin.max_length()
+    )
+}
+
+fn origins_to_json(origins: &[AddressOrigin]) -> JsonValue {
+    let mut res = JsonValue::new_array();
+    for origin in origins {
+        let mut value = JsonValue::new_object();
+        value["prefix"] = format!(
+            "{}/{}", origin.address(), origin.address_length()
+        ).into();
+        value["maxLength"] = origin.max_length().into();
+        let _ = res.push(value);
+    }
+    res
+}
+
+
+//------------ DiffGroup -----------------------------------------------------
+
+/// The changed route origins of one AS under one trust anchor.
+#[derive(Clone, Debug)]
+pub struct DiffGroup {
+    tal: String,
+    as_id: AsId,
+    added: Vec<AddressOrigin>,
+    removed: Vec<AddressOrigin>,
+}
+
+impl DiffGroup {
+    pub fn tal_name(&self) -> &str {
+        &self.tal
+    }
+
+    pub fn as_id(&self) -> AsId {
+        self.as_id
+    }
+
+    pub fn added(&self) -> &[AddressOrigin] {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &[AddressOrigin] {
+        &self.removed
+    }
+}
+
+
+//------------ SnapshotError -------------------------------------------------
+
+#[derive(Debug, Fail)]
+pub enum SnapshotError {
+    #[fail(display="{}", _0)]
+    Io(io::Error),
+
+    #[fail(display="{}", _0)]
+    Json(json::Error),
+
+    #[fail(display="{}", _0)]
+    State(StateError),
+
+    #[fail(display="entry {}: {}", _0, _1)]
+    Invalid(usize, &'static str),
+
+    #[fail(display="unknown JSON format")]
+    UnknownFormat,
+
+    #[fail(display="{}", _0)]
+    Capacity(CapacityError),
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<json::Error> for SnapshotError {
+    fn from(err: json::Error) -> SnapshotError {
+        SnapshotError::Json(err)
+    }
+}
+
+impl From<StateError> for SnapshotError {
+    fn from(err: StateError) -> SnapshotError {
+        SnapshotError::State(err)
+    }
+}
+
+impl From<CapacityError> for SnapshotError {
+    fn from(err: CapacityError) -> SnapshotError {
+        SnapshotError::Capacity(err)
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_formats() {
+        let csv = parse(
+            "ASN,IP Prefix,Max Length,Trust Anchor\n\
+             AS64496,10.0.0.0/16,24,ripe;arin\n\
+             AS64497,2001:db8::/32,48,N/A\n"
+        ).unwrap();
+        let csvext = parse(
+            "URI,ASN,IP Prefix,Max Length,Trust Anchor\n\
+             rsync://a/a.roa,AS64496,10.0.0.0/16,24,ripe\n\
+             N/A,AS64497,2001:db8::/32,48,N/A\n"
+        ).unwrap();
+        let json = parse(
+            r#"{ "roas": [
+                { "asn": "AS64496", "prefix": "10.0.0.0/16",
+                  "maxLength": 24, "ta": "ripe" },
+                { "asn": 64497, "prefix": "2001:db8::/32",
+                  "maxLength": 48, "ta": "N/A" }
+            ] }"#
+        ).unwrap();
+        let jsonext = parse(
+            r#"{ "roas": [
+                { "asn": "AS64496", "prefix": "10.0.0.0/16",
+                  "maxLength": 24,
+                  "source": [ { "ta": "ripe", "uri": "rsync://a/a.roa" } ]
+                },
+                { "asn": "AS64497", "prefix": "2001:db8::/32",
+                  "maxLength": 48, "source": [] }
+            ] }"#
+        ).unwrap();
+        for set in &[&csv, &csvext, &json, &jsonext] {
+            assert_eq!(set.len(), 2);
+            assert!(SnapshotDiff::new(&csv, set).is_empty());
+        }
+        assert_eq!(csv.get(0).unwrap().tal_list(), "arin;ripe");
+        assert_eq!(
+            csvext.get(0).unwrap().sources()[0].uri(),
+            Some("rsync://a/a.roa")
+        );
+        assert_eq!(jsonext.get(1).unwrap().tal_list(), "N/A");
+        assert!(parse("ASN,IP Prefix\nfoo,bar\n").is_err());
+        match parse("{ \"foo\": 1 }") {
+            Err(SnapshotError::UnknownFormat) => { }
+            _ => panic!("accepted unknown JSON format")
+        }
+    }
+
+    #[test]
+    fn should_diff_snapshots() {
+        let old = parse(
+            "ASN,IP Prefix,Max Length,Trust Anchor\n\
+             AS64496,10.0.0.0/16,24,ripe\n\
+             AS64496,10.1.0.0/16,16,ripe\n\
+             AS64497,192.0.2.0/24,24,arin\n"
+        ).unwrap();
+        let new = parse(
+            "ASN,IP Prefix,Max Length,Trust Anchor\n\
+             AS64496,10.0.0.0/16,24,ripe\n\
+             AS64496,10.2.0.0/16,16,ripe\n\
+             AS64498,2001:db8::/32,48,arin\n"
+        ).unwrap();
+        let diff = SnapshotDiff::new(&old, &new);
+        assert_eq!((diff.added(), diff.removed()), (2, 2));
+        let mut text = Vec::new();
+        diff.write_text(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "arin AS64497\n\
+             \x20   - 192.0.2.0/24-24\n\
+             arin AS64498\n\
+             \x20   + 2001:db8::/32-48\n\
+             ripe AS64496\n\
+             \x20   + 10.2.0.0/16-16\n\
+             \x20   - 10.1.0.0/16-16\n\
+             2 route origins added, 2 removed.\n"
+        );
+        let json = diff.to_json();
+        assert_eq!(json["groups"].len(), 3);
+        assert_eq!(json["groups"][2]["asn"], "AS64496");
+        assert_eq!(json["groups"][2]["added"][0]["prefix"], "10.2.0.0/16");
+        assert_eq!(json["groups"][2]["removed"][0]["maxLength"], 16);
+    }
+}
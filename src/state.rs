@@ -0,0 +1,390 @@
+//! Persisting the RTR state across restarts.
+//!
+//! RTR clients remember the session ID and serial number of the data they
+//! have received. If these don’t match after a restart, they have to
+//! perform a cache reset and load the full data set again. To avoid this,
+//! the current set of address origins, the recent diffs, the serial number,
+//! and the session ID of an [`OriginsHistory`] are written to a state file
+//! after every update and read back on startup.
+//!
+//! The state file is a JSON object with the following members:
+//!
+//! * `version`: the version of the file format, currently 1,
+//! * `session`: the RTR session ID,
+//! * `serial`: the serial number of the current set,
+//! * `origins`: the current set of address origins,
+//! * `diffs`: the diffs, newest first, each an object with members
+//!   `serial`, `announce`, and `withdraw`. The serial of the first diff is
+//!   that of the current set, each following diff has the serial number
+//!   right before that of its predecessor.
+//!
+//! Each address origin is an object with the members `asn`, `prefix`,
+//! `maxLength`, and `sources`. The latter is a list of objects with the
+//! members `ta` and `uri` for the name of the trust anchor and the URI of
+//! the ROA, either of which can be `null`.
+//!
+//! [`OriginsHistory`]: ../origins/struct.OriginsHistory.html
+
+use std::io;
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use json;
+use json::JsonValue;
+use rpki::asres::AsId;
+use super::atomic::AtomicFile;
+use super::origins::{
+    AddressOrigin, AddressOrigins, AddressPrefix, CapacityError, OriginSource,
+    OriginsDiff, OriginsHistory, TalCache
+};
+use super::serial::Serial;
+
+
+//------------ save and load -------------------------------------------------
+
+/// Writes the state of `history` to the file at `path`.
+///
+/// The file is replaced atomically, so that a crash never leaves a
+/// partially written state file behind.
+pub fn save(history: &OriginsHistory, path: &Path) -> Result<(), io::Error> {
+    let file = AtomicFile::create(path)?;
+    if let Err(err) = to_json(history).write(&mut file.file()) {
+        file.abort();
+        return Err(err)
+    }
+    file.commit()
+}
+
+/// Restores a history from the state file at `path`.
+///
+/// Returns `Ok(None)` if there is no such file. The restored history will
+/// keep up to `keep` diffs.
+pub fn load(
+    path: &Path,
+    keep: usize
+) -> Result<Option<OriginsHistory>, StateError> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(None)
+        }
+        Err(err) => return Err(err.into())
+    };
+    let mut buf = String::new();
+    file.read_to_string(&mut buf)?;
+    from_json(json::parse(&buf)?, keep).map(Some)
+}
+
+
+/// Returns the current set of address origins from a parsed state file.
+///
+/// The diffs, serial number, and session ID are ignored.
+pub fn origins_from_state(
+    json: &JsonValue
+) -> Result<Vec<AddressOrigin>, StateError> {
+    if json["version"].as_u8() != Some(1) {
+        return Err(StateError::BadVersion)
+    }
+    origins_from_json(&json["origins"], &mut TalCache::default())
+}
+
+
+//------------ Conversion to JSON --------------------------------------------
+
+/// Returns the state of `history` as a JSON value.
+fn to_json(history: &OriginsHistory) -> JsonValue {
+    let (current, diffs, serial, session) = history.snapshot();
+    let mut list = JsonValue::new_array();
+    for diff in diffs {
+        let mut value = JsonValue::new_object();
+        value["serial"] = u32::from(diff.serial()).into();
+        value["announce"] = origins_to_json(diff.announce());
+        value["withdraw"] = origins_to_json(diff.withdraw());
+        let _ = list.push(value);
+    }
+    let mut res = JsonValue::new_object();
+    res["version"] = 1.into();
+    res["session"] = session.into();
+    res["serial"] = u32::from(serial).into();
+    res["origins"] = origins_to_json(current.iter());
+    res["diffs"] = list;
+    res
+}
+
+fn origins_to_json<I>(origins: I) -> JsonValue
+where I: IntoIterator, I::Item: Borrow<AddressOrigin> {
+    let mut res = JsonValue::new_array();
+    for origin in origins {
+        let origin = origin.borrow();
+        let mut value = JsonValue::new_object();
+        value["asn"] = u32::from(origin.as_id()).into();
+        value["prefix"] = format!(
+            "{}/{}", origin.address(), origin.address_length()
+        ).into();
+        value["maxLength"] = origin.max_length().into();
+        let mut sources = JsonValue::new_array();
+        for source in origin.sources() {
+            let mut item = JsonValue::new_object();
+            item["ta"] = source.tal().map(|tal| tal.name()).into();
+            item["uri"] = source.uri().into();
+            let _ = sources.push(item);
+        }
+        value["sources"] = sources;
+        let _ = res.push(value);
+    }
+    res
+}
+
+
+//------------ Conversion from JSON ------------------------------------------
+
+/// Creates a history from its JSON representation.
+fn from_json(
+    json: JsonValue,
+    keep: usize
+) -> Result<OriginsHistory, StateError> {
+    let mut tals = TalCache::default();
+    if json["version"].as_u8() != Some(1) {
+        return Err(StateError::BadVersion)
+    }
+    let session = json["session"].as_u16()
+        .ok_or(StateError::Invalid("session"))?;
+    let serial = json["serial"].as_u32().map(Serial)
+        .ok_or(StateError::Invalid("serial"))?;
+    let current = origins_from_json(&json["origins"], &mut tals)?;
+    if !json["diffs"].is_array() {
+        return Err(StateError::Invalid("diffs"))
+    }
+    let mut diffs = Vec::new();
+    let mut expected = serial;
+    for diff in json["diffs"].members() {
+        let diff_serial = diff["serial"].as_u32().map(Serial)
+            .ok_or(StateError::Invalid("diffs.serial"))?;
+        if diff_serial != expected {
+            return Err(StateError::DiffSerial(diff_serial, expected))
+        }
+        expected = Serial(diff_serial.0.wrapping_sub(1));
+        diffs.push(OriginsDiff::new(
+            diff_serial,
+            origins_from_json(&diff["announce"], &mut tals)?,
+            origins_from_json(&diff["withdraw"], &mut tals)?,
+        ))
+    }
+    Ok(OriginsHistory::from_parts(
+        AddressOrigins::try_from_iter(current)?,
+        diffs, serial, session, keep
+    ))
+}
+
+fn origins_from_json(
+    json: &JsonValue,
+    tals: &mut TalCache
+) -> Result<Vec<AddressOrigin>, StateError> {
+    if !json.is_array() {
+        return Err(StateError::Invalid("origins"))
+    }
+    let mut res = Vec::with_capacity(json.len());
+    for value in json.members() {
+        let asn = value["asn"].as_u32().ok_or(StateError::Invalid("asn"))?;
+        let prefix = value["prefix"].as_str().and_then(|prefix| {
+            AddressPrefix::from_str(prefix).ok()
+        }).ok_or(StateError::Invalid("prefix"))?;
+        let max_bits = if prefix.address().is_ipv4() { 32 } else { 128 };
+        let max_length = value["maxLength"].as_u8().and_then(|len| {
+            if len < prefix.address_length() || len > max_bits {
+                None
+            }
+            else {
+                Some(len)
+            }
+        }).ok_or(StateError::Invalid("maxLength"))?;
+        if !value["sources"].is_array() {
+            return Err(StateError::Invalid("sources"))
+        }
+        let sources = value["sources"].members().map(|source| {
+            OriginSource::new(
+                source["ta"].as_str().map(|name| tals.get(name)),
+                source["uri"].as_str().map(Into::into)
+            )
+        }).collect();
+        res.push(AddressOrigin::with_sources(
+            AsId::from(asn), prefix, max_length, sources
+        ))
+    }
+    Ok(res)
+}
+
+
+//------------ StateError ----------------------------------------------------
+
+#[derive(Debug, Fail)]
+pub enum StateError {
+    #[fail(display="{}", _0)]
+    Io(io::Error),
+
+    #[fail(display="{}", _0)]
+    Json(json::Error),
+
+    #[fail(display="unsupported state file version")]
+    BadVersion,
+
+    #[fail(display="missing or invalid {}", _0)]
+    Invalid(&'static str),
+
+    #[fail(display="diff with serial {} where {} was expected", _0, _1)]
+    DiffSerial(Serial, Serial),
+
+    #[fail(display="{}", _0)]
+    Capacity(CapacityError),
+}
+
+impl From<io::Error> for StateError {
+    fn from(err: io::Error) -> StateError {
+        StateError::Io(err)
+    }
+}
+
+impl From<json::Error> for StateError {
+    fn from(err: json::Error) -> StateError {
+        StateError::Json(err)
+    }
+}
+
+impl From<CapacityError> for StateError {
+    fn from(err: CapacityError) -> StateError {
+        StateError::Capacity(err)
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use std::sync::Arc;
+    use rpki::tal::TalInfo;
+    use super::*;
+
+    fn make_origin(asn: u32, prefix: &str, max_len: u8) -> AddressOrigin {
+        AddressOrigin::new(AsId::from(asn), prefix.parse().unwrap(), max_len)
+    }
+
+    #[test]
+    fn should_restore_history() {
+        let tal = Arc::new(TalInfo::from_name("tal".into()));
+        let uri = Some("rsync://example.net/a.roa".into());
+        let history = OriginsHistory::from_parts(
+            vec![
+                AddressOrigin::with_sources(
+                    AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 20,
+                    vec![
+                        OriginSource::new(Some(tal), uri),
+                        OriginSource::new(None, None),
+                    ]
+                ),
+                make_origin(64497, "2001:db8::/32", 48),
+            ].into_iter().collect(),
+            vec![
+                OriginsDiff::new(
+                    Serial(12), vec![make_origin(64497, "2001:db8::/32", 48)],
+                    vec![make_origin(64498, "192.0.2.0/24", 24)]
+                ),
+                OriginsDiff::new(
+                    Serial(11), vec![make_origin(64498, "192.0.2.0/24", 24)],
+                    Vec::new()
+                ),
+            ],
+            Serial(12), 4711, 10
+        );
+        let json = json::parse(&to_json(&history).dump()).unwrap();
+        let restored = from_json(json, 10).unwrap();
+        assert_eq!(restored.serial(), Serial(12));
+        assert_eq!(restored.session(), 4711);
+        let current = restored.current();
+        assert_eq!(
+            current.get(0).unwrap().sources(),
+            history.current().get(0).unwrap().sources()
+        );
+        assert_eq!(
+            current.iter().map(|origin| origin.prefix()).collect::<Vec<_>>(),
+            vec![
+                "10.0.0.0/16".parse().unwrap(),
+                "2001:db8::/32".parse().unwrap()
+            ]
+        );
+        let diffs = restored.diffs();
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].serial(), Serial(12));
+        assert_eq!(diffs[0].withdraw(), history.diffs()[0].withdraw());
+        assert_eq!(diffs[1].announce(), history.diffs()[1].announce());
+    }
+
+    #[test]
+    fn should_reject_bad_state() {
+        for data in &[
+            r#"{"version": 2}"#,
+            r#"{"version": 1, "session": 1, "serial": 1}"#,
+            r#"{"version": 1, "session": 1, "serial": 1,
+                "origins": [{"asn": 1, "prefix": "10.0.0.0", "maxLength": 8}],
+                "diffs": []}"#,
+        ] {
+            assert!(from_json(json::parse(data).unwrap(), 10).is_err())
+        }
+    }
+
+    #[test]
+    fn should_reject_bad_max_length() {
+        for &(prefix, max_length) in &[
+            ("10.0.0.0/16", 8), ("10.0.0.0/16", 33), ("2001:db8::/32", 129)
+        ] {
+            let mut json = json::parse(
+                r#"{"version": 1, "session": 1, "serial": 1,
+                    "origins": [], "diffs": []}"#
+            ).unwrap();
+            let mut origin = JsonValue::new_object();
+            origin["asn"] = 64496.into();
+            origin["prefix"] = prefix.into();
+            origin["maxLength"] = max_length.into();
+            origin["sources"] = JsonValue::new_array();
+            json["origins"].push(origin).unwrap();
+            match from_json(json, 10) {
+                Err(StateError::Invalid("maxLength")) => { }
+                _ => panic!("accepted {} max {}", prefix, max_length)
+            }
+        }
+    }
+
+    #[test]
+    fn should_reject_broken_diff_chain() {
+        for serials in &[&[11, 10][..], &[12, 10], &[11, 12], &[12, 12]] {
+            let mut json = json::parse(
+                r#"{"version": 1, "session": 1, "serial": 12,
+                    "origins": [], "diffs": []}"#
+            ).unwrap();
+            for serial in serials.iter() {
+                let mut diff = JsonValue::new_object();
+                diff["serial"] = (*serial).into();
+                diff["announce"] = JsonValue::new_array();
+                diff["withdraw"] = JsonValue::new_array();
+                json["diffs"].push(diff).unwrap();
+            }
+            match from_json(json, 10) {
+                Err(StateError::DiffSerial(..)) => { }
+                _ => panic!("accepted diff serials {:?}", serials)
+            }
+        }
+
+        // Serials wrap around.
+        let json = json::parse(
+            r#"{"version": 1, "session": 1, "serial": 0, "origins": [],
+                "diffs": [
+                    {"serial": 0, "announce": [], "withdraw": []},
+                    {"serial": 4294967295, "announce": [], "withdraw": []}
+                ]}"#
+        ).unwrap();
+        assert_eq!(from_json(json, 10).unwrap().diffs().len(), 2);
+    }
+}
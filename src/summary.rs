@@ -0,0 +1,398 @@
+//! A summary of a validation run.
+//!
+//! The [`Summary`] collects the numbers of a validation run per trust
+//! anchor: how many ROAs were valid and how many route origins they
+//! produced, how many CAs and publication points were encountered, how
+//! many objects were rejected and why, and how long it all took. It is
+//! meant as a quick health check for humans rather than a data set for
+//! further processing.
+//!
+//! [`Summary`]: struct.Summary.html
+
+use std::io;
+use std::time::Duration;
+use chrono::SecondsFormat;
+use super::origins::{AddressOrigin, AddressOrigins};
+use super::output::{Metadata, OutputWriter};
+use super::report::{RejectReason, TalStats, ValidationReport};
+
+
+//------------ Summary -------------------------------------------------------
+
+/// The summary of a validation run.
+#[derive(Clone, Debug)]
+pub struct Summary {
+    /// Information about the data set.
+    metadata: Metadata,
+
+    /// The summaries of the individual trust anchors ordered by name.
+    tals: Vec<TalSummary>,
+
+    /// The route origins from local exceptions.
+    local: VrpCount,
+
+    /// The sum over all trust anchors.
+    ///
+    /// The route origins are counted after removing duplicates and include
+    /// those from local exceptions.
+    total: TalSummary,
+}
+
+impl Summary {
+    /// Creates the summary from the result of a validation run.
+    pub fn new(
+        origins: &AddressOrigins,
+        report: &ValidationReport,
+        metadata: &Metadata
+    ) -> Self {
+        let mut res = Self::start(report.tal_stats(), metadata);
+        for origin in origins.iter() {
+            res.push(&origin)
+        }
+        res
+    }
+
+    /// Creates a summary without any route origins.
+    fn start(stats: &[TalStats], metadata: &Metadata) -> Self {
+        let mut tals: Vec<_> = stats.iter().map(|stats| {
+            TalSummary {
+                stats: stats.clone(),
+                vrps: VrpCount::default(),
+            }
+        }).collect();
+        tals.sort_by(|left, right| left.stats.tal.cmp(&right.stats.tal));
+        let mut total = TalSummary {
+            stats: TalStats::new("total".into()),
+            vrps: VrpCount::default(),
+        };
+        for tal in &tals {
+            total.stats.add(&tal.stats)
+        }
+        Summary {
+            metadata: metadata.clone(),
+            tals,
+            local: VrpCount::default(),
+            total
+        }
+    }
+
+    /// Adds a route origin to the summary.
+    fn push(&mut self, origin: &AddressOrigin) {
+        let ipv4 = origin.address().is_ipv4();
+        self.total.vrps.count(ipv4);
+        let names = origin.tal_names();
+        if names.is_empty() {
+            self.local.count(ipv4);
+        }
+        for name in names {
+            if let Some(tal) = self.tals.iter_mut().find(|tal| {
+                tal.stats.tal == name
+            }) {
+                tal.vrps.count(ipv4)
+            }
+        }
+    }
+
+    /// Returns the summaries of the individual trust anchors.
+    pub fn tals(&self) -> &[TalSummary] {
+        &self.tals
+    }
+
+    /// Returns the number of route origins from local exceptions.
+    pub fn local(&self) -> VrpCount {
+        self.local
+    }
+
+    /// Returns the sum over all trust anchors.
+    pub fn total(&self) -> &TalSummary {
+        &self.total
+    }
+
+    /// Writes the summary in human readable form.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target, "Summary generated {}",
+            self.metadata.generated().to_rfc3339_opts(
+                SecondsFormat::Secs, true
+            )
+        )?;
+        writeln!(
+            target, "{:<24}{:>10}", "repository update:",
+            match self.metadata.update_duration() {
+                Some(update) => duration(update),
+                None => "skipped".into()
+            }
+        )?;
+        if let Some(process) = self.metadata.process_duration() {
+            writeln!(
+                target, "{:<24}{:>10}", "validation:", duration(process)
+            )?;
+        }
+        for tal in &self.tals {
+            writeln!(target, "\n{}:", tal.stats.tal)?;
+            tal.write_text(true, target)?;
+        }
+        if self.local.total() > 0 {
+            writeln!(target, "\nlocal exceptions:")?;
+            self.local.write_text(target)?;
+        }
+        writeln!(target, "\ntotal:")?;
+        self.total.write_text(false, target)
+    }
+}
+
+
+//------------ TalSummary ----------------------------------------------------
+
+/// The summary for a single trust anchor.
+#[derive(Clone, Debug)]
+pub struct TalSummary {
+    /// The statistics collected during processing.
+    stats: TalStats,
+
+    /// The route origins derived from the trust anchor.
+    vrps: VrpCount,
+}
+
+impl TalSummary {
+    /// Returns the statistics collected during processing.
+    pub fn stats(&self) -> &TalStats {
+        &self.stats
+    }
+
+    /// Returns the number of route origins.
+    pub fn vrps(&self) -> VrpCount {
+        self.vrps
+    }
+
+    /// Writes the summary in human readable form.
+    ///
+    /// The processing time is only included if `time` is `true`.
+    fn write_text<W: io::Write>(
+        &self,
+        time: bool,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(target, "    {:<20}{:>10}", "valid ROAs:", self.stats.roas)?;
+        self.vrps.write_text(target)?;
+        writeln!(
+            target, "    {:<20}{:>10}", "CA certificates:",
+            self.stats.ca_certs
+        )?;
+        writeln!(
+            target, "    {:<20}{:>10}", "publication points:",
+            self.stats.publication_points
+        )?;
+        writeln!(
+            target, "    {:<20}{:>10}", "rejected objects:",
+            self.stats.rejected_total()
+        )?;
+        for reason in RejectReason::all() {
+            let count = self.stats.rejected(*reason);
+            if count > 0 {
+                writeln!(
+                    target, "        {:<16}{:>10}",
+                    format!("{}:", reason), count
+                )?;
+            }
+        }
+        if time {
+            writeln!(
+                target, "    {:<20}{:>10}", "processing time:",
+                duration(self.stats.duration)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ VrpCount ------------------------------------------------------
+
+/// The number of route origins split by address family.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct VrpCount {
+    /// The number of IPv4 route origins.
+    pub ipv4: usize,
+
+    /// The number of IPv6 route origins.
+    pub ipv6: usize,
+}
+
+impl VrpCount {
+    /// Returns the total number of route origins.
+    pub fn total(self) -> usize {
+        self.ipv4 + self.ipv6
+    }
+
+    fn count(&mut self, ipv4: bool) {
+        if ipv4 {
+            self.ipv4 += 1
+        }
+        else {
+            self.ipv6 += 1
+        }
+    }
+
+    fn write_text<W: io::Write>(
+        self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target, "    {:<20}{:>10} (IPv4: {}, IPv6: {})", "VRPs:",
+            self.total(), self.ipv4, self.ipv6
+        )
+    }
+}
+
+
+//------------ SummaryWriter -------------------------------------------------
+
+/// Writes the summary of a validation run in human readable form.
+///
+/// Since the summary only contains numbers, the route origins are counted
+/// as they are passed in and the text is produced at the very end.
+#[derive(Clone, Debug)]
+pub struct SummaryWriter {
+    /// The statistics of the trust anchors.
+    stats: Vec<TalStats>,
+
+    /// The summary collected so far.
+    summary: Option<Summary>,
+}
+
+impl SummaryWriter {
+    /// Creates a writer using the statistics from a validation report.
+    pub fn new(report: &ValidationReport) -> Self {
+        SummaryWriter {
+            stats: report.tal_stats().to_vec(),
+            summary: None,
+        }
+    }
+}
+
+impl OutputWriter for SummaryWriter {
+    fn begin(
+        &mut self,
+        metadata: &Metadata,
+        _target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        self.summary = Some(Summary::start(&self.stats, metadata));
+        Ok(())
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        _target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        if let Some(ref mut summary) = self.summary {
+            summary.push(origin)
+        }
+        Ok(())
+    }
+
+    fn end(
+        &mut self,
+        mut target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        match self.summary.take() {
+            Some(summary) => summary.write_text(&mut target),
+            None => Ok(())
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Formats a duration in seconds with millisecond precision.
+fn duration(duration: Duration) -> String {
+    format!("{}.{:03}s", duration.as_secs(), duration.subsec_millis())
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+    use chrono::{TimeZone, Utc};
+    use rpki::asres::AsId;
+    use rpki::tal::TalInfo;
+    use super::*;
+    use super::super::origins::{AddressOrigin, OriginSource};
+    use super::super::output::Selection;
+
+    #[test]
+    fn should_summarize() {
+        let ripe = Arc::new(TalInfo::from_name("ripe".into()));
+        let arin = Arc::new(TalInfo::from_name("arin".into()));
+        let origins: AddressOrigins = vec![
+            AddressOrigin::with_sources(
+                AsId::from(64496), "10.0.0.0/16".parse().unwrap(), 24,
+                vec![
+                    OriginSource::new(Some(ripe.clone()), None),
+                    OriginSource::new(Some(arin), None),
+                ]
+            ),
+            AddressOrigin::with_tal(
+                AsId::from(64497), "2001:db8::/32".parse().unwrap(), 48,
+                Some(ripe)
+            ),
+            AddressOrigin::new(
+                AsId::from(64498), "192.0.2.0/24".parse().unwrap(), 24
+            ),
+        ].into_iter().collect();
+
+        let mut report = ValidationReport::new();
+        let mut stats = TalStats::new("ripe".into());
+        stats.roas = 2;
+        stats.ca_certs = 3;
+        stats.publication_points = 2;
+        stats.reject(RejectReason::Revoked);
+        stats.reject(RejectReason::Invalid);
+        stats.reject(RejectReason::Invalid);
+        stats.duration = Duration::from_millis(1250);
+        report.push_tal_stats(stats);
+        let mut stats = TalStats::new("arin".into());
+        stats.roas = 1;
+        stats.ca_certs = 1;
+        stats.publication_points = 1;
+        stats.duration = Duration::from_millis(30);
+        report.push_tal_stats(stats);
+
+        let metadata = Metadata::new(
+            Utc.ymd(2026, 10, 18).and_hms(6, 0, 0), None, 3, 4
+        ).with_durations(
+            Some(Duration::from_secs(61)), Some(Duration::from_millis(1300))
+        );
+
+        let summary = Summary::new(&origins, &report, &metadata);
+        assert_eq!(summary.tals()[0].stats().tal, "arin");
+        assert_eq!(summary.tals()[0].vrps(), VrpCount { ipv4: 1, ipv6: 0 });
+        assert_eq!(summary.tals()[1].vrps(), VrpCount { ipv4: 1, ipv6: 1 });
+        assert_eq!(summary.local(), VrpCount { ipv4: 1, ipv6: 0 });
+        assert_eq!(summary.total().vrps().total(), 3);
+        assert_eq!(summary.total().stats().rejected_total(), 3);
+
+        let mut text = Vec::new();
+        summary.write_text(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            include_str!("../test/summary/summary.txt")
+        );
+
+        let mut text = Vec::new();
+        SummaryWriter::new(&report).write_origins(
+            &metadata, &origins, &Selection::new(), &mut text
+        ).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            include_str!("../test/summary/summary.txt")
+        );
+    }
+}
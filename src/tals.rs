@@ -0,0 +1,1082 @@
+//! Trust anchor management.
+//!
+//! This module contains [`TalDir`], the directory that keeps the trust
+//! anchor locators (TALs) Routinator uses. Each TAL lives in its own file.
+//! Files with the extension `.tal` are in use, files with the extension
+//! `.tal.disabled` have been disabled by the user and are ignored during
+//! validation. This way, TALs can be disabled and later enabled again
+//! without the need to keep a copy of the file elsewhere.
+//!
+//! [`TalDir`]: struct.TalDir.html
+
+use std::{fmt, fs, io, process};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use rpki::tal::Tal;
+use super::atomic::AtomicFile;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The file name extension of enabled TALs.
+const ENABLED_EXT: &str = ".tal";
+
+/// The file name extension of disabled TALs.
+const DISABLED_EXT: &str = ".tal.disabled";
+
+/// The location of the terms of use for the ARIN TAL.
+pub const ARIN_TERMS_URL: &str =
+    "https://www.arin.net/resources/rpki/tal.html";
+
+/// The location of the ARIN TAL in RFC 7730 format.
+pub const ARIN_TAL_URL: &str =
+    "https://www.arin.net/resources/rpki/arin-rfc7730.tal";
+
+/// The TALs we install into a fresh TAL directory.
+///
+/// Note that the content of `arin.tal` isn’t actually a TAL but a
+/// placeholder explaining where to get the real thing.
+const DEFAULT_TALS: [(&str, &[u8]); 5] = [
+    ("afrinic.tal", include_bytes!("../tals/afrinic.tal")),
+    ("apnic.tal", include_bytes!("../tals/apnic.tal")),
+    ("arin.tal", ARIN_PLACEHOLDER),
+    ("lacnic.tal", include_bytes!("../tals/lacnic.tal")),
+    ("ripe.tal", include_bytes!("../tals/ripe.tal")),
+];
+
+/// The content of the placeholder for the ARIN TAL.
+const ARIN_PLACEHOLDER: &[u8] = include_bytes!("../tals/arin.tal");
+
+
+//------------ TalDir --------------------------------------------------------
+
+/// The directory containing the trust anchor locators.
+#[derive(Clone, Debug)]
+pub struct TalDir {
+    /// The path to the directory.
+    path: PathBuf,
+}
+
+impl TalDir {
+    /// Creates a new value for the TAL directory at the given path.
+    ///
+    /// This doesn’t check whether the directory actually exists.
+    pub fn new(path: PathBuf) -> Self {
+        TalDir { path }
+    }
+
+    /// Returns the path to the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Populates the directory with the default set of TALs.
+    ///
+    /// Existing files of the same name will be overwritten.
+    pub fn install_defaults(&self) -> Result<(), TalError> {
+        for &(name, content) in &DEFAULT_TALS {
+            write_tal(&self.path.join(name), content)?;
+        }
+        Ok(())
+    }
+
+    /// Returns a list of all TALs, enabled or disabled, sorted by name.
+    ///
+    /// Files with other extensions are silently skipped.
+    pub fn entries(&self) -> Result<Vec<TalEntry>, TalError> {
+        let dir = fs::read_dir(&self.path).map_err(|err| {
+            TalError::Io(self.path.display().to_string(), err)
+        })?;
+        let mut res = Vec::new();
+        for entry in dir {
+            let entry = entry.map_err(|err| {
+                TalError::Io(self.path.display().to_string(), err)
+            })?;
+            if let Some(entry) = TalEntry::from_path(entry.path()) {
+                res.push(entry)
+            }
+        }
+        res.sort_by(|left, right| left.name.cmp(&right.name));
+        Ok(res)
+    }
+
+    /// Returns a list of all enabled TALs, sorted by name.
+    pub fn enabled(&self) -> Result<Vec<TalEntry>, TalError> {
+        let mut res = self.entries()?;
+        res.retain(TalEntry::is_enabled);
+        Ok(res)
+    }
+
+    /// Checks the directory and all enabled TALs for problems.
+    ///
+    /// In addition to checking that each enabled TAL can be parsed, this
+    /// looks at the URIs and keys of the TALs individually, checks for the
+    /// same key appearing in more than one TAL, and reports files that
+    /// don’t seem to belong into the directory.
+    pub fn check(&self) -> Result<TalCheck, TalError> {
+        let mut res = TalCheck::default();
+
+        let dir = fs::read_dir(&self.path).map_err(|err| {
+            TalError::Io(self.path.display().to_string(), err)
+        })?;
+        for entry in dir {
+            let entry = entry.map_err(|err| {
+                TalError::Io(self.path.display().to_string(), err)
+            })?;
+            let path = entry.path();
+            let hidden = entry.file_name().to_str().map(|name| {
+                name.starts_with('.')
+            }).unwrap_or(false);
+            if hidden {
+                continue
+            }
+            match entry.file_type() {
+                Ok(ref file_type) if file_type.is_file() => { }
+                _ => {
+                    res.push(path, TalProblemKind::NotAFile);
+                    continue
+                }
+            }
+            if TalEntry::from_path(path.clone()).is_none() {
+                res.push(path, TalProblemKind::UnknownExtension);
+            }
+        }
+
+        let mut keys: Vec<(String, String)> = Vec::new();
+        for entry in self.enabled()? {
+            let (key, problems) = entry.check();
+            let fatal = problems.iter().any(TalProblemKind::is_fatal);
+            for problem in problems {
+                res.push(entry.path.clone(), problem)
+            }
+            if fatal {
+                res.broken.push(entry);
+                continue
+            }
+            if let Some(key) = key {
+                let dup = keys.iter().find(|item| item.0 == key).map(|item| {
+                    item.1.clone()
+                });
+                match dup {
+                    Some(other) => {
+                        res.push(
+                            entry.path.clone(),
+                            TalProblemKind::DuplicateKey(other)
+                        );
+                        // Using it would validate the same trust anchor
+                        // twice under different names.
+                        res.broken.push(entry);
+                        continue
+                    }
+                    None => keys.push((key, entry.name.clone()))
+                }
+            }
+            res.usable.push(entry);
+        }
+        Ok(res)
+    }
+
+    /// Returns the TAL with the given name.
+    ///
+    /// The name may be given with or without the `.tal` extension. If the
+    /// TAL exists both enabled and disabled, it is unclear which one is
+    /// meant and the method fails.
+    pub fn find(&self, name: &str) -> Result<TalEntry, TalError> {
+        let name = check_name(strip_ext(name))?;
+        let mut found = self.entries()?.into_iter().filter(|entry| {
+            entry.name == name
+        });
+        let res = found.next().ok_or_else(|| {
+            TalError::NotFound(name.into())
+        })?;
+        if found.next().is_some() {
+            return Err(TalError::Ambiguous(name.into()))
+        }
+        Ok(res)
+    }
+
+    /// Enables the TAL with the given name.
+    ///
+    /// Enabling a TAL that already is enabled is not an error.
+    pub fn enable(&self, name: &str) -> Result<(), TalError> {
+        let entry = self.find(name)?;
+        if entry.enabled {
+            return Ok(())
+        }
+        entry.rename(self.enabled_path(&entry.name))
+    }
+
+    /// Disables the TAL with the given name.
+    ///
+    /// Disabling a TAL that already is disabled is not an error.
+    pub fn disable(&self, name: &str) -> Result<(), TalError> {
+        let entry = self.find(name)?;
+        if !entry.enabled {
+            return Ok(())
+        }
+        entry.rename(self.disabled_path(&entry.name))
+    }
+
+    /// Adds a new TAL from a file or URL.
+    ///
+    /// If `source` starts with `http://` or `https://`, the TAL is fetched
+    /// from that URL. Otherwise it is taken to be a path to a local file.
+    /// If `name` is `None`, the name of the new TAL will be derived from
+    /// the last component of the source.
+    ///
+    /// The content is checked to be a valid TAL before it is installed.
+    /// If a TAL of the same name exists already, enabled or disabled, the
+    /// method fails.
+    ///
+    /// Returns the name of the new TAL.
+    pub fn add(
+        &self,
+        source: &str,
+        name: Option<&str>
+    ) -> Result<String, TalError> {
+        let name = match name {
+            Some(name) => check_name(strip_ext(name))?.to_string(),
+            None => name_from_source(source)?,
+        };
+        if self.entries()?.iter().any(|entry| entry.name == name) {
+            return Err(TalError::Exists(name))
+        }
+        let content = load_source(source)?;
+        self.install(&name, &content)?;
+        Ok(name)
+    }
+
+    /// Installs the ARIN TAL.
+    ///
+    /// This will fetch the TAL from ARIN’s website and install it as
+    /// `arin.tal`, replacing the placeholder if present. The placeholder is
+    /// only removed once the new TAL has been checked and written, so it
+    /// stays in place if anything goes wrong.
+    ///
+    /// Because of ARIN’s terms of use, this requires the user’s explicit
+    /// consent which is to be given via `accept`. If it is `false`, the
+    /// method will fail with an error pointing the user to the terms.
+    pub fn install_arin(&self, accept: bool) -> Result<(), TalError> {
+        if !accept {
+            return Err(TalError::ArinTerms(ARIN_TERMS_URL))
+        }
+        let placeholder = match self.find("arin") {
+            Ok(entry) => {
+                if !entry.is_placeholder() {
+                    return Err(TalError::Exists(entry.name))
+                }
+                Some(entry)
+            }
+            Err(_) => None
+        };
+        let content = load_source(ARIN_TAL_URL)?;
+        self.install("arin", &content)?;
+        if let Some(entry) = placeholder {
+            // An enabled placeholder has just been replaced. A disabled
+            // one is still around and needs to go.
+            if entry.path != self.enabled_path("arin") {
+                fs::remove_file(&entry.path).map_err(|err| {
+                    TalError::Io(entry.path.display().to_string(), err)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks and then writes a TAL to the directory.
+    fn install(&self, name: &str, content: &[u8]) -> Result<(), TalError> {
+        let path = self.enabled_path(check_name(name)?);
+        if let Err(err) = Tal::read(&path, &mut &content[..]) {
+            return Err(TalError::Invalid(name.into(), err.to_string()))
+        }
+        write_tal(&path, content)
+    }
+
+    /// Returns the path of the enabled TAL with the given name.
+    ///
+    /// The name must have been checked via `check_name` so that the path
+    /// stays within the directory.
+    fn enabled_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{}{}", name, ENABLED_EXT))
+    }
+
+    /// Returns the path of the disabled TAL with the given name.
+    ///
+    /// The name must have been checked via `check_name`, too.
+    fn disabled_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{}{}", name, DISABLED_EXT))
+    }
+}
+
+
+//------------ TalEntry ------------------------------------------------------
+
+/// A single TAL in the TAL directory.
+#[derive(Clone, Debug)]
+pub struct TalEntry {
+    /// The name of the TAL, i.e., the file name without extension.
+    name: String,
+
+    /// The path of the TAL file.
+    path: PathBuf,
+
+    /// Is the TAL enabled?
+    enabled: bool,
+}
+
+impl TalEntry {
+    /// Creates an entry from a path if the path looks like a TAL.
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let (name, enabled) = {
+            let file_name = path.file_name()?.to_str()?;
+            if file_name.ends_with(DISABLED_EXT) {
+                (&file_name[..file_name.len() - DISABLED_EXT.len()], false)
+            }
+            else if file_name.ends_with(ENABLED_EXT) {
+                (&file_name[..file_name.len() - ENABLED_EXT.len()], true)
+            }
+            else {
+                return None
+            }
+        };
+        if name.is_empty() {
+            return None
+        }
+        Some(TalEntry { name: name.into(), path: path.clone(), enabled })
+    }
+
+    /// Returns the name of the TAL.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the path of the TAL file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the TAL is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns whether the file is the placeholder for the ARIN TAL.
+    pub fn is_placeholder(&self) -> bool {
+        match fs::read(&self.path) {
+            Ok(content) => content.as_slice() == ARIN_PLACEHOLDER,
+            Err(_) => false
+        }
+    }
+
+    /// Reads and parses the TAL.
+    pub fn read(&self) -> Result<Tal, TalError> {
+        let mut file = File::open(&self.path).map_err(|err| {
+            TalError::Io(self.path.display().to_string(), err)
+        })?;
+        Tal::read(&self.path, &mut file).map_err(|err| {
+            if self.is_placeholder() {
+                TalError::Placeholder(self.name.clone())
+            }
+            else {
+                TalError::Invalid(self.name.clone(), err.to_string())
+            }
+        })
+    }
+
+    /// Returns the status of the TAL.
+    pub fn status(&self) -> TalStatus {
+        if !self.enabled {
+            TalStatus::Disabled
+        }
+        else if self.is_placeholder() {
+            TalStatus::Placeholder
+        }
+        else if self.read().is_err() {
+            TalStatus::Broken
+        }
+        else {
+            TalStatus::Enabled
+        }
+    }
+
+    /// Checks the TAL for problems.
+    ///
+    /// Returns the normalized key of the TAL if it could be determined and
+    /// a list of all problems found.
+    pub fn check(&self) -> (Option<String>, Vec<TalProblemKind>) {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(err) => {
+                return (None, vec![TalProblemKind::Io(err.to_string())])
+            }
+        };
+        if content.as_slice() == ARIN_PLACEHOLDER {
+            return (None, vec![TalProblemKind::Placeholder])
+        }
+        let (key, mut problems) = check_content(&content);
+        if !problems.iter().any(TalProblemKind::is_fatal) {
+            // Our own checks are happy, but will the real parser be, too?
+            if let Err(err) = Tal::read(&self.path, &mut content.as_slice()) {
+                problems.push(TalProblemKind::Unparseable(err.to_string()))
+            }
+        }
+        (key, problems)
+    }
+
+    fn rename(&self, target: PathBuf) -> Result<(), TalError> {
+        if target.exists() {
+            return Err(TalError::Exists(self.name.clone()))
+        }
+        fs::rename(&self.path, &target).map_err(|err| {
+            TalError::Io(self.path.display().to_string(), err)
+        })
+    }
+}
+
+
+//------------ TalStatus -----------------------------------------------------
+
+/// The status of a TAL in the TAL directory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TalStatus {
+    /// The TAL is enabled and used for validation.
+    Enabled,
+
+    /// The TAL has been disabled and is ignored.
+    Disabled,
+
+    /// The TAL is the ARIN placeholder and needs to be replaced.
+    Placeholder,
+
+    /// The TAL is enabled but cannot be parsed.
+    Broken,
+}
+
+impl fmt::Display for TalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TalStatus::Enabled => "enabled",
+            TalStatus::Disabled => "disabled",
+            TalStatus::Placeholder => "placeholder",
+            TalStatus::Broken => "broken",
+        })
+    }
+}
+
+
+//------------ TalCheck ------------------------------------------------------
+
+/// The result of checking the TAL directory.
+#[derive(Clone, Debug, Default)]
+pub struct TalCheck {
+    /// The enabled TALs that can be used for validation.
+    usable: Vec<TalEntry>,
+
+    /// The enabled TALs that cannot be used.
+    broken: Vec<TalEntry>,
+
+    /// All the problems found.
+    problems: Vec<TalProblem>,
+}
+
+impl TalCheck {
+    /// Returns the TALs that can be used.
+    pub fn usable(&self) -> &[TalEntry] {
+        &self.usable
+    }
+
+    /// Returns the enabled TALs that cannot be used.
+    pub fn broken(&self) -> &[TalEntry] {
+        &self.broken
+    }
+
+    /// Returns all problems found.
+    pub fn problems(&self) -> &[TalProblem] {
+        &self.problems
+    }
+
+    /// Returns whether any of the enabled TALs cannot be used.
+    pub fn has_fatal(&self) -> bool {
+        !self.broken.is_empty()
+    }
+
+    /// Logs all problems.
+    ///
+    /// Fatal problems are logged as errors if `skip_broken` is `false` and
+    /// as warnings otherwise. All other problems are logged as warnings.
+    pub fn log(&self, skip_broken: bool) {
+        for problem in &self.problems {
+            if problem.is_fatal() && !skip_broken {
+                error!("{}", problem)
+            }
+            else {
+                warn!("{}", problem)
+            }
+        }
+        if skip_broken {
+            for entry in &self.broken {
+                warn!("Skipping broken TAL {}.", entry.name)
+            }
+        }
+    }
+
+    fn push(&mut self, path: PathBuf, kind: TalProblemKind) {
+        self.problems.push(TalProblem { path, kind })
+    }
+}
+
+
+//------------ TalProblem ----------------------------------------------------
+
+/// A problem found when checking the TAL directory.
+#[derive(Clone, Debug)]
+pub struct TalProblem {
+    /// The path of the offending file.
+    path: PathBuf,
+
+    /// What exactly is wrong.
+    kind: TalProblemKind,
+}
+
+impl TalProblem {
+    /// Returns the path of the file the problem was found in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the kind of problem.
+    pub fn kind(&self) -> &TalProblemKind {
+        &self.kind
+    }
+
+    /// Returns whether the problem renders the TAL unusable.
+    pub fn is_fatal(&self) -> bool {
+        self.kind.is_fatal()
+    }
+}
+
+impl fmt::Display for TalProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.kind)
+    }
+}
+
+
+//------------ TalProblemKind ------------------------------------------------
+
+/// The kinds of problems with TALs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TalProblemKind {
+    /// Something other than a file is in the TAL directory.
+    NotAFile,
+
+    /// A file with an unknown extension is in the TAL directory.
+    UnknownExtension,
+
+    /// The file couldn’t be read.
+    Io(String),
+
+    /// The TAL is the ARIN placeholder.
+    Placeholder,
+
+    /// The TAL isn’t a text file.
+    NotText,
+
+    /// A URI line in the TAL has a scheme we don’t support.
+    UnsupportedUri(String),
+
+    /// A URI line in the TAL isn’t a URI at all.
+    BadUri(String),
+
+    /// The TAL contains no usable URI.
+    NoUris,
+
+    /// The TAL contains no key.
+    NoKey,
+
+    /// The key isn’t correctly base64 encoded.
+    BadBase64,
+
+    /// The TAL was rejected by the TAL parser.
+    Unparseable(String),
+
+    /// The key of the TAL also appears in the TAL with the given name.
+    ///
+    /// Only the first TAL in order of their names is used.
+    DuplicateKey(String),
+}
+
+impl TalProblemKind {
+    /// Returns whether the problem renders the TAL unusable.
+    pub fn is_fatal(&self) -> bool {
+        match *self {
+            TalProblemKind::Io(_) | TalProblemKind::Placeholder |
+            TalProblemKind::NotText | TalProblemKind::NoUris |
+            TalProblemKind::NoKey | TalProblemKind::BadBase64 |
+            TalProblemKind::Unparseable(_) |
+            TalProblemKind::DuplicateKey(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for TalProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TalProblemKind::NotAFile => {
+                f.write_str("not a file, ignored")
+            }
+            TalProblemKind::UnknownExtension => {
+                f.write_str(
+                    "unknown extension, ignored (TALs need to end in .tal)"
+                )
+            }
+            TalProblemKind::Io(ref err) => write!(f, "{}", err),
+            TalProblemKind::Placeholder => {
+                f.write_str(
+                    "placeholder for the ARIN TAL; \
+                     see 'routinator tal install-arin'"
+                )
+            }
+            TalProblemKind::NotText => f.write_str("not a text file"),
+            TalProblemKind::UnsupportedUri(ref uri) => {
+                write!(f, "unsupported URI {}, ignored", uri)
+            }
+            TalProblemKind::BadUri(ref uri) => {
+                write!(f, "invalid URI '{}', ignored", uri)
+            }
+            TalProblemKind::NoUris => f.write_str("no usable rsync URIs"),
+            TalProblemKind::NoKey => f.write_str("missing public key"),
+            TalProblemKind::BadBase64 => {
+                f.write_str("public key is not valid base64")
+            }
+            TalProblemKind::Unparseable(ref err) => {
+                write!(f, "cannot parse TAL: {}", err)
+            }
+            TalProblemKind::DuplicateKey(ref other) => {
+                write!(f, "same public key as TAL '{}'", other)
+            }
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Checks the content of a TAL file.
+///
+/// A TAL as defined in RFC 7730 consists of one or more URI lines, an empty
+/// line, and the base64 encoded public key which may be spread over
+/// multiple lines. RFC 8630 additionally allows comment lines starting
+/// with `#` at the very beginning.
+///
+/// Returns the key with all white space removed if there is a key and a
+/// list of problems.
+fn check_content(content: &[u8]) -> (Option<String>, Vec<TalProblemKind>) {
+    let mut problems = Vec::new();
+    let content = match ::std::str::from_utf8(content) {
+        Ok(content) => content,
+        Err(_) => return (None, vec![TalProblemKind::NotText])
+    };
+    let mut lines = content.lines().map(str::trim).skip_while(|line| {
+        line.starts_with('#')
+    });
+
+    let mut usable = 0;
+    for line in lines.by_ref().take_while(|line| !line.is_empty()) {
+        if line.starts_with("rsync://") && line.len() > 8 {
+            usable += 1
+        }
+        else if line.contains("://") {
+            problems.push(TalProblemKind::UnsupportedUri(line.into()))
+        }
+        else {
+            problems.push(TalProblemKind::BadUri(line.into()))
+        }
+    }
+    if usable == 0 {
+        problems.push(TalProblemKind::NoUris)
+    }
+
+    let key: String = lines.collect();
+    if key.is_empty() {
+        problems.push(TalProblemKind::NoKey);
+        return (None, problems)
+    }
+    if !is_base64(&key) {
+        problems.push(TalProblemKind::BadBase64);
+        return (None, problems)
+    }
+    (Some(key), problems)
+}
+
+/// Returns whether a string is correctly padded base64.
+fn is_base64(s: &str) -> bool {
+    if s.len() % 4 != 0 {
+        return false
+    }
+    let data = s.trim_end_matches('=');
+    if s.len() - data.len() > 2 {
+        return false
+    }
+    data.bytes().all(|ch| {
+        ch.is_ascii_alphanumeric() || ch == b'+' || ch == b'/'
+    })
+}
+
+/// Strips a `.tal` extension off a name if present.
+fn strip_ext(name: &str) -> &str {
+    if name.ends_with(ENABLED_EXT) {
+        &name[..name.len() - ENABLED_EXT.len()]
+    }
+    else {
+        name
+    }
+}
+
+/// Checks that a TAL name can safely be used as a file name.
+///
+/// Since the name is used to construct the path of the TAL file, it must
+/// neither be empty nor contain path separators or `..`, and it must not
+/// start with a dot so that it doesn’t become a hidden file.
+fn check_name(name: &str) -> Result<&str, TalError> {
+    if name.is_empty() || name.starts_with('.') || name.contains("..")
+        || name.contains('/') || name.contains('\\')
+    {
+        Err(TalError::InvalidName(name.into()))
+    }
+    else {
+        Ok(name)
+    }
+}
+
+/// Returns whether the source of a TAL is a URL.
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Derives a TAL name from the last path component of a file or URL.
+fn name_from_source(source: &str) -> Result<String, TalError> {
+    let last = if is_url(source) {
+        source.rsplit('/').next()
+    }
+    else {
+        Path::new(source).file_name().and_then(|name| name.to_str())
+    };
+    match last.map(strip_ext).map(check_name) {
+        Some(Ok(name)) => Ok(name.into()),
+        _ => Err(TalError::BadName(source.into()))
+    }
+}
+
+/// Loads the content of a TAL from a file or URL.
+///
+/// URLs are fetched by running curl.
+fn load_source(source: &str) -> Result<Vec<u8>, TalError> {
+    if !is_url(source) {
+        return fs::read(source).map_err(|err| {
+            TalError::Io(source.into(), err)
+        })
+    }
+    info!("Fetching TAL from {}.", source);
+    let output = process::Command::new("curl")
+        .arg("--silent").arg("--show-error").arg("--fail").arg("--location")
+        .arg(source)
+        .output()
+        .map_err(|err| TalError::Fetch(source.into(), err.to_string()))?;
+    if !output.status.success() {
+        return Err(TalError::Fetch(
+            source.into(),
+            String::from_utf8_lossy(&output.stderr).trim().into()
+        ))
+    }
+    Ok(output.stdout)
+}
+
+/// Writes a TAL file.
+///
+/// The file is replaced atomically so that a validation run happening at
+/// the same time never sees a partially written TAL.
+fn write_tal(path: &Path, content: &[u8]) -> Result<(), TalError> {
+    AtomicFile::create(path).and_then(|file| {
+        match file.file().write_all(content) {
+            Ok(()) => file.commit(),
+            Err(err) => {
+                file.abort();
+                Err(err)
+            }
+        }
+    }).map_err(|err| TalError::Io(path.display().to_string(), err))
+}
+
+
+//------------ TalError ------------------------------------------------------
+
+#[derive(Debug, Fail)]
+pub enum TalError {
+    #[fail(display="{}: {}", _0, _1)]
+    Io(String, io::Error),
+
+    #[fail(display="no TAL named '{}'", _0)]
+    NotFound(String),
+
+    #[fail(display="a TAL named '{}' already exists", _0)]
+    Exists(String),
+
+    #[fail(
+        display="TAL '{}' exists both enabled and disabled. Please remove \
+                 one of them.", _0
+    )]
+    Ambiguous(String),
+
+    #[fail(display="cannot derive a TAL name from '{}'", _0)]
+    BadName(String),
+
+    #[fail(display="invalid TAL name '{}'", _0)]
+    InvalidName(String),
+
+    #[fail(display="TAL '{}' is invalid: {}", _0, _1)]
+    Invalid(String, String),
+
+    #[fail(
+        display="TAL '{}' is only a placeholder for the ARIN TAL. \
+                 Run 'routinator tal install-arin' to install the real \
+                 TAL or 'routinator tal disable {}' to ignore it.", _0, _0
+    )]
+    Placeholder(String),
+
+    #[fail(display="failed to fetch TAL from {}: {}", _0, _1)]
+    Fetch(String, String),
+
+    #[fail(
+        display="Installing the ARIN TAL requires agreeing to ARIN’s \
+                 Relying Party Agreement available at {}. If you \
+                 agree, add the --accept-arin-rpa option.", _0
+    )]
+    ArinTerms(&'static str),
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_accept_good_tal() {
+        let (key, problems) = check_content(
+            include_bytes!("../tals/ripe.tal")
+        );
+        assert!(key.is_some());
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn should_accept_comments() {
+        let (key, problems) = check_content(
+            b"# A comment\nrsync://example.com/ta.cer\n\nAAAA\nAA==\n"
+        );
+        assert_eq!(key, Some("AAAAAA==".into()));
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn should_reject_placeholder() {
+        let (key, problems) = check_content(ARIN_PLACEHOLDER);
+        assert!(key.is_none());
+        assert!(problems.iter().any(TalProblemKind::is_fatal));
+    }
+
+    #[test]
+    fn should_find_bad_base64() {
+        let (key, problems) = check_content(
+            b"rsync://example.com/ta.cer\n\nAA?A\n"
+        );
+        assert!(key.is_none());
+        assert_eq!(problems, vec![TalProblemKind::BadBase64]);
+    }
+
+    #[test]
+    fn should_find_missing_uris() {
+        let (_, problems) = check_content(
+            b"https://example.com/ta.cer\n\nAAAA\n"
+        );
+        assert_eq!(
+            problems,
+            vec![
+                TalProblemKind::UnsupportedUri(
+                    "https://example.com/ta.cer".into()
+                ),
+                TalProblemKind::NoUris
+            ]
+        );
+    }
+
+    /// A temporary TAL directory that is removed again when dropped.
+    struct TempTalDir(PathBuf);
+
+    impl TempTalDir {
+        fn new(name: &str) -> Self {
+            let path = ::std::env::temp_dir().join(format!(
+                "routinator-tals-{}-{}", process::id(), name
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempTalDir(path)
+        }
+
+        fn tals(&self) -> TalDir {
+            TalDir::new(self.0.clone())
+        }
+    }
+
+    impl Drop for TempTalDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const RIPE_TAL: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"), "/tals/ripe.tal"
+    );
+
+    #[test]
+    fn should_check_names() {
+        assert!(check_name("ripe").is_ok());
+        assert!(check_name("ripe-ncc.v2").is_ok());
+        for name in &["", ".hidden", "..", "../../etc/foo", "a/b", "a\\b"] {
+            match check_name(name) {
+                Err(TalError::InvalidName(_)) => { }
+                res => panic!("{:?} accepted as {:?}", name, res)
+            }
+        }
+        assert_eq!(
+            name_from_source("https://example.com/tals/ripe.tal").unwrap(),
+            "ripe"
+        );
+        assert!(name_from_source("https://example.com/tals/").is_err());
+        assert!(name_from_source("https://example.com/..").is_err());
+        assert!(name_from_source("https://example.com/.tal").is_err());
+    }
+
+    #[test]
+    fn should_add_enable_and_disable() {
+        let dir = TempTalDir::new("add");
+        let tals = dir.tals();
+
+        assert_eq!(tals.add(RIPE_TAL, Some("test.tal")).unwrap(), "test");
+        assert_eq!(tals.add(RIPE_TAL, None).unwrap(), "ripe");
+        match tals.add(RIPE_TAL, Some("test")) {
+            Err(TalError::Exists(_)) => { }
+            res => panic!("added twice: {:?}", res)
+        }
+        let names: Vec<_> = tals.entries().unwrap().iter().map(|entry| {
+            (entry.name().to_string(), entry.is_enabled())
+        }).collect();
+        assert_eq!(
+            names, vec![("ripe".into(), true), ("test".into(), true)]
+        );
+        // No temporary files are left behind.
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 2);
+
+        tals.disable("test").unwrap();
+        tals.disable("test").unwrap();
+        assert!(dir.0.join("test.tal.disabled").is_file());
+        assert!(!dir.0.join("test.tal").exists());
+        assert_eq!(tals.find("test").unwrap().status(), TalStatus::Disabled);
+        assert_eq!(tals.enabled().unwrap().len(), 1);
+
+        tals.enable("test.tal").unwrap();
+        assert!(dir.0.join("test.tal").is_file());
+        assert!(!dir.0.join("test.tal.disabled").exists());
+        assert_eq!(tals.enabled().unwrap().len(), 2);
+
+        match tals.enable("nope") {
+            Err(TalError::NotFound(_)) => { }
+            res => panic!("enabled missing TAL: {:?}", res)
+        }
+
+        fs::copy(RIPE_TAL, dir.0.join("test.tal.disabled")).unwrap();
+        match tals.find("test") {
+            Err(TalError::Ambiguous(_)) => { }
+            res => panic!("found TAL both enabled and disabled: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn should_not_use_duplicate_keys() {
+        let dir = TempTalDir::new("dup");
+        let tals = dir.tals();
+        tals.add(RIPE_TAL, Some("a")).unwrap();
+        tals.add(RIPE_TAL, Some("b")).unwrap();
+        let check = tals.check().unwrap();
+        assert!(check.has_fatal());
+        assert_eq!(check.usable().len(), 1);
+        assert_eq!(check.usable()[0].name(), "a");
+        assert_eq!(check.broken().len(), 1);
+        assert_eq!(check.broken()[0].name(), "b");
+        assert_eq!(
+            check.problems()[0].kind,
+            TalProblemKind::DuplicateKey("a".into())
+        );
+    }
+
+    #[test]
+    fn should_stay_inside_the_directory() {
+        let dir = TempTalDir::new("escape");
+        let tals = TalDir::new(dir.0.join("tals"));
+        fs::create_dir(tals.path()).unwrap();
+
+        for name in &["../escaped", "sub/escaped", ".escaped", ""] {
+            match tals.add(RIPE_TAL, Some(name)) {
+                Err(TalError::InvalidName(_)) => { }
+                res => panic!("added {:?}: {:?}", name, res)
+            }
+        }
+        assert!(tals.add("https://example.com/..", None).is_err());
+        match tals.enable("../escaped") {
+            Err(TalError::InvalidName(_)) => { }
+            res => panic!("enabled: {:?}", res)
+        }
+        match tals.disable("../escaped") {
+            Err(TalError::InvalidName(_)) => { }
+            res => panic!("disabled: {:?}", res)
+        }
+        assert!(!dir.0.join("escaped.tal").exists());
+        assert!(tals.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_install_arin_only_with_consent() {
+        let dir = TempTalDir::new("arin");
+        let tals = dir.tals();
+        tals.install_defaults().unwrap();
+        assert_eq!(
+            tals.find("arin").unwrap().status(), TalStatus::Placeholder
+        );
+        match tals.install_arin(false) {
+            Err(TalError::ArinTerms(url)) => assert_eq!(url, ARIN_TERMS_URL),
+            res => panic!("installed without consent: {:?}", res)
+        }
+        assert!(tals.find("arin").unwrap().is_placeholder());
+
+        // A real ARIN TAL is never replaced, so this doesn’t fetch.
+        fs::copy(RIPE_TAL, dir.0.join("arin.tal")).unwrap();
+        match tals.install_arin(true) {
+            Err(TalError::Exists(_)) => { }
+            res => panic!("replaced existing TAL: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn should_find_missing_key() {
+        let (key, problems) = check_content(
+            b"rsync://example.com/ta.cer\n"
+        );
+        assert!(key.is_none());
+        assert_eq!(problems, vec![TalProblemKind::NoKey]);
+    }
+}
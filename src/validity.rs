@@ -0,0 +1,399 @@
+//! Route origin validation.
+//!
+//! This module implements the route origin validation procedure described
+//! in RFC 6811. Given a route announcement consisting of an address prefix
+//! and an origin AS number, [`RouteValidity`] determines whether the
+//! announcement is valid, invalid, or not found based on a set of
+//! validated route origins.
+//!
+//! The easiest way to validate a route is through the `validate` method of
+//! [`AddressOrigins`]. For validating a large number of routes such as a
+//! full table, [`ValiditySummary`] collects the number of routes in each
+//! state and keeps only the invalid ones.
+//!
+//! [`RouteValidity`]: struct.RouteValidity.html
+//! [`ValiditySummary`]: struct.ValiditySummary.html
+//! [`AddressOrigins`]: ../origins/struct.AddressOrigins.html
+
+use std::{fmt, io};
+use std::borrow::Borrow;
+use json::JsonValue;
+use super::bgp::Announcement;
+use super::origins::{AddressOrigin, AddressOrigins};
+
+
+//------------ RouteState ----------------------------------------------------
+
+/// The validation state of a route announcement.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RouteState {
+    /// At least one route origin matches the announcement.
+    Valid,
+
+    /// There are covering route origins but none of them matches.
+    Invalid,
+
+    /// There are no covering route origins.
+    NotFound,
+}
+
+impl RouteState {
+    /// Returns all states.
+    pub fn all() -> &'static [RouteState] {
+        &[RouteState::Valid, RouteState::Invalid, RouteState::NotFound]
+    }
+}
+
+impl fmt::Display for RouteState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            RouteState::Valid => "valid",
+            RouteState::Invalid => "invalid",
+            RouteState::NotFound => "not-found",
+        })
+    }
+}
+
+
+//------------ RouteValidity -------------------------------------------------
+
+/// The result of validating a route announcement.
+///
+/// Besides the validation state, this contains all route origins covering
+/// the announced prefix, sorted into those that match the announcement,
+/// those with a different AS number, and those whose maximum length is
+/// shorter than the announced prefix.
+#[derive(Clone, Debug)]
+pub struct RouteValidity {
+    announcement: Announcement,
+    matched: Vec<AddressOrigin>,
+    unmatched_as: Vec<AddressOrigin>,
+    unmatched_length: Vec<AddressOrigin>,
+}
+
+impl RouteValidity {
+    /// Validates an announcement against a set of route origins.
+    ///
+    /// The route origins don’t need to be covering the announced prefix.
+    /// Those that don’t are simply ignored.
+    pub fn new<I>(announcement: Announcement, origins: I) -> Self
+    where I: IntoIterator, I::Item: Borrow<AddressOrigin> {
+        let mut res = RouteValidity {
+            announcement,
+            matched: Vec::new(),
+            unmatched_as: Vec::new(),
+            unmatched_length: Vec::new(),
+        };
+        for origin in origins {
+            let origin = origin.borrow();
+            if !origin.prefix().covers(announcement.prefix) {
+                continue
+            }
+            let length = announcement.prefix.address_length();
+            if length > origin.max_length() {
+                res.unmatched_length.push(origin.clone())
+            }
+            // AS0 route origins never match anything, see RFC 7607.
+            else if origin.as_id() != announcement.origin
+                || u32::from(origin.as_id()) == 0
+            {
+                res.unmatched_as.push(origin.clone())
+            }
+            else {
+                res.matched.push(origin.clone())
+            }
+        }
+        res
+    }
+
+    /// Returns the announcement that was validated.
+    pub fn announcement(&self) -> Announcement {
+        self.announcement
+    }
+
+    /// Returns the validation state of the announcement.
+    pub fn state(&self) -> RouteState {
+        if !self.matched.is_empty() {
+            RouteState::Valid
+        }
+        else if self.unmatched_as.is_empty()
+            && self.unmatched_length.is_empty()
+        {
+            RouteState::NotFound
+        }
+        else {
+            RouteState::Invalid
+        }
+    }
+
+    /// Returns the route origins matching the announcement.
+    pub fn matched(&self) -> &[AddressOrigin] {
+        &self.matched
+    }
+
+    /// Returns the covering route origins with a different AS number.
+    pub fn unmatched_as(&self) -> &[AddressOrigin] {
+        &self.unmatched_as
+    }
+
+    /// Returns the covering route origins with a too short max length.
+    pub fn unmatched_length(&self) -> &[AddressOrigin] {
+        &self.unmatched_length
+    }
+
+    /// Writes the result in human readable form.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target, "{}/{} {}: {}",
+            self.announcement.prefix.address(),
+            self.announcement.prefix.address_length(),
+            self.announcement.origin,
+            self.state()
+        )?;
+        for &(label, list) in &[
+            ("matched", &self.matched),
+            ("unmatched AS", &self.unmatched_as),
+            ("unmatched length", &self.unmatched_length),
+        ] {
+            for origin in list.iter() {
+                writeln!(
+                    target, "    {}: {} {}/{}-{} ({})",
+                    label, origin.as_id(),
+                    origin.address(), origin.address_length(),
+                    origin.max_length(), origin.tal_list()
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the result as a JSON value.
+    pub fn to_json(&self) -> JsonValue {
+        let mut route = JsonValue::new_object();
+        route["origin_asn"] = self.announcement.origin.to_string().into();
+        route["prefix"] = format!(
+            "{}/{}",
+            self.announcement.prefix.address(),
+            self.announcement.prefix.address_length()
+        ).into();
+        let mut vrps = JsonValue::new_object();
+        vrps["matched"] = origins_json(&self.matched);
+        vrps["unmatched_as"] = origins_json(&self.unmatched_as);
+        vrps["unmatched_length"] = origins_json(&self.unmatched_length);
+        let mut validity = JsonValue::new_object();
+        validity["state"] = self.state().to_string().into();
+        validity["VRPs"] = vrps;
+        let mut res = JsonValue::new_object();
+        res["route"] = route;
+        res["validity"] = validity;
+        res
+    }
+}
+
+
+//------------ ValiditySummary -----------------------------------------------
+
+/// The result of validating a list of route announcements.
+///
+/// Only the number of routes in each state and the details of the invalid
+/// routes are kept.
+#[derive(Clone, Debug, Default)]
+pub struct ValiditySummary {
+    valid: usize,
+    invalid: usize,
+    not_found: usize,
+    invalid_routes: Vec<RouteValidity>,
+}
+
+impl ValiditySummary {
+    /// Validates all announcements against a set of route origins.
+    pub fn new<'a, I>(origins: &AddressOrigins, announcements: I) -> Self
+    where I: IntoIterator<Item=&'a Announcement> {
+        let mut res = Self::default();
+        for announcement in announcements {
+            let validity = origins.validate(*announcement);
+            match validity.state() {
+                RouteState::Valid => res.valid += 1,
+                RouteState::Invalid => {
+                    res.invalid += 1;
+                    res.invalid_routes.push(validity);
+                }
+                RouteState::NotFound => res.not_found += 1,
+            }
+        }
+        res
+    }
+
+    /// Returns the number of routes in the given state.
+    pub fn count(&self, state: RouteState) -> usize {
+        match state {
+            RouteState::Valid => self.valid,
+            RouteState::Invalid => self.invalid,
+            RouteState::NotFound => self.not_found,
+        }
+    }
+
+    /// Returns the number of routes validated.
+    pub fn total(&self) -> usize {
+        self.valid + self.invalid + self.not_found
+    }
+
+    /// Returns the validation results of all invalid routes.
+    pub fn invalid_routes(&self) -> &[RouteValidity] {
+        &self.invalid_routes
+    }
+
+    /// Writes the summary in human readable form.
+    pub fn write_text<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        for state in RouteState::all() {
+            writeln!(
+                target, "{:<10} {:>9} ({:.2}%)",
+                format!("{}:", state), self.count(*state),
+                self.percentage(*state)
+            )?;
+        }
+        writeln!(target, "{:<10} {:>9}", "total:", self.total())?;
+        if !self.invalid_routes.is_empty() {
+            writeln!(target)?;
+            for route in &self.invalid_routes {
+                route.write_text(target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the summary as a JSON value.
+    pub fn to_json(&self) -> JsonValue {
+        let mut summary = JsonValue::new_object();
+        for state in RouteState::all() {
+            summary[state.to_string()] = self.count(*state).into();
+        }
+        summary["total"] = self.total().into();
+        let mut routes = JsonValue::new_array();
+        for route in &self.invalid_routes {
+            let _ = routes.push(route.to_json());
+        }
+        let mut res = JsonValue::new_object();
+        res["summary"] = summary;
+        res["invalid_routes"] = routes;
+        res
+    }
+
+    fn percentage(&self, state: RouteState) -> f64 {
+        if self.total() == 0 {
+            0.
+        }
+        else {
+            self.count(state) as f64 * 100. / self.total() as f64
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+fn origins_json(origins: &[AddressOrigin]) -> JsonValue {
+    let mut res = JsonValue::new_array();
+    for origin in origins {
+        let mut value = JsonValue::new_object();
+        value["asn"] = origin.as_id().to_string().into();
+        value["prefix"] = format!(
+            "{}/{}", origin.address(), origin.address_length()
+        ).into();
+        value["max_length"] = origin.max_length().into();
+        value["ta"] = origin.tal_list().into();
+        let _ = res.push(value);
+    }
+    res
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use rpki::asres::AsId;
+
+    fn make_origin(asn: u32, prefix: &str, max_len: u8) -> AddressOrigin {
+        AddressOrigin::new(AsId::from(asn), prefix.parse().unwrap(), max_len)
+    }
+
+    fn validate(
+        origins: &[AddressOrigin], prefix: &str, asn: u32
+    ) -> RouteValidity {
+        RouteValidity::new(
+            Announcement::new(prefix.parse().unwrap(), AsId::from(asn)),
+            origins
+        )
+    }
+
+    #[test]
+    fn should_validate_routes() {
+        let origins = vec![
+            make_origin(64496, "10.0.0.0/16", 20),
+            make_origin(64497, "10.0.0.0/8", 8),
+            make_origin(0, "192.0.2.0/24", 24),
+            make_origin(64496, "2001:db8::/32", 48),
+        ];
+
+        let res = validate(&origins, "10.0.0.0/18", 64496);
+        assert_eq!(res.state(), RouteState::Valid);
+        assert_eq!(res.matched().len(), 1);
+        assert_eq!(res.unmatched_length().len(), 1);
+
+        let res = validate(&origins, "10.0.0.0/24", 64496);
+        assert_eq!(res.state(), RouteState::Invalid);
+        assert_eq!(res.unmatched_length().len(), 2);
+
+        let res = validate(&origins, "10.0.0.0/8", 64496);
+        assert_eq!(res.state(), RouteState::Invalid);
+        assert_eq!(res.unmatched_as().len(), 1);
+
+        assert_eq!(
+            validate(&origins, "192.0.2.0/24", 0).state(),
+            RouteState::Invalid
+        );
+        assert_eq!(
+            validate(&origins, "198.51.100.0/24", 64496).state(),
+            RouteState::NotFound
+        );
+        assert_eq!(
+            validate(&origins, "2001:db8:1::/48", 64496).state(),
+            RouteState::Valid
+        );
+        assert_eq!(
+            validate(&origins, "10.0.0.0/8", 64497).state(),
+            RouteState::Valid
+        );
+    }
+
+    #[test]
+    fn should_summarize_routes() {
+        let origins: AddressOrigins = vec![
+            make_origin(64496, "10.0.0.0/16", 20),
+            make_origin(64497, "2001:db8::/32", 48),
+        ].into_iter().collect();
+        let announcements: Vec<_> = [
+            ("10.0.0.0/16", 64496), ("10.0.0.0/24", 64496),
+            ("10.0.0.0/16", 64497), ("192.0.2.0/24", 64496),
+            ("2001:db8::/48", 64497),
+        ].iter().map(|&(prefix, asn)| {
+            Announcement::new(prefix.parse().unwrap(), AsId::from(asn))
+        }).collect();
+        let res = ValiditySummary::new(&origins, &announcements);
+        assert_eq!(res.count(RouteState::Valid), 2);
+        assert_eq!(res.count(RouteState::Invalid), 2);
+        assert_eq!(res.count(RouteState::NotFound), 1);
+        assert_eq!(res.total(), 5);
+        assert_eq!(res.invalid_routes()[0].announcement(), announcements[1]);
+        assert_eq!(res.to_json()["summary"]["not-found"], 1);
+    }
+}
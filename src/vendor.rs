@@ -0,0 +1,213 @@
+//! Static route origin tables for router configurations.
+//!
+//! Routers that cannot speak RTR can still perform route origin
+//! validation if the route origins are configured statically. This module
+//! produces such configuration in the syntax of various router vendors.
+//!
+//! For Cisco IOS-XR, [`IosXrWriter`] produces `rpki route` statements for
+//! the `router bgp` section. For Juniper Junos, [`JunosWriter`] produces
+//! `record` statements in the `routing-options validation static`
+//! hierarchy.
+//!
+//! [`IosXrWriter`]: struct.IosXrWriter.html
+//! [`JunosWriter`]: struct.JunosWriter.html
+
+use std::io;
+use rpki::asres::AsId;
+use super::origins::AddressOrigin;
+use super::output::{Metadata, OutputWriter};
+
+
+//------------ IosXrWriter ---------------------------------------------------
+
+/// Writes route origins as static ROAs for Cisco IOS-XR.
+///
+/// Each route origin results in a statement
+/// `rpki route <prefix> max <max-length> origin <asn>`. If `bgp_as` is
+/// given, the statements are wrapped in a `router bgp` section for this
+/// AS. Otherwise only the statements are produced for pasting into an
+/// existing section.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IosXrWriter {
+    bgp_as: Option<AsId>,
+}
+
+impl IosXrWriter {
+    pub fn new(bgp_as: Option<AsId>) -> Self {
+        IosXrWriter { bgp_as }
+    }
+}
+
+impl OutputWriter for IosXrWriter {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        if let Some(as_id) = self.bgp_as {
+            writeln!(target, "router bgp {}", u32::from(as_id))?;
+        }
+        Ok(())
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target, "{}rpki route {}/{} max {} origin {}",
+            if self.bgp_as.is_some() { " " } else { "" },
+            origin.address(), origin.address_length(),
+            origin.max_length(), u32::from(origin.as_id())
+        )
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if self.bgp_as.is_some() {
+            writeln!(target, "!")?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ JunosWriter ---------------------------------------------------
+
+/// Writes route origins as static validation records for Junos.
+///
+/// The output is a complete `routing-options` hierarchy. Each prefix
+/// results in one `record` with a `maximum-length` statement for each
+/// maximum length which in turn contains all the origin AS numbers for
+/// this prefix and maximum length.
+#[derive(Clone, Debug, Default)]
+pub struct JunosWriter {
+    /// The previous route origin.
+    last: Option<AddressOrigin>,
+}
+
+impl JunosWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OutputWriter for JunosWriter {
+    fn begin(
+        &mut self,
+        _metadata: &Metadata,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        self.last = None;
+        writeln!(target, "routing-options {{")?;
+        writeln!(target, "    validation {{")?;
+        writeln!(target, "        static {{")
+    }
+
+    fn entry(
+        &mut self,
+        origin: &AddressOrigin,
+        target: &mut dyn io::Write
+    ) -> Result<(), io::Error> {
+        // The origins are sorted by prefix and then maximum length, so all
+        // we need to do is notice when either changes.
+        let (new_prefix, new_max) = match self.last {
+            Some(ref last) => {
+                if last.prefix() != origin.prefix() {
+                    (true, true)
+                }
+                else {
+                    (false, last.max_length() != origin.max_length())
+                }
+            }
+            None => (true, true)
+        };
+        if self.last.is_some() {
+            if new_max {
+                writeln!(target, "                }}")?;
+            }
+            if new_prefix {
+                writeln!(target, "            }}")?;
+            }
+        }
+        if new_prefix {
+            writeln!(
+                target, "            record {}/{} {{",
+                origin.address(), origin.address_length()
+            )?;
+        }
+        if new_max {
+            writeln!(
+                target, "                maximum-length {} {{",
+                origin.max_length()
+            )?;
+        }
+        writeln!(
+            target,
+            "                    origin-autonomous-system {} \
+             validation-state valid;",
+            u32::from(origin.as_id())
+        )?;
+        self.last = Some(origin.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, target: &mut dyn io::Write) -> Result<(), io::Error> {
+        if self.last.is_some() {
+            writeln!(target, "                }}")?;
+            writeln!(target, "            }}")?;
+        }
+        writeln!(target, "        }}")?;
+        writeln!(target, "    }}")?;
+        writeln!(target, "}}")
+    }
+}
+
+
+//------------ Tests ---------------------------------------------------------
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use super::super::origins::AddressOrigins;
+    use super::super::output::tests::written;
+
+    fn origins() -> AddressOrigins {
+        vec![
+            (64497, "2001:db8::/32", 48),
+            (64496, "10.0.0.0/16", 24),
+            (64497, "10.0.0.0/16", 24),
+            (64496, "10.0.0.0/16", 16),
+            (64496, "192.0.2.0/24", 24),
+        ].into_iter().map(|(as_id, prefix, max_length)| {
+            AddressOrigin::new(
+                AsId::from(as_id), prefix.parse().unwrap(), max_length
+            )
+        }).collect()
+    }
+
+    #[test]
+    fn should_write_iosxr() {
+        let origins = origins();
+        assert_eq!(
+            written(IosXrWriter::new(Some(AsId::from(65000))), &origins),
+            include_str!("../test/vendor/iosxr.txt")
+        );
+        assert_eq!(
+            written(IosXrWriter::new(None), &origins),
+            include_str!("../test/vendor/iosxr-bare.txt")
+        );
+    }
+
+    #[test]
+    fn should_write_junos() {
+        assert_eq!(
+            written(JunosWriter::new(), &origins()),
+            include_str!("../test/vendor/junos.txt")
+        );
+        assert_eq!(
+            written(JunosWriter::new(), &AddressOrigins::new()),
+            include_str!("../test/vendor/junos-empty.txt")
+        );
+    }
+}